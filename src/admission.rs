@@ -0,0 +1,412 @@
+use std::{cmp::Ordering, collections::HashMap, fmt};
+
+use csv::{ReaderBuilder, StringRecord};
+use itertools::Itertools;
+
+use crate::error::{Error, Result};
+use crate::parsing::*;
+
+/// Faculties every student applied to, most wanted first, keyed by student id.
+pub type Preferences = HashMap<String, Vec<String>>;
+
+/// The number of students each faculty admitted in the publication.
+pub fn published_capacities(students: &[StudentData]) -> HashMap<String, usize> {
+    students
+        .iter()
+        .counts_by(|student| student.faculty_id.clone())
+}
+
+/// Reads `faculty id,capacity` rows, with a header.
+pub fn read_capacities(file_name: &str) -> Result<HashMap<String, usize>> {
+    let mut capacities = HashMap::new();
+
+    for (line_number, row) in read_rows(file_name)? {
+        let (Some(faculty_id), Some(capacity)) = (row.first(), row.get(1)) else {
+            continue;
+        };
+
+        let capacity = capacity.parse().map_err(|_| Error::BadNumber {
+            file: String::from(file_name),
+            line: line_number,
+            value: capacity.clone(),
+        })?;
+
+        capacities.insert(faculty_id.clone(), capacity);
+    }
+
+    Ok(capacities)
+}
+
+/// Reads `student id,faculty id,faculty id,...` rows, with a header. The
+/// faculties are listed most wanted first.
+pub fn read_preferences(file_name: &str) -> Result<Preferences> {
+    Ok(read_rows(file_name)?
+        .into_iter()
+        .filter_map(|(_, row)| {
+            let (student_id, faculties) = row.split_first()?;
+
+            Some((
+                student_id.clone(),
+                faculties
+                    .iter()
+                    .filter(|faculty_id| !faculty_id.is_empty())
+                    .cloned()
+                    .collect_vec(),
+            ))
+        })
+        .collect())
+}
+
+fn read_rows(file_name: &str) -> Result<Vec<(usize, Vec<String>)>> {
+    let csv_error = |source| Error::Csv {
+        file: String::from(file_name),
+        source,
+    };
+
+    let mut reader = ReaderBuilder::new()
+        .flexible(true)
+        .from_path(file_name)
+        .map_err(csv_error)?;
+
+    let mut rows = Vec::new();
+
+    while !reader.is_done() {
+        let mut csv_line = StringRecord::new();
+        reader.read_record(&mut csv_line).map_err(csv_error)?;
+
+        let line_number = csv_line.position().map_or(0, |p| p.line() as usize);
+        let row = csv_line
+            .iter()
+            .map(|cell| cell.trim().to_string())
+            .collect_vec();
+
+        if !row.is_empty() {
+            rows.push((line_number, row));
+        }
+    }
+
+    Ok(rows)
+}
+
+/// How a student fares in the simulation compared to the publication.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PlacementChange {
+    Unchanged,
+    /// Admitted to a faculty higher on the student's list.
+    Gained,
+    /// Admitted to a faculty lower on the student's list, or to none.
+    Lost,
+}
+
+impl fmt::Display for PlacementChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PlacementChange::Unchanged => "unchanged",
+            PlacementChange::Gained => "gained",
+            PlacementChange::Lost => "lost",
+        })
+    }
+}
+
+/// Where a student was admitted in the publication and in the simulation.
+#[derive(Debug, Clone)]
+pub struct Admission {
+    pub student_id: String,
+    /// The faculties the student applied to, most wanted first.
+    pub preferences: Vec<String>,
+    pub published_faculty: String,
+    /// Position among the students of the published faculty, from 1.
+    pub published_rank: usize,
+    pub simulated_faculty: Option<String>,
+    /// Position among the students admitted to the simulated faculty, from 1.
+    pub simulated_rank: Option<usize>,
+    /// The score the student was ranked by in the simulated faculty.
+    pub simulated_score: Option<f32>,
+}
+
+impl Admission {
+    fn choice(&self, faculty_id: &str) -> Option<usize> {
+        self.preferences.iter().position(|id| id == faculty_id)
+    }
+
+    pub fn change(&self) -> PlacementChange {
+        let published = self.choice(&self.published_faculty);
+        let simulated = self
+            .simulated_faculty
+            .as_deref()
+            .and_then(|f| self.choice(f));
+
+        match (published, simulated) {
+            (Some(published), Some(simulated)) => match simulated.cmp(&published) {
+                Ordering::Less => PlacementChange::Gained,
+                Ordering::Equal => PlacementChange::Unchanged,
+                Ordering::Greater => PlacementChange::Lost,
+            },
+            (_, None) => PlacementChange::Lost,
+            (None, Some(_)) => PlacementChange::Gained,
+        }
+    }
+}
+
+impl fmt::Display for Admission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} #{} -> ",
+            self.student_id, self.published_faculty, self.published_rank
+        )?;
+
+        match (&self.simulated_faculty, self.simulated_rank) {
+            (Some(faculty), Some(rank)) => write!(f, "{faculty} #{rank}")?,
+            _ => write!(f, "not admitted")?,
+        }
+
+        write!(f, " ({})", self.change())
+    }
+}
+
+/// Admits the students again, ranking them with `score` instead of the
+/// published competitive score.
+///
+/// Students propose to the faculties on their preference lists in order
+/// (deferred acceptance), each faculty keeping the best `capacity` of its
+/// proposals so far. Students without a preference list apply only to the
+/// faculty they were published in, which is also added to the end of every
+/// list that doesn't have it. Faculties without a capacity or a [`Faculty`]
+/// admit no one, and no faculty admits a student `score` has no score for.
+pub fn simulate_admissions(
+    students: &[StudentData],
+    faculties: &HashMap<String, Faculty>,
+    capacities: &HashMap<String, usize>,
+    preferences: &Preferences,
+    score: impl Fn(&StudentData, &Faculty) -> Option<f32>,
+) -> Vec<Admission> {
+    let published_scores = students
+        .iter()
+        .map(|s| s.published_score().unwrap_or(f32::NEG_INFINITY))
+        .collect_vec();
+
+    let mut published_ranks = vec![0; students.len()];
+    for (_, group) in &(0..students.len())
+        .sorted_by(|a, b| {
+            students[*a]
+                .faculty_id
+                .cmp(&students[*b].faculty_id)
+                .then(f32::total_cmp(&published_scores[*b], &published_scores[*a]))
+        })
+        .group_by(|i| &students[*i].faculty_id)
+    {
+        for (rank, i) in group.enumerate() {
+            published_ranks[i] = rank + 1;
+        }
+    }
+
+    let lists = students
+        .iter()
+        .map(|student| {
+            let mut list = preferences.get(&student.id).cloned().unwrap_or_default();
+            if !list.contains(&student.faculty_id) {
+                list.push(student.faculty_id.clone());
+            }
+            list
+        })
+        .collect_vec();
+
+    // ties go to the higher published score
+    let better = |(a, a_score): (usize, f32), (b, b_score): (usize, f32)| {
+        f32::total_cmp(&b_score, &a_score)
+            .then(f32::total_cmp(&published_scores[b], &published_scores[a]))
+            .then(students[a].id.cmp(&students[b].id))
+    };
+
+    let mut held: HashMap<&str, Vec<(usize, f32)>> = HashMap::new();
+    let mut next_choice = vec![0; students.len()];
+    let mut free = (0..students.len()).rev().collect_vec();
+
+    while let Some(student) = free.pop() {
+        let Some(faculty_id) = lists[student].get(next_choice[student]) else {
+            continue;
+        };
+        next_choice[student] += 1;
+
+        let capacity = capacities.get(faculty_id).copied().unwrap_or_default();
+        let proposal = faculties
+            .get(faculty_id)
+            .and_then(|faculty| score(&students[student], faculty));
+
+        let Some(proposal) = proposal.filter(|_| capacity > 0) else {
+            free.push(student);
+            continue;
+        };
+
+        let admitted = held.entry(faculty_id.as_str()).or_default();
+        let position = admitted
+            .binary_search_by(|held| better(*held, (student, proposal)))
+            .unwrap_or_else(|position| position);
+        admitted.insert(position, (student, proposal));
+
+        if admitted.len() > capacity {
+            let (rejected, _) = admitted.pop().unwrap();
+            free.push(rejected);
+        }
+    }
+
+    let mut simulated = vec![None; students.len()];
+    for (faculty_id, admitted) in &held {
+        for (rank, (student, score)) in admitted.iter().enumerate() {
+            simulated[*student] = Some((*faculty_id, rank + 1, *score));
+        }
+    }
+
+    students
+        .iter()
+        .enumerate()
+        .map(|(i, student)| Admission {
+            student_id: student.id.clone(),
+            preferences: lists[i].clone(),
+            published_faculty: student.faculty_id.clone(),
+            published_rank: published_ranks[i],
+            simulated_faculty: simulated[i].map(|(faculty_id, _, _)| String::from(faculty_id)),
+            simulated_rank: simulated[i].map(|(_, rank, _)| rank),
+            simulated_score: simulated[i].map(|(_, _, score)| score),
+        })
+        .collect_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn faculties() -> HashMap<String, Faculty> {
+        ["001", "002"]
+            .map(|id| {
+                let faculty = Faculty {
+                    id: String::from(id),
+                    name: String::new(),
+                    subjects: [true; ALL_SUBJECTS.len()],
+                };
+                (String::from(id), faculty)
+            })
+            .into()
+    }
+
+    /// A student with the published competitive score and the math score
+    /// they are ranked by in the simulation.
+    fn student(id: &str, faculty_id: &str, published: f32, math: Option<f32>) -> StudentData {
+        let mut scores = [None; ALL_SUBJECTS.len()];
+        scores[Subject::Math as usize] = math.map(Score::Equalized);
+
+        StudentData {
+            id: String::from(id),
+            scores,
+            overall_score: published.to_string(),
+            placement: None,
+            faculty_id: String::from(faculty_id),
+            grant: None,
+        }
+    }
+
+    fn simulate(
+        students: &[StudentData],
+        capacities: &[(&str, usize)],
+        preferences: &[(&str, &[&str])],
+    ) -> Vec<Admission> {
+        let capacities = capacities
+            .iter()
+            .map(|(id, capacity)| (String::from(*id), *capacity))
+            .collect();
+        let preferences = preferences
+            .iter()
+            .map(|(id, list)| {
+                (
+                    String::from(*id),
+                    list.iter().map(|f| f.to_string()).collect(),
+                )
+            })
+            .collect();
+
+        simulate_admissions(
+            students,
+            &faculties(),
+            &capacities,
+            &preferences,
+            |student, _| student.scores[Subject::Math as usize]?.equalized(),
+        )
+    }
+
+    fn placements(admissions: &[Admission]) -> Vec<(Option<&str>, Option<usize>, PlacementChange)> {
+        admissions
+            .iter()
+            .map(|a| (a.simulated_faculty.as_deref(), a.simulated_rank, a.change()))
+            .collect_vec()
+    }
+
+    #[test]
+    fn students_are_admitted_by_deferred_acceptance() {
+        let students = [
+            student("1", "001", 80.0, Some(90.0)),
+            student("2", "002", 90.0, Some(70.0)),
+            student("3", "001", 60.0, Some(50.0)),
+        ];
+        // 1 takes 2's place at 002, 2 takes 3's at 001
+        let admissions = simulate(
+            &students,
+            &[("001", 1), ("002", 1)],
+            &[("1", &["002", "001"]), ("2", &["002", "001"])],
+        );
+
+        assert_eq!(
+            placements(&admissions),
+            [
+                (Some("002"), Some(1), PlacementChange::Gained),
+                (Some("001"), Some(1), PlacementChange::Lost),
+                (None, None, PlacementChange::Lost),
+            ]
+        );
+        // the list of a student without preferences is their faculty
+        assert_eq!(admissions[2].preferences, ["001"]);
+        assert_eq!(
+            admissions.iter().map(|a| a.published_rank).collect_vec(),
+            [1, 1, 2]
+        );
+    }
+
+    #[test]
+    fn ties_go_to_the_higher_published_score() {
+        let students = [
+            student("1", "001", 70.0, Some(50.0)),
+            student("2", "001", 80.0, Some(50.0)),
+        ];
+        let admissions = simulate(&students, &[("001", 1)], &[]);
+
+        assert_eq!(
+            placements(&admissions),
+            [
+                (None, None, PlacementChange::Lost),
+                (Some("001"), Some(1), PlacementChange::Unchanged),
+            ]
+        );
+        assert_eq!(admissions[1].simulated_score, Some(50.0));
+    }
+
+    #[test]
+    fn no_capacity_or_score_admits_no_one() {
+        let students = [
+            student("1", "001", 70.0, Some(50.0)),
+            student("2", "002", 80.0, None),
+            student("3", "003", 90.0, Some(60.0)),
+        ];
+        // 003 isn't a known faculty
+        let admissions = simulate(&students, &[("002", 5), ("003", 5)], &[]);
+
+        assert!(admissions.iter().all(|a| a.simulated_faculty.is_none()));
+        assert_eq!(
+            published_capacities(&students),
+            HashMap::from([
+                (String::from("001"), 1),
+                (String::from("002"), 1),
+                (String::from("003"), 1),
+            ])
+        );
+    }
+}
@@ -0,0 +1,114 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::error::{Error, Result};
+use crate::extract::*;
+
+/// Bumped whenever the extraction changes what it makes of the same PDF, so
+/// that stale TSV files aren't picked up from the cache.
+const EXTRACTION_VERSION: &str = "publication-tsv-1";
+
+/// Hex SHA-256 of the parts, one after the other.
+pub fn content_hash<T: AsRef<[u8]>>(parts: &[T]) -> String {
+    let mut hasher = Sha256::new();
+
+    for part in parts {
+        hasher.update(part.as_ref());
+    }
+
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// A directory of files named after the hash of what they were made from.
+#[derive(Debug, Clone)]
+pub struct Cache {
+    directory: PathBuf,
+}
+
+impl Cache {
+    /// Opens the cache in `directory`, creating it if needed.
+    pub fn new(directory: impl Into<PathBuf>) -> Result<Self> {
+        let directory = directory.into();
+
+        fs::create_dir_all(&directory).map_err(|source| Error::Io {
+            file: directory.to_string_lossy().into_owned(),
+            source,
+        })?;
+
+        Ok(Self { directory })
+    }
+
+    /// `$XDG_CACHE_HOME/descale-and-sort`, or `~/.cache/descale-and-sort`.
+    pub fn default_directory() -> PathBuf {
+        let base = env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))
+            .unwrap_or_else(|| PathBuf::from("."));
+
+        base.join("descale-and-sort")
+    }
+
+    pub fn path(&self, key: &str, extension: &str) -> PathBuf {
+        self.directory.join(format!("{key}.{extension}"))
+    }
+
+    /// The cached file, if there is one.
+    pub fn get(&self, key: &str, extension: &str) -> Option<PathBuf> {
+        let path = self.path(key, extension);
+
+        path.exists().then_some(path)
+    }
+
+    /// Copies `file` into the cache.
+    pub fn put(&self, key: &str, extension: &str, file: &Path) -> Result<PathBuf> {
+        let path = self.path(key, extension);
+
+        fs::copy(file, &path).map_err(|source| Error::Io {
+            file: path.to_string_lossy().into_owned(),
+            source,
+        })?;
+
+        Ok(path)
+    }
+
+    /// The TSV extracted from the publication, extracting it only if this PDF
+    /// hasn't been seen before.
+    pub fn publication_tsv(&self, input_file: &str) -> Result<PathBuf> {
+        let pdf = fs::read(input_file).map_err(|source| Error::Io {
+            file: String::from(input_file),
+            source,
+        })?;
+
+        let key = content_hash(&[
+            EXTRACTION_VERSION.as_bytes(),
+            format!("{PUBLICATION_AREA:?}").as_bytes(),
+            &pdf,
+        ]);
+
+        if let Some(path) = self.get(&key, "tsv") {
+            println!("using the cached extraction of {input_file}.");
+            return Ok(path);
+        }
+
+        println!("extracting data from {input_file}...");
+
+        let path = self.path(&key, "tsv");
+        let tsv = extract_publication_tsv(input_file, PUBLICATION_AREA)?;
+        fs::write(&path, tsv).map_err(|source| Error::Io {
+            file: path.to_string_lossy().into_owned(),
+            source,
+        })?;
+
+        println!("done.");
+
+        Ok(path)
+    }
+}
@@ -0,0 +1,465 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+};
+
+use csv::WriterBuilder;
+use itertools::Itertools;
+
+use crate::dataset::Dataset;
+use crate::error::{Error, Result};
+use crate::parsing::*;
+
+/// Where the equalized scores of a group of students stand in one year.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScoreSummary {
+    pub students: usize,
+    /// The lowest score among the students, all of them admitted. It isn't a
+    /// threshold anyone set, a single student admitted with a low score in a
+    /// subject others made up for lowers it.
+    pub cutoff: f32,
+    pub mean: f32,
+    pub median: f32,
+    /// The lowest score that got a grant, `None` if no one got one.
+    pub grant_threshold: Option<f32>,
+}
+
+/// The median of the sorted values, `None` if there are none.
+pub fn median(sorted: &[f32]) -> Option<f32> {
+    let middle = sorted.len() / 2;
+
+    if sorted.len().is_multiple_of(2) {
+        Some((sorted.get(middle.checked_sub(1)?)? + sorted[middle]) / 2.0)
+    } else {
+        Some(sorted[middle])
+    }
+}
+
+impl ScoreSummary {
+    /// `None` if there are no scores.
+    pub fn new(scores: &[(f32, Grant)]) -> Option<Self> {
+        let sorted = scores
+            .iter()
+            .map(|(score, _)| *score)
+            .sorted_by(f32::total_cmp)
+            .collect_vec();
+
+        Some(Self {
+            students: sorted.len(),
+            cutoff: *sorted.first()?,
+            mean: sorted.iter().sum::<f32>() / sorted.len() as f32,
+            median: median(&sorted)?,
+            grant_threshold: scores
+                .iter()
+                .filter(|(_, grant)| *grant != Grant::Zero)
+                .map(|(score, _)| *score)
+                .min_by(f32::total_cmp),
+        })
+    }
+
+    pub fn change_since(&self, previous: &ScoreSummary) -> ScoreChange {
+        ScoreChange {
+            cutoff: self.cutoff - previous.cutoff,
+            median: self.median - previous.median,
+            grant_threshold: self
+                .grant_threshold
+                .zip(previous.grant_threshold)
+                .map(|(threshold, previous)| threshold - previous),
+        }
+    }
+}
+
+/// How a [`ScoreSummary`] moved since the year before.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScoreChange {
+    pub cutoff: f32,
+    pub median: f32,
+    /// `None` unless both years had grants.
+    pub grant_threshold: Option<f32>,
+}
+
+impl fmt::Display for ScoreSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} students, cutoff {:.1}, median {:.1}",
+            self.students, self.cutoff, self.median
+        )?;
+
+        match self.grant_threshold {
+            Some(threshold) => write!(f, ", grant from {threshold:.1}"),
+            None => write!(f, ", no grants"),
+        }
+    }
+}
+
+/// Summaries of the equalized scores of every subject, indexed by subject.
+pub fn subject_summaries(students: &[StudentData]) -> [Option<ScoreSummary>; ALL_SUBJECTS.len()] {
+    ALL_SUBJECTS.map(|subject| {
+        let scores = students
+            .iter()
+            .filter_map(|s| {
+                let score = s.scores[subject as usize]?.equalized()?;
+                Some((score, s.grant.unwrap_or(Grant::Zero)))
+            })
+            .collect_vec();
+
+        ScoreSummary::new(&scores)
+    })
+}
+
+/// Summaries of the equalized scores of the subjects every faculty counts,
+/// over the students admitted to it, indexed by subject. Faculties missing
+/// from `faculties` are left out.
+pub fn faculty_summaries(
+    students: &[StudentData],
+    faculties: &HashMap<String, Faculty>,
+) -> HashMap<String, [Option<ScoreSummary>; ALL_SUBJECTS.len()]> {
+    students
+        .iter()
+        .into_group_map_by(|s| s.faculty_id.as_str())
+        .into_iter()
+        .filter_map(|(faculty_id, admitted)| {
+            let faculty = faculties.get(faculty_id)?;
+            let admitted = admitted.into_iter().cloned().collect_vec();
+
+            let mut summaries = subject_summaries(&admitted);
+            for subject in ALL_SUBJECTS {
+                if !faculty.subjects[subject as usize] {
+                    summaries[subject as usize] = None;
+                }
+            }
+
+            Some((String::from(faculty_id), summaries))
+        })
+        .collect()
+}
+
+/// How a subject fared over the years, among all the students or the ones
+/// admitted to a faculty.
+#[derive(Debug, Clone)]
+pub struct YearComparison {
+    /// The faculty's id, `None` for all the students.
+    pub faculty_id: Option<String>,
+    /// The faculty's name, as of the latest year, empty for all the students.
+    pub name: String,
+    pub subject: Subject,
+    pub years: BTreeMap<u16, ScoreSummary>,
+}
+
+impl YearComparison {
+    /// Every year's summary with the one of the year before it, if there is
+    /// one.
+    pub fn with_previous(
+        &self,
+    ) -> impl Iterator<Item = (u16, &ScoreSummary, Option<&ScoreSummary>)> {
+        let previous = std::iter::once(None).chain(self.years.values().map(Some));
+
+        self.years
+            .iter()
+            .zip(previous)
+            .map(|((year, summary), previous)| (*year, summary, previous))
+    }
+
+    /// Every year's summary with its change since the year before, if there
+    /// is one.
+    pub fn with_changes(&self) -> impl Iterator<Item = (u16, &ScoreSummary, Option<ScoreChange>)> {
+        self.with_previous().map(|(year, summary, previous)| {
+            (
+                year,
+                summary,
+                previous.map(|previous| summary.change_since(previous)),
+            )
+        })
+    }
+}
+
+impl fmt::Display for YearComparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.faculty_id {
+            Some(faculty_id) => write!(f, "{} ({faculty_id}), {}:", self.name, self.subject)?,
+            None => write!(f, "{}:", self.subject)?,
+        }
+
+        for (year, summary, change) in self.with_changes() {
+            write!(f, "\n\t{year}: {summary}")?;
+
+            if let Some(change) = change {
+                write!(
+                    f,
+                    " (cutoff {:+.1}, median {:+.1})",
+                    change.cutoff, change.median
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Compares the subjects over the years, among all the students and the ones
+/// admitted to every faculty, faculties being matched by id.
+///
+/// Faculties are compared subject by subject, as the weights of a competitive
+/// score fitted to each year's publication would differ from year to year
+/// and the scores couldn't be told apart from the weights.
+pub fn compare_years(datasets: &BTreeMap<u16, Dataset>) -> Vec<YearComparison> {
+    let mut subjects = ALL_SUBJECTS.map(|subject| YearComparison {
+        faculty_id: None,
+        name: String::new(),
+        subject,
+        years: BTreeMap::new(),
+    });
+    let mut faculties: BTreeMap<(u64, String, usize), YearComparison> = BTreeMap::new();
+
+    for (year, dataset) in datasets {
+        for (subject_index, summary) in subject_summaries(&dataset.students).into_iter().enumerate()
+        {
+            if let Some(summary) = summary {
+                subjects[subject_index].years.insert(*year, summary);
+            }
+        }
+
+        for (faculty_id, summaries) in faculty_summaries(&dataset.students, &dataset.faculties) {
+            let name = &dataset.faculties[&faculty_id].name;
+
+            for (subject, summary) in ALL_SUBJECTS.into_iter().zip(summaries) {
+                let Some(summary) = summary else {
+                    continue;
+                };
+
+                // ids have up to eleven digits, too many for an f32 to tell apart
+                let key = (
+                    faculty_id.parse().unwrap_or(u64::MAX),
+                    faculty_id.clone(),
+                    subject as usize,
+                );
+                let comparison = faculties.entry(key).or_insert_with(|| YearComparison {
+                    faculty_id: Some(faculty_id.clone()),
+                    name: String::new(),
+                    subject,
+                    years: BTreeMap::new(),
+                });
+
+                comparison.name.clone_from(name);
+                comparison.years.insert(*year, summary);
+            }
+        }
+    }
+
+    subjects
+        .into_iter()
+        .filter(|comparison| !comparison.years.is_empty())
+        .chain(faculties.into_values())
+        .collect_vec()
+}
+
+/// Writes the comparisons as CSV, a row per subject, faculty and year, with
+/// the changes since the year before. The faculty is empty for all the
+/// students.
+pub fn write_comparisons(file_name: &str, comparisons: &[YearComparison]) -> Result<()> {
+    let csv_error = |source| Error::Csv {
+        file: String::from(file_name),
+        source,
+    };
+
+    let mut writer = WriterBuilder::new()
+        .from_path(file_name)
+        .map_err(csv_error)?;
+
+    writer
+        .write_record([
+            "faculty_id",
+            "name",
+            "subject",
+            "year",
+            "students",
+            "cutoff",
+            "median",
+            "grant_threshold",
+            "cutoff_change",
+            "median_change",
+            "grant_threshold_change",
+        ])
+        .map_err(csv_error)?;
+
+    let optional = |value: Option<f32>| value.map(|v| format!("{v:.1}")).unwrap_or_default();
+
+    for comparison in comparisons {
+        for (year, summary, change) in comparison.with_changes() {
+            writer
+                .write_record([
+                    comparison.faculty_id.clone().unwrap_or_default(),
+                    comparison.name.clone(),
+                    String::from(comparison.subject.key()),
+                    year.to_string(),
+                    summary.students.to_string(),
+                    format!("{:.1}", summary.cutoff),
+                    format!("{:.1}", summary.median),
+                    optional(summary.grant_threshold),
+                    optional(change.map(|c| c.cutoff)),
+                    optional(change.map(|c| c.median)),
+                    optional(change.and_then(|c| c.grant_threshold)),
+                ])
+                .map_err(csv_error)?;
+        }
+    }
+
+    writer.flush().map_err(|source| Error::Io {
+        file: String::from(file_name),
+        source,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::descaling::DescalingModel;
+
+    const FACULTY_ID: &str = "00101015";
+
+    fn student(math: f32, english: f32, published: f32) -> StudentData {
+        let mut scores = [None; ALL_SUBJECTS.len()];
+        scores[Subject::Math as usize] = Some(Score::Equalized(math));
+        scores[Subject::English as usize] = Some(Score::Equalized(english));
+
+        StudentData {
+            id: String::new(),
+            scores,
+            overall_score: published.to_string(),
+            placement: None,
+            faculty_id: String::from(FACULTY_ID),
+            grant: None,
+        }
+    }
+
+    fn dataset(students: Vec<StudentData>) -> Dataset {
+        let mut subjects = [false; ALL_SUBJECTS.len()];
+        subjects[Subject::Math as usize] = true;
+
+        let faculty = Faculty {
+            id: String::from(FACULTY_ID),
+            name: String::from("Mathematics"),
+            subjects,
+        };
+
+        Dataset {
+            year: None,
+            model: DescalingModel::default(),
+            students,
+            schools: HashMap::new(),
+            faculties: HashMap::from([(String::from(FACULTY_ID), faculty)]),
+        }
+    }
+
+    #[test]
+    fn faculties_are_compared_by_the_subjects_they_count() {
+        // the same students, published with other weights the year after
+        let scores = [(40.0, 70.0), (60.0, 50.0), (80.0, 90.0), (90.0, 30.0)];
+        let datasets = BTreeMap::from([
+            (
+                2022,
+                dataset(scores.map(|(m, e)| student(m, e, 2.0 * m + e)).to_vec()),
+            ),
+            (
+                2023,
+                dataset(scores.map(|(m, e)| student(m, e, m + 3.0 * e)).to_vec()),
+            ),
+        ]);
+        let comparisons = compare_years(&datasets);
+
+        let faculty = comparisons
+            .iter()
+            .filter(|c| c.faculty_id.as_deref() == Some(FACULTY_ID))
+            .exactly_one()
+            .unwrap();
+        assert_eq!(faculty.subject, Subject::Math);
+        assert_eq!(faculty.name, "Mathematics");
+
+        let expected = ScoreSummary {
+            students: 4,
+            cutoff: 40.0,
+            mean: 67.5,
+            median: 70.0,
+            grant_threshold: None,
+        };
+        assert_eq!(faculty.years.values().collect_vec(), [&expected, &expected]);
+    }
+
+    #[test]
+    fn changes_are_since_the_year_before() {
+        let year = |scores: [(f32, Option<Grant>); 3]| {
+            dataset(
+                scores
+                    .map(|(math, grant)| StudentData {
+                        grant,
+                        ..student(math, math, math)
+                    })
+                    .to_vec(),
+            )
+        };
+        let datasets = BTreeMap::from([
+            (
+                2021,
+                year([(40.0, None), (60.0, None), (80.0, Some(Grant::Hundred))]),
+            ),
+            (
+                2022,
+                year([
+                    (50.0, None),
+                    (70.0, Some(Grant::Fifty)),
+                    (90.0, Some(Grant::Hundred)),
+                ]),
+            ),
+            // no grants
+            (
+                2023,
+                year([(50.0, None), (60.0, None), (70.0, Some(Grant::Zero))]),
+            ),
+        ]);
+        let comparisons = compare_years(&datasets);
+
+        let math = comparisons
+            .iter()
+            .find(|c| c.faculty_id.is_none() && c.subject == Subject::Math)
+            .unwrap();
+
+        let previous_years = math
+            .with_previous()
+            .map(|(year, _, previous)| (year, previous.map(|p| p.cutoff)))
+            .collect_vec();
+        assert_eq!(
+            previous_years,
+            [(2021, None), (2022, Some(40.0)), (2023, Some(50.0))]
+        );
+
+        let changes = math
+            .with_changes()
+            .map(|(year, summary, change)| (year, summary.grant_threshold, change))
+            .collect_vec();
+        assert_eq!(
+            changes,
+            [
+                (2021, Some(80.0), None),
+                (
+                    2022,
+                    Some(70.0),
+                    Some(ScoreChange {
+                        cutoff: 10.0,
+                        median: 10.0,
+                        grant_threshold: Some(-10.0),
+                    })
+                ),
+                (
+                    2023,
+                    None,
+                    Some(ScoreChange {
+                        cutoff: 0.0,
+                        median: -10.0,
+                        grant_threshold: None,
+                    })
+                ),
+            ]
+        );
+    }
+}
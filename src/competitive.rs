@@ -0,0 +1,514 @@
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use csv::{ReaderBuilder, StringRecord};
+use itertools::Itertools;
+
+use crate::error::{Error, Result};
+use crate::parsing::*;
+
+/// Published competitive scores further than this from the one recomputed
+/// from the subject scores are reported as inconsistent. Every score is
+/// rounded to a tenth, which with coefficients of a few points adds up to
+/// less than this.
+pub const CONSISTENCY_TOLERANCE: f32 = 1.0;
+
+/// Weights are fitted a subject at a time only to faculties with at least
+/// this many students per subject.
+pub const MIN_STUDENTS_PER_WEIGHT: usize = 5;
+
+/// Which competitive score students are ranked by.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Ranking {
+    /// The published one, made of scaled scores.
+    #[default]
+    Published,
+    /// The one recomputed from equalized scores with the faculty weights.
+    Equalized,
+}
+
+impl fmt::Display for Ranking {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Ranking::Published => "published",
+            Ranking::Equalized => "equalized",
+        })
+    }
+}
+
+impl FromStr for Ranking {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "published" => Ok(Ranking::Published),
+            "equalized" => Ok(Ranking::Equalized),
+            _ => Err(format!(
+                "unknown ranking `{s}`, expected `published` or `equalized`"
+            )),
+        }
+    }
+}
+
+/// Where the weights of a faculty come from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WeightSource {
+    /// Read from a weights file.
+    Configured,
+    /// Non-negative least squares fit of the published scores, a weight per
+    /// subject.
+    Fitted,
+    /// Least squares fit with one weight for every subject, for faculties
+    /// with too few students to tell the subjects apart or whose fitted
+    /// weights don't reproduce the published scores.
+    Uniform,
+}
+
+impl fmt::Display for WeightSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            WeightSource::Configured => "configured",
+            WeightSource::Fitted => "fitted",
+            WeightSource::Uniform => "uniform",
+        })
+    }
+}
+
+/// The coefficients a faculty multiplies the subject scores with to get the
+/// competitive score.
+#[derive(Debug, Clone)]
+pub struct FacultyWeights {
+    pub faculty_id: String,
+    /// Indexed by subject, `None` for subjects the faculty doesn't count.
+    pub weights: [Option<f32>; ALL_SUBJECTS.len()],
+    pub source: WeightSource,
+    /// Number of students the weights were checked against.
+    pub students: usize,
+    /// Root mean square difference between the published and the recomputed
+    /// competitive scores of those students.
+    pub residual: f32,
+}
+
+impl FacultyWeights {
+    /// The competitive score of the student, from the equalized scores or
+    /// from the scaled ones. `None` if the student lacks one of them.
+    pub fn competitive_score(&self, student: &StudentData, from_equalized: bool) -> Option<f32> {
+        self.weights
+            .iter()
+            .enumerate()
+            .filter_map(|(subject_index, weight)| Some((subject_index, (*weight)?)))
+            .map(|(subject_index, weight)| {
+                let score = student.scores[subject_index]?;
+                let value = if from_equalized {
+                    score.equalized()
+                } else {
+                    score.scaled()
+                };
+
+                Some(weight * value?)
+            })
+            .sum()
+    }
+
+    fn with_residual(mut self, students: &[&StudentData]) -> Self {
+        let differences = students
+            .iter()
+            .filter_map(|s| Some(s.published_score()? - self.competitive_score(s, false)?))
+            .collect_vec();
+
+        self.students = differences.len();
+        self.residual = if differences.is_empty() {
+            0.0
+        } else {
+            (differences.iter().map(|d| d * d).sum::<f32>() / differences.len() as f32).sqrt()
+        };
+
+        self
+    }
+}
+
+impl fmt::Display for FacultyWeights {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} ({}, {} students, residual {:.2})",
+            self.faculty_id,
+            ALL_SUBJECTS
+                .iter()
+                .filter_map(|subject| Some(format!(
+                    "{subject} {:.3}",
+                    self.weights[*subject as usize]?
+                )))
+                .join(", "),
+            self.source,
+            self.students,
+            self.residual,
+        )
+    }
+}
+
+/// Solves the square system by Gaussian elimination, `None` if it's singular.
+fn solve(mut matrix: Vec<Vec<f64>>, mut rhs: Vec<f64>) -> Option<Vec<f64>> {
+    let n = rhs.len();
+    let scale = matrix
+        .iter()
+        .flatten()
+        .fold(0.0, |max: f64, x| max.max(x.abs()));
+
+    for column in 0..n {
+        let pivot = (column..n)
+            .max_by(|a, b| f64::total_cmp(&matrix[*a][column].abs(), &matrix[*b][column].abs()))?;
+
+        if matrix[pivot][column].abs() <= 1e-9 * scale {
+            return None;
+        }
+
+        matrix.swap(column, pivot);
+        rhs.swap(column, pivot);
+
+        let (above, below) = matrix.split_at_mut(column + 1);
+        let pivot_row = &above[column];
+
+        for (row, rhs_row) in below.iter_mut().zip(column + 1..n) {
+            let factor = row[column] / pivot_row[column];
+            for (x, pivot_x) in row[column..].iter_mut().zip(&pivot_row[column..]) {
+                *x -= factor * pivot_x;
+            }
+            rhs[rhs_row] -= factor * rhs[column];
+        }
+    }
+
+    let mut solution = vec![0.0; n];
+    for row in (0..n).rev() {
+        let known = (row + 1..n)
+            .map(|k| matrix[row][k] * solution[k])
+            .sum::<f64>();
+        solution[row] = (rhs[row] - known) / matrix[row][row];
+    }
+
+    Some(solution)
+}
+
+/// Root mean square of how far the scores the weights give are from the
+/// published ones.
+fn fit_residual(rows: &[(Vec<f64>, f64)], coefficients: &[f64]) -> f64 {
+    let squares = rows.iter().map(|(x, y)| {
+        let recomputed = x.iter().zip(coefficients).map(|(x, c)| x * c).sum::<f64>();
+        (y - recomputed).powi(2)
+    });
+
+    (squares.sum::<f64>() / rows.len() as f64).sqrt()
+}
+
+/// Least squares coefficients of the columns, none of them negative. Every
+/// subset of the columns is fitted on its own, the others being left at
+/// zero, faculties count few enough subjects for that. `None` if the
+/// students can't tell the columns apart, e.g. when they all scored the
+/// same, or if no subset has a non-negative solution.
+fn non_negative_least_squares(rows: &[(Vec<f64>, f64)], columns: usize) -> Option<Vec<f64>> {
+    let all_columns = (1_usize << columns) - 1;
+    subset_least_squares(rows, columns, all_columns)?;
+
+    (1..=all_columns)
+        .filter_map(|subset| {
+            let solution = subset_least_squares(rows, columns, subset)?;
+            if solution.iter().any(|c| *c < 0.0) {
+                return None;
+            }
+
+            Some(solution)
+        })
+        .min_by(|a, b| f64::total_cmp(&fit_residual(rows, a), &fit_residual(rows, b)))
+}
+
+/// Least squares coefficients of the columns in the `subset` bit mask, the
+/// others left at zero. `None` if the normal equations are singular.
+fn subset_least_squares(
+    rows: &[(Vec<f64>, f64)],
+    columns: usize,
+    subset: usize,
+) -> Option<Vec<f64>> {
+    let kept = (0..columns)
+        .filter(|column| subset & (1 << column) != 0)
+        .collect_vec();
+
+    let normal_matrix = kept
+        .iter()
+        .map(|a| {
+            kept.iter()
+                .map(|b| rows.iter().map(|(x, _)| x[*a] * x[*b]).sum())
+                .collect_vec()
+        })
+        .collect_vec();
+    let normal_rhs = kept
+        .iter()
+        .map(|a| rows.iter().map(|(x, y)| x[*a] * y).sum())
+        .collect_vec();
+
+    let solution = solve(normal_matrix, normal_rhs)?;
+    let mut coefficients = vec![0.0; columns];
+    for (column, c) in kept.iter().zip(solution) {
+        coefficients[*column] = c;
+    }
+
+    Some(coefficients)
+}
+
+/// Fits the weights of a faculty to the published competitive scores of its
+/// students, a weight per subject when there are [`MIN_STUDENTS_PER_WEIGHT`]
+/// students per subject and the fit reproduces their scores within
+/// [`CONSISTENCY_TOLERANCE`], a single weight for every subject otherwise.
+/// `None` if none of the students has every subject of the faculty and a
+/// published score.
+pub fn fit_faculty_weights(faculty: &Faculty, students: &[&StudentData]) -> Option<FacultyWeights> {
+    let subjects = ALL_SUBJECTS
+        .iter()
+        .filter(|subject| faculty.subjects[**subject as usize])
+        .map(|subject| *subject as usize)
+        .collect_vec();
+
+    let rows = students
+        .iter()
+        .filter_map(|student| {
+            let scores = subjects
+                .iter()
+                .map(|i| Some(student.scores[*i]?.scaled()? as f64))
+                .collect::<Option<Vec<_>>>()?;
+
+            Some((scores, student.published_score()? as f64))
+        })
+        .collect_vec();
+
+    if subjects.is_empty() || rows.is_empty() {
+        return None;
+    }
+
+    let fitted = if rows.len() >= MIN_STUDENTS_PER_WEIGHT * subjects.len() {
+        non_negative_least_squares(&rows, subjects.len())
+            .filter(|c| fit_residual(&rows, c) <= CONSISTENCY_TOLERANCE as f64)
+    } else {
+        None
+    };
+
+    let (source, coefficients) = match fitted {
+        Some(coefficients) => (WeightSource::Fitted, coefficients),
+        None => {
+            let totals = rows.iter().map(|(x, y)| (x.iter().sum::<f64>(), *y));
+            let (xy, xx) = totals.fold((0.0, 0.0), |(xy, xx), (x, y)| (xy + x * y, xx + x * x));
+
+            (WeightSource::Uniform, vec![xy / xx; subjects.len()])
+        }
+    };
+
+    let mut weights = [None; ALL_SUBJECTS.len()];
+    for (subject_index, coefficient) in subjects.iter().zip(coefficients) {
+        weights[*subject_index] = Some(coefficient as f32);
+    }
+
+    Some(
+        FacultyWeights {
+            faculty_id: faculty.id.clone(),
+            weights,
+            source,
+            students: 0,
+            residual: 0.0,
+        }
+        .with_residual(students),
+    )
+}
+
+/// Reads `faculty id,subject,weight` rows, with a header. Subjects go by
+/// their name or their [`Subject::key`].
+pub fn read_weights(file_name: &str) -> Result<HashMap<String, [Option<f32>; ALL_SUBJECTS.len()]>> {
+    let mut weights = HashMap::new();
+
+    let csv_error = |source| Error::Csv {
+        file: String::from(file_name),
+        source,
+    };
+
+    let mut reader = ReaderBuilder::new()
+        .from_path(file_name)
+        .map_err(csv_error)?;
+
+    while !reader.is_done() {
+        let mut csv_line = StringRecord::new();
+        reader.read_record(&mut csv_line).map_err(csv_error)?;
+
+        let line_number = csv_line.position().map_or(0, |p| p.line() as usize);
+        let csv_line = csv_line.iter().map(str::trim).collect_vec();
+
+        let [faculty_id, subject, weight] = csv_line[..] else {
+            continue;
+        };
+
+        let Some(subject) = Subject::from(subject).or_else(|| Subject::from_key(subject)) else {
+            return Err(Error::UnknownSubject {
+                file: String::from(file_name),
+                line: line_number,
+                subject: String::from(subject),
+            });
+        };
+
+        let weight = weight.parse().map_err(|_| Error::BadNumber {
+            file: String::from(file_name),
+            line: line_number,
+            value: String::from(weight),
+        })?;
+
+        weights
+            .entry(String::from(faculty_id))
+            .or_insert([None; ALL_SUBJECTS.len()])[subject as usize] = Some(weight);
+    }
+
+    Ok(weights)
+}
+
+/// The weights of every faculty: the configured ones where there are any,
+/// fitted to the published scores elsewhere.
+pub fn faculty_weights(
+    students: &[StudentData],
+    faculties: &HashMap<String, Faculty>,
+    configured: &HashMap<String, [Option<f32>; ALL_SUBJECTS.len()]>,
+) -> HashMap<String, FacultyWeights> {
+    let by_faculty = students.iter().into_group_map_by(|s| s.faculty_id.as_str());
+
+    faculties
+        .values()
+        .filter_map(|faculty| {
+            let students = by_faculty
+                .get(faculty.id.as_str())
+                .map_or(&[][..], Vec::as_slice);
+
+            let weights = match configured.get(&faculty.id) {
+                Some(weights) => FacultyWeights {
+                    faculty_id: faculty.id.clone(),
+                    weights: *weights,
+                    source: WeightSource::Configured,
+                    students: 0,
+                    residual: 0.0,
+                }
+                .with_residual(students),
+                None => fit_faculty_weights(faculty, students)?,
+            };
+
+            Some((faculty.id.clone(), weights))
+        })
+        .collect()
+}
+
+/// Students whose published competitive score is further than
+/// [`CONSISTENCY_TOLERANCE`] from the one recomputed with the weights of their
+/// faculty, with the difference.
+pub fn inconsistent_scores<'a>(
+    students: &'a [StudentData],
+    weights: &HashMap<String, FacultyWeights>,
+) -> Vec<(&'a StudentData, f32)> {
+    students
+        .iter()
+        .filter_map(|student| {
+            let recomputed = weights
+                .get(&student.faculty_id)?
+                .competitive_score(student, false)?;
+            let difference = student.published_score()? - recomputed;
+
+            (difference.abs() > CONSISTENCY_TOLERANCE).then_some((student, difference))
+        })
+        .collect_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn faculty() -> Faculty {
+        let mut subjects = [false; ALL_SUBJECTS.len()];
+        subjects[Subject::Math as usize] = true;
+        subjects[Subject::English as usize] = true;
+
+        Faculty {
+            id: String::from("00101015"),
+            name: String::new(),
+            subjects,
+        }
+    }
+
+    /// A student of [`faculty`] with the scaled scores in math and English.
+    fn student(math: f32, english: f32, published: f32) -> StudentData {
+        let mut scores = [None; ALL_SUBJECTS.len()];
+        scores[Subject::Math as usize] = Some(Score::Scaled(math));
+        scores[Subject::English as usize] = Some(Score::Scaled(english));
+
+        StudentData {
+            id: String::new(),
+            scores,
+            overall_score: published.to_string(),
+            placement: None,
+            faculty_id: String::from("00101015"),
+            grant: None,
+        }
+    }
+
+    fn fit(students: &[StudentData]) -> FacultyWeights {
+        fit_faculty_weights(&faculty(), &students.iter().collect_vec()).unwrap()
+    }
+
+    fn weight(weights: &FacultyWeights, subject: Subject) -> f32 {
+        weights.weights[subject as usize].unwrap()
+    }
+
+    #[test]
+    fn known_weights_are_recovered() {
+        let students = (0..12)
+            .map(|i| {
+                let (math, english) = (120.0 + 7.0 * i as f32, 180.0 - (i * i % 11) as f32);
+                student(math, english, 2.0 * math + 3.0 * english)
+            })
+            .collect_vec();
+        let weights = fit(&students);
+
+        assert_eq!(weights.source, WeightSource::Fitted);
+        assert!((weight(&weights, Subject::Math) - 2.0).abs() < 1e-3);
+        assert!((weight(&weights, Subject::English) - 3.0).abs() < 1e-3);
+        assert!(weights.residual < 0.01);
+    }
+
+    #[test]
+    fn degenerate_cohorts_get_uniform_weights() {
+        // too few students for a weight per subject
+        let few = [
+            student(150.0, 160.0, 620.0),
+            student(140.0, 170.0, 600.0),
+            student(130.0, 120.0, 500.0),
+        ];
+        // every student the same, the subjects can't be told apart
+        let same = vec![student(150.0, 160.0, 620.0); 12];
+
+        for students in [&few[..], &same[..]] {
+            let weights = fit(students);
+
+            assert_eq!(weights.source, WeightSource::Uniform);
+            assert_eq!(
+                weight(&weights, Subject::Math),
+                weight(&weights, Subject::English)
+            );
+            assert!(weight(&weights, Subject::Math) > 0.0);
+        }
+    }
+
+    #[test]
+    fn fitted_weights_are_never_negative() {
+        // English follows math closely, the published score rewards math
+        // and penalizes English: the unconstrained fit is 5 and -1
+        let students = (0..12)
+            .map(|i| {
+                let math = 120.0 + 7.0 * i as f32;
+                let english = math + [3.0, -2.0, 1.0, -4.0][i % 4];
+                student(math, english, 5.0 * math - english)
+            })
+            .collect_vec();
+        let weights = fit(&students);
+
+        assert!(weight(&weights, Subject::Math) >= 0.0);
+        assert!(weight(&weights, Subject::English) >= 0.0);
+    }
+}
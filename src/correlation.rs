@@ -0,0 +1,348 @@
+use std::{collections::HashMap, fmt, fs};
+
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::error::{Error, Result};
+use crate::parsing::*;
+
+/// How the equalized scores of two subjects go together, over the students
+/// who took both.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Correlation {
+    pub subjects: (Subject, Subject),
+    pub students: usize,
+    /// `None` if there are fewer than two students or either subject's scores
+    /// are all the same.
+    pub pearson: Option<f32>,
+    /// Pearson correlation of the ranks, ties sharing their mean rank.
+    pub spearman: Option<f32>,
+}
+
+impl fmt::Display for Correlation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let optional = |value: Option<f32>| value.map_or(String::from("-"), |v| format!("{v:.2}"));
+
+        write!(
+            f,
+            "{} - {}: {} students, pearson {}, spearman {}",
+            self.subjects.0,
+            self.subjects.1,
+            self.students,
+            optional(self.pearson),
+            optional(self.spearman)
+        )
+    }
+}
+
+fn pearson(pairs: &[(f32, f32)]) -> Option<f32> {
+    if pairs.len() < 2 {
+        return None;
+    }
+
+    let n = pairs.len() as f64;
+    let mean_x = pairs.iter().map(|(x, _)| *x as f64).sum::<f64>() / n;
+    let mean_y = pairs.iter().map(|(_, y)| *y as f64).sum::<f64>() / n;
+
+    let (mut covariance, mut variance_x, mut variance_y) = (0.0, 0.0, 0.0);
+    for (x, y) in pairs {
+        let (dx, dy) = (*x as f64 - mean_x, *y as f64 - mean_y);
+        covariance += dx * dy;
+        variance_x += dx * dx;
+        variance_y += dy * dy;
+    }
+
+    if variance_x == 0.0 || variance_y == 0.0 {
+        return None;
+    }
+
+    Some((covariance / (variance_x * variance_y).sqrt()) as f32)
+}
+
+/// Ranks from 1, ties sharing their mean rank.
+fn ranks(values: impl Iterator<Item = f32>) -> Vec<f32> {
+    let values = values.collect_vec();
+    let order = (0..values.len())
+        .sorted_by(|a, b| f32::total_cmp(&values[*a], &values[*b]))
+        .collect_vec();
+
+    let mut ranks = vec![0.0; values.len()];
+    let mut start = 0;
+    while start < order.len() {
+        let end = start
+            + order[start..]
+                .iter()
+                .take_while(|i| values[**i] == values[order[start]])
+                .count();
+        let rank = (start + end + 1) as f32 / 2.0;
+
+        for i in &order[start..end] {
+            ranks[*i] = rank;
+        }
+
+        start = end;
+    }
+
+    ranks
+}
+
+/// The equalized scores of the two subjects of every student who took both.
+pub fn paired_scores(students: &[StudentData], subjects: (Subject, Subject)) -> Vec<(f32, f32)> {
+    students
+        .iter()
+        .filter_map(|s| {
+            Some((
+                s.scores[subjects.0 as usize]?.equalized()?,
+                s.scores[subjects.1 as usize]?.equalized()?,
+            ))
+        })
+        .collect_vec()
+}
+
+/// The correlation of every pair of subjects some students took both of, in
+/// the order of [`ALL_SUBJECTS`].
+pub fn subject_correlations(students: &[StudentData]) -> Vec<Correlation> {
+    ALL_SUBJECTS
+        .into_iter()
+        .tuple_combinations()
+        .filter_map(|subjects| {
+            let pairs = paired_scores(students, subjects);
+            if pairs.is_empty() {
+                return None;
+            }
+
+            let ranked = ranks(pairs.iter().map(|(x, _)| *x))
+                .into_iter()
+                .zip(ranks(pairs.iter().map(|(_, y)| *y)))
+                .collect_vec();
+
+            Some(Correlation {
+                subjects,
+                students: pairs.len(),
+                pearson: pearson(&pairs),
+                spearman: pearson(&ranked),
+            })
+        })
+        .collect_vec()
+}
+
+/// Faculties with the same competitive subjects, and the students admitted to
+/// them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubjectCombination {
+    /// Indexed by subject, as in [`Faculty::subjects`].
+    pub subjects: [bool; ALL_SUBJECTS.len()],
+    pub faculties: usize,
+    pub students: usize,
+}
+
+impl SubjectCombination {
+    pub fn subjects(&self) -> impl Iterator<Item = Subject> + '_ {
+        ALL_SUBJECTS
+            .into_iter()
+            .filter(|subject| self.subjects[*subject as usize])
+    }
+}
+
+impl fmt::Display for SubjectCombination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} faculties, {} students",
+            self.subjects().join(" + "),
+            self.faculties,
+            self.students
+        )
+    }
+}
+
+/// Subject combinations of the faculties, the most students first.
+pub fn subject_combinations(
+    students: &[StudentData],
+    faculties: &HashMap<String, Faculty>,
+) -> Vec<SubjectCombination> {
+    let admitted = students.iter().counts_by(|s| s.faculty_id.as_str());
+
+    faculties
+        .values()
+        .into_group_map_by(|faculty| faculty.subjects)
+        .into_iter()
+        .map(|(subjects, faculties)| SubjectCombination {
+            subjects,
+            faculties: faculties.len(),
+            students: faculties
+                .iter()
+                .filter_map(|faculty| admitted.get(faculty.id.as_str()))
+                .sum(),
+        })
+        .sorted_by(|a, b| {
+            (b.students, b.faculties)
+                .cmp(&(a.students, a.faculties))
+                .then(a.subjects.cmp(&b.subjects))
+        })
+        .collect_vec()
+}
+
+#[derive(Debug, Serialize)]
+struct ExportedCorrelation {
+    /// [`Subject::key`]s.
+    subjects: [&'static str; 2],
+    students: usize,
+    pearson: Option<f32>,
+    spearman: Option<f32>,
+}
+
+#[derive(Debug, Serialize)]
+struct ExportedCombination {
+    subjects: Vec<&'static str>,
+    faculties: usize,
+    students: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct ExportedAnalysis {
+    correlations: Vec<ExportedCorrelation>,
+    combinations: Vec<ExportedCombination>,
+}
+
+/// Writes the correlations and the combinations as a JSON object, subjects
+/// being named by their keys.
+pub fn write_subject_analysis(
+    file_name: &str,
+    correlations: &[Correlation],
+    combinations: &[SubjectCombination],
+) -> Result<()> {
+    let analysis = ExportedAnalysis {
+        correlations: correlations
+            .iter()
+            .map(|c| ExportedCorrelation {
+                subjects: [c.subjects.0.key(), c.subjects.1.key()],
+                students: c.students,
+                pearson: c.pearson,
+                spearman: c.spearman,
+            })
+            .collect_vec(),
+        combinations: combinations
+            .iter()
+            .map(|c| ExportedCombination {
+                subjects: c.subjects().map(|subject| subject.key()).collect_vec(),
+                faculties: c.faculties,
+                students: c.students,
+            })
+            .collect_vec(),
+    };
+
+    let contents = serde_json::to_vec_pretty(&analysis).map_err(|source| Error::Json {
+        file: String::from(file_name),
+        source,
+    })?;
+
+    fs::write(file_name, contents).map_err(|source| Error::Io {
+        file: String::from(file_name),
+        source,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn student(faculty_id: &str, math: f32, english: Option<f32>) -> StudentData {
+        let mut scores = [None; ALL_SUBJECTS.len()];
+        scores[Subject::Math as usize] = Some(Score::Equalized(math));
+        scores[Subject::English as usize] = english.map(Score::Equalized);
+
+        StudentData {
+            id: String::new(),
+            scores,
+            overall_score: String::new(),
+            placement: None,
+            faculty_id: String::from(faculty_id),
+            grant: None,
+        }
+    }
+
+    fn assert_close(a: Option<f32>, b: f32) {
+        assert!(a.is_some_and(|a| (a - b).abs() < 1e-5), "{a:?} != {b}");
+    }
+
+    #[test]
+    fn ties_share_their_mean_rank() {
+        let values = [30.0, 10.0, 20.0, 10.0, 30.0, 30.0];
+
+        assert_eq!(ranks(values.into_iter()), [5.0, 1.5, 3.0, 1.5, 5.0, 5.0]);
+        assert_eq!(ranks(std::iter::empty()), Vec::<f32>::new());
+    }
+
+    #[test]
+    fn pearson_correlations() {
+        assert_close(pearson(&[(1.0, 2.0), (2.0, 4.0), (3.0, 6.0)]), 1.0);
+        assert_close(pearson(&[(1.0, 3.0), (2.0, 2.0), (3.0, 1.0)]), -1.0);
+        // covariance 1, variances 2
+        assert_close(pearson(&[(1.0, 1.0), (2.0, 3.0), (3.0, 2.0)]), 0.5);
+
+        assert_eq!(pearson(&[(1.0, 1.0)]), None);
+        assert_eq!(pearson(&[(1.0, 5.0), (2.0, 5.0)]), None);
+    }
+
+    #[test]
+    fn spearman_is_the_pearson_correlation_of_the_ranks() {
+        let students = [
+            student("001", 1.0, Some(1.0)),
+            student("001", 2.0, Some(3.0)),
+            student("001", 3.0, Some(2.0)),
+            student("002", 4.0, Some(100.0)),
+            student("003", 5.0, None),
+        ];
+        let correlations = subject_correlations(&students);
+
+        let [correlation] = correlations[..] else {
+            panic!("{correlations:?}");
+        };
+        assert_eq!(correlation.subjects, (Subject::Math, Subject::English));
+        assert_eq!(correlation.students, 4);
+        // ranks 1, 2, 3, 4 against 1, 3, 2, 4: covariance 4, variances 5
+        assert_close(correlation.spearman, 0.8);
+    }
+
+    #[test]
+    fn combinations_count_faculties_and_their_students() {
+        let faculty = |id: &str, subjects: &[Subject]| {
+            let mut counted = [false; ALL_SUBJECTS.len()];
+            for subject in subjects {
+                counted[*subject as usize] = true;
+            }
+
+            let faculty = Faculty {
+                id: String::from(id),
+                name: String::new(),
+                subjects: counted,
+            };
+            (String::from(id), faculty)
+        };
+        let faculties = HashMap::from([
+            faculty("001", &[Subject::Math, Subject::English]),
+            faculty("002", &[Subject::Math, Subject::English]),
+            faculty("003", &[Subject::Math]),
+            faculty("004", &[Subject::Math]),
+        ]);
+        let students = [
+            student("001", 1.0, None),
+            student("001", 1.0, None),
+            student("002", 1.0, None),
+            student("003", 1.0, None),
+        ];
+
+        let combinations = subject_combinations(&students, &faculties)
+            .iter()
+            .map(|c| (c.subjects().collect_vec(), c.faculties, c.students))
+            .collect_vec();
+        assert_eq!(
+            combinations,
+            [
+                (vec![Subject::Math, Subject::English], 2, 3),
+                (vec![Subject::Math], 2, 1),
+            ]
+        );
+    }
+}
@@ -0,0 +1,224 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::Path,
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::cache::Cache;
+use crate::descaling::DescalingModel;
+use crate::error::{Error, Result};
+use crate::parsing::*;
+use crate::processing::{descale_with_independent_data, sort_students};
+use crate::read::*;
+use crate::spreadsheet::ColumnMapping;
+
+/// A descaled and sorted publication, saved so that reports can be made from
+/// it without reading and descaling the publication again.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Dataset {
+    /// The exam year, if known.
+    #[serde(default)]
+    pub year: Option<u16>,
+    /// The model the scores were descaled with.
+    pub model: DescalingModel,
+    pub students: Vec<StudentData>,
+    pub schools: HashMap<String, School>,
+    pub faculties: HashMap<String, Faculty>,
+}
+
+impl Dataset {
+    /// Descales the publication with the descaling data, the students ranked
+    /// by their published competitive scores.
+    pub fn descale(
+        (students, schools, faculties): Publication,
+        independent_data: [Option<SubjectStats>; ALL_SUBJECTS.len()],
+        model: DescalingModel,
+        year: Option<u16>,
+    ) -> Result<Self> {
+        let students = descale_with_independent_data(students, independent_data, model)?;
+
+        Ok(Self {
+            year,
+            model,
+            students: sort_students(students)?,
+            schools,
+            faculties,
+        })
+    }
+
+    pub fn read(file_name: &str) -> Result<Self> {
+        let file_contents = fs::read(file_name).map_err(|source| Error::Io {
+            file: String::from(file_name),
+            source,
+        })?;
+
+        serde_json::from_slice(&file_contents).map_err(|source| Error::Json {
+            file: String::from(file_name),
+            source,
+        })
+    }
+
+    pub fn write(&self, file_name: &str) -> Result<()> {
+        let file_contents = serde_json::to_vec(self).map_err(|source| Error::Json {
+            file: String::from(file_name),
+            source,
+        })?;
+
+        fs::write(file_name, file_contents).map_err(|source| Error::Io {
+            file: String::from(file_name),
+            source,
+        })
+    }
+}
+
+/// The year in the file name, e.g. 2023 in `publication-2023.pdf`.
+pub fn year_from_file_name(file_name: &str) -> Option<u16> {
+    let stem = Path::new(file_name).file_stem()?.to_string_lossy();
+
+    stem.split(|c: char| !c.is_ascii_digit())
+        .filter(|digits| digits.len() == 4)
+        .find_map(|digits| digits.parse().ok())
+}
+
+/// Where a year's dataset comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DatasetSource {
+    /// A dataset file, as `descale` writes it.
+    Dataset(String),
+    /// A publication and its descaling data, descaled when they're read.
+    Publication {
+        publication: String,
+        descaling_data: String,
+    },
+}
+
+/// A year of [`read_datasets`], written `[YEAR=]DATASET` or
+/// `[YEAR=]PUBLICATION,DESCALING_DATA`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatasetInput {
+    /// The exam year, by default the dataset's or the one in the file name.
+    pub year: Option<u16>,
+    pub source: DatasetSource,
+}
+
+impl DatasetInput {
+    /// The dataset or the publication, the year is looked for in its name.
+    pub fn file_name(&self) -> &str {
+        match &self.source {
+            DatasetSource::Dataset(file_name) => file_name,
+            DatasetSource::Publication { publication, .. } => publication,
+        }
+    }
+}
+
+impl FromStr for DatasetInput {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (year, files) = match s.split_once('=') {
+            Some((year, files)) if year.len() == 4 && year.bytes().all(|b| b.is_ascii_digit()) => {
+                (year.parse().ok(), files)
+            }
+            _ => (None, s),
+        };
+
+        let source = match files.split_once(',') {
+            Some((publication, descaling_data)) => DatasetSource::Publication {
+                publication: String::from(publication),
+                descaling_data: String::from(descaling_data),
+            },
+            None => DatasetSource::Dataset(String::from(files)),
+        };
+
+        let input = Self { year, source };
+        if input.file_name().is_empty() {
+            return Err(format!("no file in `{s}`"));
+        }
+
+        Ok(input)
+    }
+}
+
+/// Reads datasets of several years, keyed by year. Publications are descaled
+/// with `model`, datasets keep the model they were descaled with. Inputs
+/// without a year are keyed by the dataset's year or the year in their file
+/// name.
+pub fn read_datasets(
+    inputs: &[DatasetInput],
+    model: DescalingModel,
+    cache: Option<&Cache>,
+) -> Result<BTreeMap<u16, Dataset>> {
+    let mut datasets = BTreeMap::new();
+
+    for input in inputs {
+        let file_name = input.file_name();
+
+        let mut dataset = match &input.source {
+            DatasetSource::Dataset(file_name) => Dataset::read(file_name)?,
+            DatasetSource::Publication {
+                publication,
+                descaling_data,
+            } => {
+                let column_mapping = ColumnMapping::default();
+
+                Dataset::descale(
+                    read_publication(publication, &column_mapping, cache)?,
+                    read_descaling_data(descaling_data, &column_mapping)?,
+                    model,
+                    None,
+                )?
+            }
+        };
+
+        let year = input
+            .year
+            .or(dataset.year)
+            .or_else(|| year_from_file_name(file_name))
+            .ok_or_else(|| Error::MissingYear {
+                file: String::from(file_name),
+            })?;
+        dataset.year = Some(year);
+
+        if datasets.insert(year, dataset).is_some() {
+            return Err(Error::DuplicateYear {
+                file: String::from(file_name),
+                year,
+            });
+        }
+    }
+
+    Ok(datasets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inputs_are_parsed() {
+        assert_eq!(
+            "2022=publication.pdf,data/ind/2022.csv".parse(),
+            Ok(DatasetInput {
+                year: Some(2022),
+                source: DatasetSource::Publication {
+                    publication: String::from("publication.pdf"),
+                    descaling_data: String::from("data/ind/2022.csv"),
+                },
+            })
+        );
+
+        // only four digits before the `=` are a year
+        assert_eq!(
+            "a=b-dataset.json".parse(),
+            Ok(DatasetInput {
+                year: None,
+                source: DatasetSource::Dataset(String::from("a=b-dataset.json")),
+            })
+        );
+
+        assert!("2022=".parse::<DatasetInput>().is_err());
+    }
+}
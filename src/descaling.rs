@@ -0,0 +1,363 @@
+use std::{fmt, str::FromStr};
+
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+/// How the equalized score is estimated between two known
+/// `(scaled, equalized)` points.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DescalingModel {
+    /// Straight lines between neighbouring anchors.
+    #[default]
+    #[serde(rename = "linear")]
+    PiecewiseLinear,
+    /// A monotone cubic (Fritsch–Carlson) curve through all anchors. Smooth
+    /// around the anchors and never reverses the order of two students.
+    #[serde(rename = "monotone-cubic")]
+    MonotoneCubic,
+}
+
+impl fmt::Display for DescalingModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DescalingModel::PiecewiseLinear => "linear",
+            DescalingModel::MonotoneCubic => "monotone-cubic",
+        })
+    }
+}
+
+impl FromStr for DescalingModel {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "linear" => Ok(DescalingModel::PiecewiseLinear),
+            "monotone-cubic" | "pchip" => Ok(DescalingModel::MonotoneCubic),
+            _ => Err(format!(
+                "unknown descaling model `{s}`, expected `linear` or `monotone-cubic`"
+            )),
+        }
+    }
+}
+
+/// A scaled to equalized mapping of a single subject, fitted through a set of
+/// `(scaled, equalized)` knots.
+#[derive(Debug, Clone)]
+pub struct DescalingCurve {
+    scaled: Vec<f32>,
+    equalized: Vec<f32>,
+    slopes: Vec<f32>,
+    /// The lowest knot wasn't published but made up.
+    estimated_minimum: bool,
+}
+
+impl DescalingCurve {
+    /// Fits the curve through the knots. Knots don't have to be sorted, knots
+    /// with the same scaled score are averaged. There has to be at least one
+    /// knot and all of them have to be finite.
+    pub fn new(knots: &[(f32, f32)], model: DescalingModel) -> Result<Self> {
+        if knots.is_empty() {
+            return Err(Error::BadKnots {
+                reason: "there are no knots",
+            });
+        }
+        if knots.iter().any(|(x, y)| !x.is_finite() || !y.is_finite()) {
+            return Err(Error::BadKnots {
+                reason: "a knot is not a finite number",
+            });
+        }
+
+        let mut scaled = Vec::new();
+        let mut equalized = Vec::new();
+
+        for (x, group) in &knots
+            .iter()
+            .sorted_by(|a, b| f32::total_cmp(&a.0, &b.0))
+            .group_by(|(x, _)| *x)
+        {
+            let ys = group.map(|(_, y)| *y).collect_vec();
+            scaled.push(x);
+            equalized.push(ys.iter().sum::<f32>() / ys.len() as f32);
+        }
+
+        let deltas = scaled
+            .iter()
+            .zip(&equalized)
+            .tuple_windows()
+            .map(|((x0, y0), (x1, y1))| (y1 - y0) / (x1 - x0))
+            .collect_vec();
+
+        let slopes = match model {
+            DescalingModel::PiecewiseLinear => Vec::new(),
+            DescalingModel::MonotoneCubic => fritsch_carlson_slopes(&deltas),
+        };
+
+        Ok(Self {
+            scaled,
+            equalized,
+            slopes,
+            estimated_minimum: false,
+        })
+    }
+
+    /// Marks the lowest knot as made up rather than published, see
+    /// [`DescalingCurve::uncertainty`].
+    pub fn with_estimated_minimum(mut self, estimated: bool) -> Self {
+        self.estimated_minimum = estimated;
+        self
+    }
+
+    /// The `(scaled, equalized)` knots the curve goes through, sorted.
+    pub fn knots(&self) -> impl Iterator<Item = (f32, f32)> + '_ {
+        self.scaled
+            .iter()
+            .copied()
+            .zip(self.equalized.iter().copied())
+    }
+
+    pub fn equalize(&self, scaled: f32) -> f32 {
+        let xs = &self.scaled;
+        let ys = &self.equalized;
+        let n = xs.len();
+
+        if n == 1 {
+            return ys[0];
+        }
+
+        // index of the segment `scaled` falls in, the end segments are
+        // extended past the first and last knots
+        let i = xs.partition_point(|&x| x <= scaled).clamp(1, n - 1) - 1;
+
+        let h = xs[i + 1] - xs[i];
+        let t = (scaled - xs[i]) / h;
+
+        if self.slopes.is_empty() {
+            return ys[i] + t * (ys[i + 1] - ys[i]);
+        }
+
+        if scaled < xs[0] {
+            return ys[0] + (scaled - xs[0]) * self.slopes[0];
+        }
+        if scaled > xs[n - 1] {
+            return ys[n - 1] + (scaled - xs[n - 1]) * self.slopes[n - 1];
+        }
+
+        let (t2, t3) = (t * t, t * t * t);
+
+        (2.0 * t3 - 3.0 * t2 + 1.0) * ys[i]
+            + (t3 - 2.0 * t2 + t) * h * self.slopes[i]
+            + (-2.0 * t3 + 3.0 * t2) * ys[i + 1]
+            + (t3 - t2) * h * self.slopes[i + 1]
+    }
+}
+
+impl DescalingCurve {
+    /// How far each interior knot is from where the curve through the other
+    /// knots puts it, per scaled point of the gap its neighbours leave without
+    /// it. The end knots are left out, the curve would have to extrapolate
+    /// there.
+    pub fn leave_one_out_error_rates(&self, model: DescalingModel) -> Vec<f32> {
+        let knots = self.knots().collect_vec();
+
+        (1..knots.len().saturating_sub(1))
+            .filter_map(|i| {
+                let (scaled, equalized) = knots[i];
+                let others = [&knots[..i], &knots[i + 1..]].concat();
+                let predicted = DescalingCurve::new(&others, model).ok()?.equalize(scaled);
+                let gap = knots[i + 1].0 - knots[i - 1].0;
+
+                Some((equalized - predicted).abs() / gap)
+            })
+            .collect_vec()
+    }
+
+    /// Estimated error of [`DescalingCurve::equalize`]. Between two knots it
+    /// is `error_rate` times the gap between them, as the leave-one-out errors
+    /// were measured, and grows with the distance past the end knots.
+    ///
+    /// Without an error rate, when there are no anchors to measure it on, and
+    /// next to a made up minimum, nothing is known but that the curve is
+    /// monotone. The error is then as large as the knots on either side
+    /// allow, from zero when the minimum is made up.
+    pub fn uncertainty(&self, scaled: f32, error_rate: Option<f32>) -> f32 {
+        let xs = &self.scaled;
+        let ys = &self.equalized;
+        let n = xs.len();
+
+        if n == 1 {
+            return 0.0;
+        }
+
+        let i = xs.partition_point(|&x| x <= scaled).clamp(1, n - 1) - 1;
+
+        match error_rate {
+            Some(error_rate) if !(i == 0 && self.estimated_minimum) => {
+                let past_ends = (xs[0] - scaled).max(scaled - xs[n - 1]).max(0.0);
+
+                error_rate * (xs[i + 1] - xs[i] + past_ends)
+            }
+            _ => {
+                let lowest = if i == 0 && self.estimated_minimum {
+                    0.0
+                } else {
+                    ys[i]
+                };
+                let equalized = self.equalize(scaled);
+
+                (equalized - lowest).max(ys[i + 1] - equalized).max(0.0)
+            }
+        }
+    }
+}
+
+/// The root mean square of the error rates, so that a single badly placed
+/// anchor isn't hidden by the others. `None` if there are none.
+pub fn typical_error_rate(error_rates: &[f32]) -> Option<f32> {
+    if error_rates.is_empty() {
+        return None;
+    }
+
+    let mean_square =
+        error_rates.iter().map(|rate| rate * rate).sum::<f32>() / error_rates.len() as f32;
+
+    Some(mean_square.sqrt())
+}
+
+/// Tangents at the knots that keep a cubic Hermite spline monotone on every
+/// segment the data itself is monotone on.
+fn fritsch_carlson_slopes(deltas: &[f32]) -> Vec<f32> {
+    let n = deltas.len() + 1;
+
+    if deltas.is_empty() {
+        return vec![0.0];
+    }
+
+    let mut slopes = vec![0.0; n];
+    slopes[0] = deltas[0];
+    slopes[n - 1] = deltas[n - 2];
+
+    for i in 1..n - 1 {
+        slopes[i] = if deltas[i - 1] * deltas[i] <= 0.0 {
+            0.0
+        } else {
+            (deltas[i - 1] + deltas[i]) / 2.0
+        };
+    }
+
+    for (i, &delta) in deltas.iter().enumerate() {
+        if delta == 0.0 {
+            slopes[i] = 0.0;
+            slopes[i + 1] = 0.0;
+            continue;
+        }
+
+        let a = slopes[i] / delta;
+        let b = slopes[i + 1] / delta;
+
+        if a < 0.0 {
+            slopes[i] = 0.0;
+        }
+        if b < 0.0 {
+            slopes[i + 1] = 0.0;
+        }
+
+        let s = a * a + b * b;
+        if s > 9.0 {
+            let tau = 3.0 / s.sqrt();
+            slopes[i] = tau * a * delta;
+            slopes[i + 1] = tau * b * delta;
+        }
+    }
+
+    slopes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bad_knots_are_errors() {
+        for knots in [vec![], vec![(100.0, 20.0), (f32::NAN, 30.0)]] {
+            assert!(matches!(
+                DescalingCurve::new(&knots, DescalingModel::PiecewiseLinear),
+                Err(Error::BadKnots { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn unsorted_knots_are_sorted_and_shared_ones_averaged() {
+        let curve = DescalingCurve::new(
+            &[(150.0, 40.0), (100.0, 10.0), (150.0, 50.0), (200.0, 60.0)],
+            DescalingModel::PiecewiseLinear,
+        )
+        .unwrap();
+
+        assert_eq!(
+            curve.knots().collect_vec(),
+            [(100.0, 10.0), (150.0, 45.0), (200.0, 60.0)]
+        );
+        assert_eq!(curve.equalize(125.0), 27.5);
+        assert_eq!(curve.equalize(210.0), 63.0);
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-4,
+            "{actual} isn't {expected}"
+        );
+    }
+
+    fn curve(knots: &[(f32, f32)]) -> DescalingCurve {
+        DescalingCurve::new(knots, DescalingModel::PiecewiseLinear).unwrap()
+    }
+
+    #[test]
+    fn typical_error_rate_does_not_hide_a_bad_anchor() {
+        assert_eq!(typical_error_rate(&[]), None);
+        assert_close(typical_error_rate(&[3.0, 4.0]).unwrap(), 12.5_f32.sqrt());
+        // the median would be 0.1
+        assert_close(typical_error_rate(&[0.1, 0.1, 3.0]).unwrap(), 1.733_974);
+    }
+
+    #[test]
+    fn leave_one_out_errors_are_per_point_of_the_gap() {
+        let curve = curve(&[(0.0, 0.0), (10.0, 10.0), (20.0, 30.0), (30.0, 30.0)]);
+        let rates = curve.leave_one_out_error_rates(DescalingModel::PiecewiseLinear);
+
+        // (10, 10) is put at 15 and (20, 30) at 20, both with a gap of 20
+        assert_eq!(rates.len(), 2);
+        assert_close(rates[0], 0.25);
+        assert_close(rates[1], 0.5);
+    }
+
+    #[test]
+    fn uncertainty_grows_with_the_gap_between_knots() {
+        let curve = curve(&[(0.0, 8.0), (10.0, 10.0), (20.0, 30.0), (40.0, 30.0)]);
+
+        assert_close(curve.uncertainty(15.0, Some(0.5)), 5.0);
+        // knots are as uncertain as the segment they start
+        assert_close(curve.uncertainty(10.0, Some(0.5)), 5.0);
+        assert_close(curve.uncertainty(0.0, Some(0.5)), 5.0);
+        assert_close(curve.uncertainty(40.0, Some(0.5)), 10.0);
+        assert_close(curve.uncertainty(45.0, Some(0.5)), 12.5);
+    }
+
+    #[test]
+    fn unknown_error_rates_and_made_up_minimums_get_the_monotone_bound() {
+        let curve = curve(&[(0.0, 8.0), (10.0, 10.0), (20.0, 30.0), (40.0, 30.0)]);
+
+        // 12 is put at 14, between 10 and 30
+        assert_close(curve.uncertainty(12.0, None), 16.0);
+        assert_close(curve.uncertainty(5.0, None), 1.0);
+
+        let curve = curve.with_estimated_minimum(true);
+
+        // 5 is put at 9, anywhere from 0 to 10 would still be monotone
+        assert_close(curve.uncertainty(5.0, Some(0.5)), 9.0);
+        assert_close(curve.uncertainty(15.0, Some(0.5)), 5.0);
+    }
+}
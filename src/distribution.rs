@@ -0,0 +1,237 @@
+use std::fmt;
+
+use itertools::Itertools;
+
+use crate::parsing::*;
+
+/// The percentiles reports show.
+pub const KEY_PERCENTILES: [f32; 5] = [10.0, 25.0, 50.0, 75.0, 90.0];
+
+/// Number of bins of the histograms in the reports.
+pub const HISTOGRAM_BINS: usize = 30;
+
+/// A bin of a histogram: scores from `start` up to `end`, the last bin
+/// including its end.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bin {
+    pub start: f32,
+    pub end: f32,
+    pub count: usize,
+}
+
+/// The scores of a subject, sorted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Distribution {
+    sorted: Vec<f32>,
+}
+
+impl Distribution {
+    /// `None` if there are no scores.
+    pub fn new(scores: impl IntoIterator<Item = f32>) -> Option<Self> {
+        let sorted = scores
+            .into_iter()
+            .filter(|score| score.is_finite())
+            .sorted_by(f32::total_cmp)
+            .collect_vec();
+
+        (!sorted.is_empty()).then_some(Self { sorted })
+    }
+
+    pub fn len(&self) -> usize {
+        self.sorted.len()
+    }
+
+    /// Always false, distributions have scores.
+    pub fn is_empty(&self) -> bool {
+        self.sorted.is_empty()
+    }
+
+    pub fn min(&self) -> f32 {
+        self.sorted[0]
+    }
+
+    pub fn max(&self) -> f32 {
+        self.sorted[self.sorted.len() - 1]
+    }
+
+    pub fn mean(&self) -> f32 {
+        self.sorted.iter().sum::<f32>() / self.len() as f32
+    }
+
+    pub fn standard_deviation(&self) -> f32 {
+        let mean = self.mean();
+
+        (self.sorted.iter().map(|s| (s - mean).powi(2)).sum::<f32>() / self.len() as f32).sqrt()
+    }
+
+    /// The score `percentile` percent of the students are below, interpolated
+    /// between the closest two.
+    pub fn percentile(&self, percentile: f32) -> f32 {
+        let position = (percentile.clamp(0.0, 100.0) / 100.0) * (self.len() - 1) as f32;
+        let below = position.floor() as usize;
+        let above = position.ceil() as usize;
+
+        self.sorted[below] + (self.sorted[above] - self.sorted[below]) * position.fract()
+    }
+
+    pub fn median(&self) -> f32 {
+        self.percentile(50.0)
+    }
+
+    /// The percentile `score` sits at: the percentage of students below it,
+    /// counting those with the same score as half below.
+    pub fn percentile_rank(&self, score: f32) -> f32 {
+        let below = self.sorted.partition_point(|s| *s < score);
+        let equal = self.sorted[below..].partition_point(|s| *s <= score);
+
+        (below as f32 + equal as f32 / 2.0) / self.len() as f32 * 100.0
+    }
+
+    /// `bins` bins of the same width from the lowest to the highest score.
+    pub fn histogram(&self, bins: usize) -> Vec<Bin> {
+        let (min, max) = (self.min(), self.max());
+        let width = if max > min {
+            (max - min) / bins as f32
+        } else {
+            1.0
+        };
+
+        let mut counts = vec![0; bins];
+        for score in &self.sorted {
+            let bin = (((score - min) / width) as usize).min(bins - 1);
+            counts[bin] += 1;
+        }
+
+        counts
+            .into_iter()
+            .enumerate()
+            .map(|(i, count)| Bin {
+                start: min + width * i as f32,
+                end: min + width * (i + 1) as f32,
+                count,
+            })
+            .collect_vec()
+    }
+}
+
+impl fmt::Display for Distribution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} students, mean {:.1}, sd {:.1}, {}",
+            self.len(),
+            self.mean(),
+            self.standard_deviation(),
+            KEY_PERCENTILES
+                .iter()
+                .map(|p| format!("p{p} {:.1}", self.percentile(*p)))
+                .join(", ")
+        )
+    }
+}
+
+/// The scaled and the equalized scores of a subject.
+#[derive(Debug, Clone)]
+pub struct SubjectDistribution {
+    pub subject: Subject,
+    pub scaled: Option<Distribution>,
+    pub equalized: Option<Distribution>,
+}
+
+impl fmt::Display for SubjectDistribution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:", self.subject)?;
+
+        if let Some(scaled) = &self.scaled {
+            write!(f, "\n\tscaled: {scaled}")?;
+        }
+        if let Some(equalized) = &self.equalized {
+            write!(f, "\n\tequalized: {equalized}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The distribution of every subject, indexed by subject.
+pub fn subject_distributions(
+    students: &[StudentData],
+) -> [SubjectDistribution; ALL_SUBJECTS.len()] {
+    ALL_SUBJECTS.map(|subject| {
+        let scores = students
+            .iter()
+            .filter_map(|s| s.scores[subject as usize])
+            .collect_vec();
+
+        SubjectDistribution {
+            subject,
+            scaled: Distribution::new(scores.iter().filter_map(|s| s.scaled())),
+            equalized: Distribution::new(scores.iter().filter_map(|s| s.equalized())),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn distribution(scores: &[f32]) -> Distribution {
+        Distribution::new(scores.iter().copied()).unwrap()
+    }
+
+    #[test]
+    fn percentiles_are_interpolated() {
+        let scores = distribution(&[40.0, 10.0, 30.0, 20.0, f32::NAN]);
+
+        assert_eq!(scores.len(), 4);
+        assert_eq!(scores.percentile(0.0), 10.0);
+        assert_eq!(scores.percentile(100.0), 40.0);
+        // the second and third scores, 20 and 30, are at 33.3 and 66.7
+        assert!((scores.percentile(50.0) - 25.0).abs() < 1e-4);
+        assert!((scores.percentile(40.0) - 22.0).abs() < 1e-4);
+        assert_eq!(scores.percentile(150.0), 40.0);
+        assert!(Distribution::new([f32::NAN]).is_none());
+    }
+
+    #[test]
+    fn ties_count_as_half_below() {
+        let scores = distribution(&[10.0, 20.0, 20.0, 30.0]);
+
+        assert_eq!(scores.percentile_rank(5.0), 0.0);
+        assert_eq!(scores.percentile_rank(10.0), 12.5);
+        assert_eq!(scores.percentile_rank(20.0), 50.0);
+        assert_eq!(scores.percentile_rank(25.0), 75.0);
+        assert_eq!(scores.percentile_rank(30.0), 87.5);
+        assert_eq!(scores.percentile_rank(35.0), 100.0);
+    }
+
+    #[test]
+    fn histogram_bins_share_their_edges() {
+        let scores = distribution(&[0.0, 1.0, 2.5, 5.0, 9.9, 10.0]);
+        let bins = scores.histogram(4);
+
+        let edges = bins.iter().map(|bin| (bin.start, bin.end)).collect_vec();
+        assert_eq!(edges, [(0.0, 2.5), (2.5, 5.0), (5.0, 7.5), (7.5, 10.0)]);
+        // a score on an edge goes to the bin it starts, the highest one to
+        // the last bin
+        let counts = bins.iter().map(|bin| bin.count).collect_vec();
+        assert_eq!(counts, [2, 1, 1, 2]);
+
+        let same = distribution(&[3.0, 3.0]).histogram(2);
+        assert_eq!(
+            same,
+            [
+                Bin {
+                    start: 3.0,
+                    end: 4.0,
+                    count: 2
+                },
+                Bin {
+                    start: 4.0,
+                    end: 5.0,
+                    count: 0
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,146 @@
+use std::io;
+
+use thiserror::Error;
+
+use crate::parsing::Subject;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("{file}: {source}")]
+    Io { file: String, source: io::Error },
+
+    #[error("{file}: {source}")]
+    Csv { file: String, source: csv::Error },
+
+    #[error("{file}: {source}")]
+    Json {
+        file: String,
+        source: serde_json::Error,
+    },
+
+    #[error("{file}: couldn't read the PDF: {source}")]
+    Pdf { file: String, source: lopdf::Error },
+
+    #[error("{file}: page {page}: the MediaBox doesn't have four numbers")]
+    BadMediaBox { file: String, page: u32 },
+
+    #[error("{file}: couldn't read the spreadsheet: {source}")]
+    Spreadsheet {
+        file: String,
+        source: calamine::Error,
+    },
+
+    #[error("{file}: no sheet `{sheet}`")]
+    MissingSheet { file: String, sheet: String },
+
+    #[error("{file}: no `{column}` column")]
+    MissingColumn { file: String, column: String },
+
+    #[error("{file}:{line}: unknown column mapping field `{field}`")]
+    UnknownColumnField {
+        file: String,
+        line: usize,
+        field: String,
+    },
+
+    #[error("{file}:{line}: faculty id `{id}` is stored as a number, which lost its leading zeros; store the ids as text or give the school id column")]
+    NumericFacultyId {
+        file: String,
+        line: usize,
+        id: String,
+    },
+
+    #[error("{file}: couldn't draw the plot: {message}")]
+    Plot { file: String, message: String },
+
+    #[error("{template}: {source}")]
+    Template {
+        template: String,
+        source: minijinja::Error,
+    },
+
+    #[error("{file}:{line}: unknown subject `{subject}`")]
+    UnknownSubject {
+        file: String,
+        line: usize,
+        subject: String,
+    },
+
+    #[error(
+        "{file}:{line}: unknown datatype `{datatype}`, expected `minimum`, `maximum` or `anchor`"
+    )]
+    BadDatatype {
+        file: String,
+        line: usize,
+        datatype: String,
+    },
+
+    #[error("{file}:{line}: `{value}` is not a valid number")]
+    BadNumber {
+        file: String,
+        line: usize,
+        value: String,
+    },
+
+    #[error("{file}: no exam year in the dataset or the file name")]
+    MissingYear { file: String },
+
+    #[error("{file}: a dataset of {year} was already read")]
+    DuplicateYear { file: String, year: u16 },
+
+    #[error("{file}:{line}: unknown grant `{value}`, expected `100`, `70`, `50` or `0`")]
+    UnknownGrant {
+        file: String,
+        line: usize,
+        value: String,
+    },
+
+    #[error("{file}:{line}: unknown grant limit `{limit}`, expected `quota` or `threshold`")]
+    UnknownGrantLimit {
+        file: String,
+        line: usize,
+        limit: String,
+    },
+
+    #[error("{file}:{line}: malformed student row, expected {expected} columns but found {found}")]
+    MalformedStudentRow {
+        file: String,
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+
+    #[error("{file}:{line}: subject header without a faculty header before it")]
+    MissingFacultyHeader { file: String, line: usize },
+
+    #[error("{file}:{line}: no minimum or maximum score for {subject}")]
+    MissingMinMax {
+        file: String,
+        line: usize,
+        subject: Subject,
+    },
+
+    #[error("{file}: no descaling data for {subject}")]
+    MissingDescalingData { file: String, subject: Subject },
+
+    #[error("can't fit a descaling curve: {reason}")]
+    BadKnots { reason: &'static str },
+
+    #[error("{file}:{line}: bad descaling data for {subject}: expected equalized scores and anchors with both equalized and scaled values")]
+    BadDescalingData {
+        file: String,
+        line: usize,
+        subject: Subject,
+    },
+
+    #[error("student {student_id}: `{value}` is not a valid competitive score")]
+    BadOverallScore { student_id: String, value: String },
+
+    #[error("student {student_id}: faculty `{faculty_id}` isn't in the dataset")]
+    UnknownFaculty {
+        student_id: String,
+        faculty_id: String,
+    },
+}
@@ -0,0 +1,393 @@
+use std::f32::consts::PI;
+use std::fmt;
+
+use itertools::Itertools;
+
+use crate::error::{Error, Result};
+use crate::parsing::*;
+
+/// Scaled scores are raw (equalized) scores shifted and stretched so that
+/// every subject has this mean...
+pub const SCALED_MEAN: f32 = 150.0;
+/// ...and this standard deviation.
+pub const SCALED_STANDARD_DEVIATION: f32 = 10.0;
+
+/// Range of lattice steps (distance between two neighbouring possible scaled
+/// scores) that are tried.
+const LATTICE_STEPS: (f32, f32) = (0.3, 3.0);
+const LATTICE_STEP_RESOLUTION: f32 = 0.001;
+/// A lattice step only counts if its comb strength `r` over `n` distinct
+/// scores satisfies `n * r^2 >= LATTICE_SIGNIFICANCE`, the rest is noise.
+const LATTICE_SIGNIFICANCE: f32 = 8.0;
+/// Relative distance around a picked lattice step searched for its peak.
+const LATTICE_PEAK_WIDTH: f32 = 0.02;
+/// How much smaller than the slope implied by the observed range the lattice
+/// slope may be, to allow for the rounding of the published scores.
+const RANGE_TOLERANCE: f32 = 0.02;
+
+/// The estimated affine map between raw and scaled scores of a subject:
+/// `scaled = intercept + slope * raw`.
+#[derive(Debug, Clone, Copy)]
+pub struct ScalingFit {
+    pub subject: Subject,
+    /// Number of scores of the subject in the publication.
+    pub scores: usize,
+    /// Distance between two neighbouring possible scaled scores, if the
+    /// scores line up on a lattice.
+    pub lattice_step: Option<f32>,
+    /// How well the scores line up on the lattice, between 0 and 1.
+    pub lattice_strength: f32,
+    /// Scaled points per raw point.
+    pub slope: f32,
+    /// Scaled score of a raw score of 0.
+    pub intercept: f32,
+    pub min_scaled: f32,
+    pub max_scaled: f32,
+}
+
+impl ScalingFit {
+    pub fn equalize(&self, scaled: f32) -> f32 {
+        (scaled - self.intercept) / self.slope
+    }
+
+    pub fn scale(&self, equalized: f32) -> f32 {
+        self.intercept + self.slope * equalized
+    }
+
+    /// Mean raw score of everyone who took the exam.
+    pub fn raw_mean(&self) -> f32 {
+        self.equalize(SCALED_MEAN)
+    }
+
+    /// Standard deviation of the raw scores of everyone who took the exam.
+    pub fn raw_standard_deviation(&self) -> f32 {
+        SCALED_STANDARD_DEVIATION / self.slope
+    }
+
+    /// The fit expressed as descaling data: the lowest and highest observed
+    /// scaled scores paired with their estimated equalized scores.
+    pub fn to_subject_stats(&self) -> SubjectStats {
+        SubjectStats {
+            min: Some(Score::EqualizedAndScaled {
+                scaled: self.min_scaled,
+                equalized: self.equalize(self.min_scaled),
+                uncertainty: 0.0,
+            }),
+            max: Some(Score::EqualizedAndScaled {
+                scaled: self.max_scaled,
+                equalized: self.equalize(self.max_scaled),
+                uncertainty: 0.0,
+            }),
+            anchors: Vec::new(),
+            estimated_minimum: false,
+            file: String::new(),
+            line: 0,
+        }
+    }
+}
+
+impl fmt::Display for ScalingFit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} scores, {:.3} scaled points per raw point",
+            self.subject, self.scores, self.slope
+        )?;
+        match self.lattice_step {
+            Some(step) => write!(
+                f,
+                " (lattice step {step:.3}, strength {:.2})",
+                self.lattice_strength
+            )?,
+            None => write!(f, " (no lattice, from the observed range)")?,
+        }
+        write!(
+            f,
+            ", raw mean {:.2}, raw sd {:.2}, {:.1} -> {:.2}, {:.1} -> {:.2}",
+            self.raw_mean(),
+            self.raw_standard_deviation(),
+            self.min_scaled,
+            self.equalize(self.min_scaled),
+            self.max_scaled,
+            self.equalize(self.max_scaled),
+        )
+    }
+}
+
+/// Strength of the comb with the given step over the scores: 1 if every score
+/// is a whole number of steps away from every other one.
+fn comb_strength(scores: &[f32], step: f32) -> f32 {
+    let (sin, cos) = scores.iter().fold((0.0, 0.0), |(sin, cos), score| {
+        let phase = 2.0 * PI * score / step;
+        (sin + phase.sin(), cos + phase.cos())
+    });
+
+    (sin * sin + cos * cos).sqrt() / scores.len() as f32
+}
+
+/// The step of the lattice the scores lie on and its strength. Of the steps
+/// about as strong as the strongest one the largest is picked, since every
+/// fraction of the true step fits the scores just as well.
+fn find_lattice(scores: &[f32]) -> Option<(f32, f32)> {
+    let (from, to) = LATTICE_STEPS;
+    let candidates = ((to - from) / LATTICE_STEP_RESOLUTION) as usize;
+
+    let strengths = (0..=candidates)
+        .map(|i| from + i as f32 * LATTICE_STEP_RESOLUTION)
+        .map(|step| (step, comb_strength(scores, step)))
+        .collect_vec();
+
+    let best = strengths.iter().map(|(_, s)| *s).fold(0.0, f32::max);
+
+    if (scores.len() as f32) * best * best < LATTICE_SIGNIFICANCE {
+        return None;
+    }
+
+    let (largest, _) = strengths
+        .iter()
+        .filter(|(_, strength)| *strength >= 0.9 * best)
+        .max_by(|a, b| f32::total_cmp(&a.0, &b.0))?;
+
+    // the peak of the picked step, not just its first candidate above the bar
+    strengths
+        .iter()
+        .filter(|(step, _)| (step - largest).abs() <= LATTICE_PEAK_WIDTH * largest)
+        .max_by(|a, b| f32::total_cmp(&a.1, &b.1))
+        .copied()
+}
+
+fn equalized(score: &Option<Score>) -> Option<f32> {
+    match score {
+        Some(Score::Equalized(equalized)) => Some(*equalized),
+        Some(Score::EqualizedAndScaled { equalized, .. }) => Some(*equalized),
+        _ => None,
+    }
+}
+
+/// Fits the scale of a single subject from its scaled scores, given the raw
+/// score range from the descaling data.
+///
+/// Raw scores move in whole (or half) points, so the scaled scores of a
+/// subject sit on a lattice whose step is the slope of the scaling. The
+/// observed scaled range can't be wider than the raw range, which decides
+/// between the step and its multiples. The lowest and highest observed scores
+/// are assumed to be equally close to the raw minimum and maximum.
+pub fn estimate_subject_scaling(
+    subject: Subject,
+    scaled_scores: &[f32],
+    stats: &SubjectStats,
+) -> Result<Option<ScalingFit>> {
+    let (Some(min_raw), Some(max_raw)) = (equalized(&stats.min), equalized(&stats.max)) else {
+        return Err(Error::MissingMinMax {
+            file: stats.file.clone(),
+            line: stats.line,
+            subject,
+        });
+    };
+
+    let distinct = scaled_scores
+        .iter()
+        .map(|s| (s * 10.0).round() as i64)
+        .sorted()
+        .dedup()
+        .map(|s| s as f32 / 10.0)
+        .collect_vec();
+
+    let (Some(&min_scaled), Some(&max_scaled)) = (distinct.first(), distinct.last()) else {
+        return Ok(None);
+    };
+    if min_scaled == max_scaled || max_raw <= min_raw {
+        return Ok(None);
+    }
+
+    let range_slope = (max_scaled - min_scaled) / (max_raw - min_raw);
+
+    let lattice = find_lattice(&distinct);
+
+    let slope = match lattice {
+        Some((step, _)) => {
+            let multiple = (range_slope * (1.0 - RANGE_TOLERANCE) / step)
+                .ceil()
+                .max(1.0);
+            step * multiple
+        }
+        None => range_slope,
+    };
+
+    let slack = (max_raw - min_raw) - (max_scaled - min_scaled) / slope;
+    let raw_at_min = min_raw + slack.max(0.0) / 2.0;
+
+    Ok(Some(ScalingFit {
+        subject,
+        scores: scaled_scores.len(),
+        lattice_step: lattice.map(|(step, _)| step),
+        lattice_strength: lattice.map_or(0.0, |(_, strength)| strength),
+        slope,
+        intercept: min_scaled - slope * raw_at_min,
+        min_scaled,
+        max_scaled,
+    }))
+}
+
+/// Fits the scale of every subject that has a raw score range in the
+/// descaling data and at least two different scaled scores in the publication.
+pub fn estimate_scaling(
+    students: &[StudentData],
+    independent_data: &[Option<SubjectStats>; ALL_SUBJECTS.len()],
+) -> Result<Vec<ScalingFit>> {
+    let mut fits = Vec::new();
+
+    for (subject_index, subject) in ALL_SUBJECTS.iter().enumerate() {
+        let Some(stats) = &independent_data[subject_index] else {
+            continue;
+        };
+
+        let scaled_scores = students
+            .iter()
+            .filter_map(|s| match s.scores[subject_index] {
+                Some(Score::Scaled(scaled)) => Some(scaled),
+                Some(Score::EqualizedAndScaled { scaled, .. }) => Some(scaled),
+                _ => None,
+            })
+            .collect_vec();
+
+        if let Some(fit) = estimate_subject_scaling(*subject, &scaled_scores, stats)? {
+            fits.push(fit);
+        }
+    }
+
+    Ok(fits)
+}
+
+/// Descaling data where every subject without anchors gets its minimum and
+/// maximum from the estimated scaling instead.
+pub fn with_estimated_anchors(
+    independent_data: [Option<SubjectStats>; ALL_SUBJECTS.len()],
+    fits: &[ScalingFit],
+) -> [Option<SubjectStats>; ALL_SUBJECTS.len()] {
+    let mut independent_data = independent_data;
+
+    for fit in fits {
+        let stats = &mut independent_data[fit.subject as usize];
+
+        if stats.as_ref().is_none_or(|s| s.anchors.is_empty()) {
+            // errors about the subject still point to its descaling data
+            let (file, line) = stats
+                .as_ref()
+                .map(|s| (s.file.clone(), s.line))
+                .unwrap_or_default();
+
+            *stats = Some(SubjectStats {
+                file,
+                line,
+                ..fit.to_subject_stats()
+            });
+        }
+    }
+
+    independent_data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(min_raw: f32, max_raw: f32) -> SubjectStats {
+        SubjectStats {
+            min: Some(Score::Equalized(min_raw)),
+            max: Some(Score::Equalized(max_raw)),
+            ..SubjectStats::default()
+        }
+    }
+
+    /// The scaled scores of every whole raw score in the range.
+    fn scaled(raw: std::ops::RangeInclusive<i32>, intercept: f32, slope: f32) -> Vec<f32> {
+        raw.map(|raw| intercept + slope * raw as f32).collect_vec()
+    }
+
+    fn fit(scaled_scores: &[f32], stats: &SubjectStats) -> ScalingFit {
+        estimate_subject_scaling(Subject::Math, scaled_scores, stats)
+            .unwrap()
+            .unwrap()
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 0.05, "{a} != {b}");
+    }
+
+    #[test]
+    fn the_lattice_step_is_the_slope() {
+        let fit = fit(&scaled(0..=50, 100.0, 2.0), &stats(0.0, 50.0));
+
+        assert_close(fit.lattice_step.unwrap(), 2.0);
+        assert_close(fit.slope, 2.0);
+        assert_close(fit.intercept, 100.0);
+        assert_close(fit.raw_mean(), 25.0);
+        assert_close(fit.raw_standard_deviation(), 5.0);
+        assert_close(fit.equalize(fit.scale(17.0)), 17.0);
+    }
+
+    #[test]
+    fn the_range_picks_a_multiple_of_the_step() {
+        // 4 is past the steps tried, its half is found instead
+        let fit = fit(&scaled(0..=25, 100.0, 4.0), &stats(0.0, 25.0));
+
+        assert_close(fit.lattice_step.unwrap(), 2.0);
+        assert_close(fit.slope, 4.0);
+        assert_close(fit.intercept, 100.0);
+    }
+
+    #[test]
+    fn unreached_raw_scores_are_split_between_the_ends() {
+        // raw scores 1 to 24 of 0 to 25, a point short at either end
+        let fit = fit(&scaled(1..=24, 100.0, 4.0), &stats(0.0, 25.0));
+
+        assert_close(fit.slope, 4.0);
+        assert_close(fit.intercept, 100.0);
+        assert_close(fit.equalize(fit.min_scaled), 1.0);
+        assert_close(fit.equalize(fit.max_scaled), 24.0);
+    }
+
+    #[test]
+    fn without_a_lattice_the_range_is_the_slope() {
+        let fit = fit(&[110.0, 137.3, 190.0], &stats(0.0, 50.0));
+
+        assert_eq!(fit.lattice_step, None);
+        assert_close(fit.slope, 1.6);
+        assert_close(fit.intercept, 110.0);
+    }
+
+    #[test]
+    fn subjects_without_a_range_are_errors() {
+        let no_minimum = SubjectStats {
+            min: None,
+            ..stats(0.0, 50.0)
+        };
+        assert!(estimate_subject_scaling(Subject::Math, &[100.0, 200.0], &no_minimum).is_err());
+
+        let same = estimate_subject_scaling(Subject::Math, &[150.0, 150.0], &stats(0.0, 50.0));
+        assert!(same.unwrap().is_none());
+    }
+
+    #[test]
+    fn only_subjects_without_anchors_get_estimated_ones() {
+        let fit = fit(&scaled(0..=50, 100.0, 2.0), &stats(0.0, 50.0));
+        let anchored = SubjectStats {
+            anchors: vec![Score::Equalized(20.0)],
+            ..stats(0.0, 50.0)
+        };
+
+        let mut independent_data: [Option<SubjectStats>; ALL_SUBJECTS.len()] = Default::default();
+        independent_data[Subject::Math as usize] = Some(anchored);
+        let kept = with_estimated_anchors(independent_data, &[fit]);
+        assert_eq!(
+            kept[Subject::Math as usize].as_ref().unwrap().anchors.len(),
+            1
+        );
+
+        let estimated = with_estimated_anchors(Default::default(), &[fit]);
+        let stats = estimated[Subject::Math as usize].as_ref().unwrap();
+        assert_eq!(stats.min.unwrap().scaled(), Some(100.0));
+        assert_close(stats.max.unwrap().equalized().unwrap(), 50.0);
+    }
+}
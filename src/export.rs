@@ -0,0 +1,428 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt, fs,
+    str::FromStr,
+};
+
+use csv::WriterBuilder;
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::error::{Error, Result};
+use crate::parsing::*;
+
+/// File format of exported student lists.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.extension())
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(ExportFormat::Csv),
+            "json" => Ok(ExportFormat::Json),
+            _ => Err(format!(
+                "unknown export format `{s}`, expected `csv` or `json`"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+struct ExportedScore {
+    scaled: Option<f32>,
+    equalized: Option<f32>,
+    uncertainty: Option<f32>,
+}
+
+impl From<Score> for ExportedScore {
+    fn from(score: Score) -> Self {
+        match score {
+            Score::Scaled(scaled) => ExportedScore {
+                scaled: Some(scaled),
+                ..Default::default()
+            },
+            Score::Equalized(equalized) => ExportedScore {
+                equalized: Some(equalized),
+                ..Default::default()
+            },
+            Score::EqualizedAndScaled {
+                scaled,
+                equalized,
+                uncertainty,
+            } => ExportedScore {
+                scaled: Some(scaled),
+                equalized: Some(equalized),
+                uncertainty: Some(uncertainty),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ExportedStudent<'a> {
+    id: &'a str,
+    faculty_id: &'a str,
+    faculty: Option<&'a str>,
+    school_id: &'a str,
+    school: Option<&'a str>,
+    /// Keyed by [`Subject::key`], only the subjects the student took.
+    scores: BTreeMap<&'static str, ExportedScore>,
+    overall_score: &'a str,
+    placement: Option<usize>,
+    grant: Option<String>,
+}
+
+fn exported_student<'a>(
+    student: &'a StudentData,
+    schools: &'a HashMap<String, School>,
+    faculties: &'a HashMap<String, Faculty>,
+) -> ExportedStudent<'a> {
+    let school_id = school_id(&student.faculty_id);
+
+    ExportedStudent {
+        id: &student.id,
+        faculty_id: &student.faculty_id,
+        faculty: faculties.get(&student.faculty_id).map(|f| f.name.as_str()),
+        school_id,
+        school: schools.get(school_id).map(|s| s.name.as_str()),
+        scores: ALL_SUBJECTS
+            .iter()
+            .filter_map(|subject| {
+                student.scores[*subject as usize].map(|score| (subject.key(), score.into()))
+            })
+            .collect(),
+        overall_score: &student.overall_score,
+        placement: student.placement,
+        grant: student.grant.map(|grant| grant.to_string()),
+    }
+}
+
+fn optional_cell<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+/// The students as CSV, one row per student with a scaled, an equalized and
+/// an uncertainty column per subject. Cells of subjects a student didn't
+/// take are left empty.
+fn students_to_csv(
+    students: &[StudentData],
+    schools: &HashMap<String, School>,
+    faculties: &HashMap<String, Faculty>,
+) -> csv::Result<Vec<u8>> {
+    let mut writer = WriterBuilder::new().from_writer(Vec::new());
+
+    let header = ["id", "faculty_id", "faculty", "school_id", "school"]
+        .into_iter()
+        .map(String::from)
+        .chain(ALL_SUBJECTS.iter().flat_map(|subject| {
+            ["scaled", "equalized", "uncertainty"]
+                .map(|column| format!("{}_{column}", subject.key()))
+        }))
+        .chain(["overall_score", "placement", "grant"].map(String::from))
+        .collect_vec();
+    writer.write_record(&header)?;
+
+    for student in students {
+        let exported = exported_student(student, schools, faculties);
+
+        let record = [
+            String::from(exported.id),
+            String::from(exported.faculty_id),
+            optional_cell(exported.faculty),
+            String::from(exported.school_id),
+            optional_cell(exported.school),
+        ]
+        .into_iter()
+        .chain(ALL_SUBJECTS.iter().flat_map(|subject| {
+            let score = exported
+                .scores
+                .get(subject.key())
+                .copied()
+                .unwrap_or_default();
+
+            [
+                optional_cell(score.scaled),
+                optional_cell(score.equalized),
+                optional_cell(score.uncertainty),
+            ]
+        }))
+        .chain([
+            String::from(exported.overall_score),
+            optional_cell(exported.placement),
+            optional_cell(exported.grant),
+        ])
+        .collect_vec();
+
+        writer.write_record(&record)?;
+    }
+
+    writer
+        .into_inner()
+        .map_err(|error| error.into_error().into())
+}
+
+/// The students as a JSON array of objects.
+fn students_to_json(
+    students: &[StudentData],
+    schools: &HashMap<String, School>,
+    faculties: &HashMap<String, Faculty>,
+) -> serde_json::Result<Vec<u8>> {
+    let exported = students
+        .iter()
+        .map(|student| exported_student(student, schools, faculties))
+        .collect_vec();
+
+    serde_json::to_vec_pretty(&exported)
+}
+
+/// Writes the students to `file_name` in the given format.
+pub fn export_students(
+    file_name: &str,
+    format: ExportFormat,
+    students: &[StudentData],
+    schools: &HashMap<String, School>,
+    faculties: &HashMap<String, Faculty>,
+) -> Result<()> {
+    let contents = match format {
+        ExportFormat::Csv => {
+            students_to_csv(students, schools, faculties).map_err(|source| Error::Csv {
+                file: String::from(file_name),
+                source,
+            })?
+        }
+        ExportFormat::Json => {
+            students_to_json(students, schools, faculties).map_err(|source| Error::Json {
+                file: String::from(file_name),
+                source,
+            })?
+        }
+    };
+
+    fs::write(file_name, contents).map_err(|source| Error::Io {
+        file: String::from(file_name),
+        source,
+    })
+}
+
+/// Writes every faculty bucket to its own `<faculty id>.<extension>` file in
+/// `directory`, creating the directory if needed.
+pub fn export_faculties(
+    directory: &str,
+    format: ExportFormat,
+    faculty_buckets: &[(&String, &[StudentData])],
+    schools: &HashMap<String, School>,
+    faculties: &HashMap<String, Faculty>,
+) -> Result<()> {
+    fs::create_dir_all(directory).map_err(|source| Error::Io {
+        file: String::from(directory),
+        source,
+    })?;
+
+    for (faculty_id, students) in faculty_buckets {
+        export_students(
+            &format!("{directory}/{faculty_id}.{}", format.extension()),
+            format,
+            students,
+            schools,
+            faculties,
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use csv::ReaderBuilder;
+    use serde_json::Value;
+
+    use super::*;
+
+    /// A directory of its own for every test, emptied first.
+    fn test_directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("national-exams-export-{name}"));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    fn student(id: &str, faculty_id: &str) -> StudentData {
+        let mut scores = [None; ALL_SUBJECTS.len()];
+        scores[Subject::Math as usize] = Some(Score::EqualizedAndScaled {
+            scaled: 150.5,
+            equalized: 30.25,
+            uncertainty: 1.5,
+        });
+        scores[Subject::English as usize] = Some(Score::Scaled(160.0));
+
+        StudentData {
+            id: String::from(id),
+            scores,
+            overall_score: String::from("612.4"),
+            placement: None,
+            faculty_id: String::from(faculty_id),
+            grant: Some(Grant::Fifty),
+        }
+    }
+
+    fn schools_and_faculties() -> (HashMap<String, School>, HashMap<String, Faculty>) {
+        let school = School {
+            id: String::from("001"),
+            name: String::from("University"),
+            short_name: None,
+        };
+        let faculty = Faculty {
+            id: String::from("00101015"),
+            name: String::from("Mathematics"),
+            subjects: [false; ALL_SUBJECTS.len()],
+        };
+
+        (
+            HashMap::from([(String::from("001"), school)]),
+            HashMap::from([(String::from("00101015"), faculty)]),
+        )
+    }
+
+    /// The rows of a CSV file, each keyed by the header.
+    fn read_csv(path: &PathBuf) -> Vec<HashMap<String, String>> {
+        let mut reader = ReaderBuilder::new().from_path(path).unwrap();
+        let header = reader.headers().unwrap().clone();
+
+        reader
+            .records()
+            .map(|row| {
+                header
+                    .iter()
+                    .map(String::from)
+                    .zip(row.unwrap().iter().map(String::from))
+                    .collect()
+            })
+            .collect_vec()
+    }
+
+    #[test]
+    fn csv_has_every_score_column() {
+        let directory = test_directory("csv");
+        let path = directory.join("students.csv");
+        let (schools, faculties) = schools_and_faculties();
+
+        let students = [student("1", "00101015")];
+        export_students(
+            path.to_str().unwrap(),
+            ExportFormat::Csv,
+            &students,
+            &schools,
+            &faculties,
+        )
+        .unwrap();
+
+        let [row] = &read_csv(&path)[..] else {
+            panic!("expected a single row");
+        };
+        let expected = [
+            ("id", "1"),
+            ("faculty", "Mathematics"),
+            ("school_id", "001"),
+            ("school", "University"),
+            ("math_scaled", "150.5"),
+            ("math_equalized", "30.25"),
+            ("math_uncertainty", "1.5"),
+            ("english_scaled", "160"),
+            ("english_equalized", ""),
+            ("history_scaled", ""),
+            ("overall_score", "612.4"),
+            ("placement", ""),
+            ("grant", "50"),
+        ];
+        for (column, value) in expected {
+            assert_eq!(row[column], value, "{column}");
+        }
+        assert_eq!(row.len(), 8 + 3 * ALL_SUBJECTS.len());
+    }
+
+    #[test]
+    fn json_has_only_the_subjects_taken() {
+        let directory = test_directory("json");
+        let path = directory.join("students.json");
+        let (schools, faculties) = schools_and_faculties();
+
+        let students = [student("1", "00101015")];
+        export_students(
+            path.to_str().unwrap(),
+            ExportFormat::Json,
+            &students,
+            &schools,
+            &faculties,
+        )
+        .unwrap();
+
+        let exported: Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        let student = &exported[0];
+
+        assert_eq!(student["faculty_id"], "00101015");
+        assert_eq!(student["placement"], Value::Null);
+        assert_eq!(student["grant"], "50");
+        assert_eq!(student["scores"]["math"]["uncertainty"], 1.5);
+        assert_eq!(student["scores"]["english"]["scaled"], 160.0);
+        assert_eq!(student["scores"]["english"]["equalized"], Value::Null);
+        assert_eq!(
+            student["scores"].as_object().unwrap().keys().collect_vec(),
+            ["english", "math"]
+        );
+    }
+
+    #[test]
+    fn faculties_are_split_into_their_own_files() {
+        let directory = test_directory("faculties");
+        let (schools, faculties) = schools_and_faculties();
+
+        let known = [student("1", "00101015")];
+        let unknown = [student("2", "00201001"), student("3", "00201001")];
+        let (known_id, unknown_id) = (String::from("00101015"), String::from("00201001"));
+        let buckets = [(&known_id, &known[..]), (&unknown_id, &unknown[..])];
+
+        export_faculties(
+            directory.to_str().unwrap(),
+            ExportFormat::Csv,
+            &buckets,
+            &schools,
+            &faculties,
+        )
+        .unwrap();
+
+        let known = read_csv(&directory.join("00101015.csv"));
+        assert_eq!(known.iter().map(|row| &row["id"]).collect_vec(), ["1"]);
+
+        // faculties and schools the publication doesn't list are left empty
+        let unknown = read_csv(&directory.join("00201001.csv"));
+        assert_eq!(
+            unknown.iter().map(|row| &row["id"]).collect_vec(),
+            ["2", "3"]
+        );
+        assert_eq!(unknown[0]["faculty"], "");
+        assert_eq!(unknown[0]["school"], "");
+    }
+}
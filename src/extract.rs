@@ -0,0 +1,542 @@
+use std::collections::{BTreeMap, HashMap};
+
+use itertools::Itertools;
+use lopdf::{content::Content, Dictionary, Document, Encoding, Object, ObjectId};
+
+use crate::error::{Error, Result};
+
+/// The part of every page that holds the results table, relative to the page
+/// size in percents: `[TOP, LEFT, BOTTOM, RIGHT]`, measured from the top left
+/// corner. Cuts off the running header and the page numbers.
+pub const PUBLICATION_AREA: [f32; 4] = [4.2, 6.5, 97.0, 100.0];
+
+/// Two pieces of text closer than this (as a fraction of the font size) are
+/// glued together, anything further apart becomes a separate column.
+const COLUMN_GAP: f32 = 0.3;
+
+/// Pieces of text whose baselines differ by less than this (as a fraction of
+/// the font size) are considered to be on the same row.
+const ROW_TOLERANCE: f32 = 0.5;
+
+#[derive(Debug, Clone)]
+struct TextChunk {
+    x_start: f32,
+    x_end: f32,
+    y: f32,
+    font_size: f32,
+    text: String,
+}
+
+type Matrix = [f32; 6];
+
+const IDENTITY: Matrix = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    [
+        a[0] * b[0] + a[1] * b[2],
+        a[0] * b[1] + a[1] * b[3],
+        a[2] * b[0] + a[3] * b[2],
+        a[2] * b[1] + a[3] * b[3],
+        a[4] * b[0] + a[5] * b[2] + b[4],
+        a[4] * b[1] + a[5] * b[3] + b[5],
+    ]
+}
+
+fn translate(tx: f32, ty: f32) -> Matrix {
+    [1.0, 0.0, 0.0, 1.0, tx, ty]
+}
+
+fn operand(operands: &[Object], index: usize) -> f32 {
+    operands
+        .get(index)
+        .and_then(|o| o.as_float().ok())
+        .unwrap_or(0.0)
+}
+
+fn operand_matrix(operands: &[Object]) -> Matrix {
+    [
+        operand(operands, 0),
+        operand(operands, 1),
+        operand(operands, 2),
+        operand(operands, 3),
+        operand(operands, 4),
+        operand(operands, 5),
+    ]
+}
+
+/// Everything needed to turn the bytes of a text-showing operator into
+/// unicode text and a horizontal advance.
+struct FontInfo<'a> {
+    encoding: Option<Encoding<'a>>,
+    code_length: usize,
+    widths: HashMap<u32, f32>,
+    default_width: f32,
+}
+
+impl<'a> FontInfo<'a> {
+    fn new(doc: &'a Document, font: &'a Dictionary) -> Self {
+        let encoding = font.get_font_encoding(doc).ok();
+
+        let mut widths = HashMap::new();
+
+        if font.get(b"Subtype").and_then(Object::as_name).ok() == Some(b"Type0") {
+            let descendant = font
+                .get_deref(b"DescendantFonts", doc)
+                .and_then(Object::as_array)
+                .ok()
+                .and_then(|fonts| fonts.first())
+                .and_then(|f| doc.dereference(f).ok())
+                .and_then(|(_, f)| f.as_dict().ok());
+
+            let default_width = descendant
+                .and_then(|d| d.get(b"DW").and_then(Object::as_float).ok())
+                .unwrap_or(1000.0);
+
+            // W array: `c [w1 w2 ...]` or `c_first c_last w`
+            if let Some(w) = descendant
+                .and_then(|d| d.get_deref(b"W", doc).ok())
+                .and_then(|w| w.as_array().ok())
+            {
+                let mut i = 0;
+                while i + 1 < w.len() {
+                    let Ok(first) = w[i].as_i64() else {
+                        break;
+                    };
+                    match doc.dereference(&w[i + 1]).map(|(_, o)| o) {
+                        Ok(Object::Array(list)) => {
+                            for (offset, width) in list.iter().enumerate() {
+                                if let Ok(width) = width.as_float() {
+                                    widths.insert(first as u32 + offset as u32, width);
+                                }
+                            }
+                            i += 2;
+                        }
+                        Ok(last) => {
+                            let (Ok(last), Some(Ok(width))) =
+                                (last.as_i64(), w.get(i + 2).map(Object::as_float))
+                            else {
+                                break;
+                            };
+                            for code in first..=last {
+                                widths.insert(code as u32, width);
+                            }
+                            i += 3;
+                        }
+                        Err(_) => break,
+                    }
+                }
+            }
+
+            Self {
+                encoding,
+                code_length: 2,
+                widths,
+                default_width,
+            }
+        } else {
+            let first_char = font.get(b"FirstChar").and_then(Object::as_i64).unwrap_or(0);
+
+            if let Ok(list) = font.get_deref(b"Widths", doc).and_then(Object::as_array) {
+                for (offset, width) in list.iter().enumerate() {
+                    if let Ok(width) = width.as_float() {
+                        widths.insert(first_char as u32 + offset as u32, width);
+                    }
+                }
+            }
+
+            Self {
+                encoding,
+                code_length: 1,
+                widths,
+                default_width: 500.0,
+            }
+        }
+    }
+
+    fn decode(&self, bytes: &[u8]) -> String {
+        match &self.encoding {
+            Some(encoding) => Document::decode_text(encoding, bytes).unwrap_or_default(),
+            None => String::from_utf8_lossy(bytes).to_string(),
+        }
+    }
+
+    /// Codes and glyph widths (in thousandths of the font size) of a string.
+    fn glyphs(&self, bytes: &[u8]) -> Vec<(u32, f32)> {
+        bytes
+            .chunks(self.code_length)
+            .map(|chunk| {
+                let code = chunk.iter().fold(0u32, |acc, &b| acc * 256 + b as u32);
+                (code, *self.widths.get(&code).unwrap_or(&self.default_width))
+            })
+            .collect_vec()
+    }
+}
+
+#[derive(Clone)]
+struct TextState {
+    font: Option<Vec<u8>>,
+    font_size: f32,
+    char_spacing: f32,
+    word_spacing: f32,
+    horizontal_scaling: f32,
+    leading: f32,
+    rise: f32,
+}
+
+impl Default for TextState {
+    fn default() -> Self {
+        Self {
+            font: None,
+            font_size: 0.0,
+            char_spacing: 0.0,
+            word_spacing: 0.0,
+            horizontal_scaling: 1.0,
+            leading: 0.0,
+            rise: 0.0,
+        }
+    }
+}
+
+/// Runs the content stream of a page and collects every piece of text it shows,
+/// positioned in page space.
+fn page_chunks(doc: &Document, page_id: ObjectId) -> lopdf::Result<Vec<TextChunk>> {
+    let fonts: BTreeMap<Vec<u8>, FontInfo> = doc
+        .get_page_fonts(page_id)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(name, font)| (name, FontInfo::new(doc, font)))
+        .collect();
+
+    let content = Content::decode(&doc.get_page_content(page_id)?)?;
+
+    let mut chunks = Vec::new();
+
+    let mut ctm = IDENTITY;
+    let mut state = TextState::default();
+    let mut stack: Vec<(Matrix, TextState)> = Vec::new();
+    let mut text_matrix = IDENTITY;
+    let mut line_matrix = IDENTITY;
+
+    for operation in &content.operations {
+        let operands = &operation.operands;
+
+        let mut show = |text_matrix: &mut Matrix, state: &TextState, items: &[Object]| {
+            let Some(font) = state.font.as_ref().and_then(|name| fonts.get(name)) else {
+                return;
+            };
+
+            let rendering = multiply(
+                &[
+                    state.font_size * state.horizontal_scaling,
+                    0.0,
+                    0.0,
+                    state.font_size,
+                    0.0,
+                    state.rise,
+                ],
+                &multiply(text_matrix, &ctm),
+            );
+            let x_start = rendering[4];
+            let y = rendering[5];
+            let scale = (rendering[0] * rendering[0] + rendering[1] * rendering[1]).sqrt();
+
+            let mut text = String::new();
+
+            for item in items {
+                match item {
+                    Object::String(bytes, _) => {
+                        text += font.decode(bytes).as_str();
+
+                        for (code, width) in font.glyphs(bytes) {
+                            let mut advance = width / 1000.0 * state.font_size + state.char_spacing;
+                            if code == 32 && font.code_length == 1 {
+                                advance += state.word_spacing;
+                            }
+                            *text_matrix = multiply(
+                                &translate(advance * state.horizontal_scaling, 0.0),
+                                text_matrix,
+                            );
+                        }
+                    }
+                    item => {
+                        if let Ok(adjustment) = item.as_float() {
+                            *text_matrix = multiply(
+                                &translate(
+                                    -adjustment / 1000.0
+                                        * state.font_size
+                                        * state.horizontal_scaling,
+                                    0.0,
+                                ),
+                                text_matrix,
+                            );
+                        }
+                    }
+                }
+            }
+
+            let x_end = multiply(text_matrix, &ctm)[4];
+
+            if !text.is_empty() {
+                chunks.push(TextChunk {
+                    x_start,
+                    x_end,
+                    y,
+                    font_size: scale,
+                    text,
+                });
+            }
+        };
+
+        match operation.operator.as_str() {
+            "q" => stack.push((ctm, state.clone())),
+            "Q" => {
+                if let Some((saved_ctm, saved_state)) = stack.pop() {
+                    ctm = saved_ctm;
+                    state = saved_state;
+                }
+            }
+            "cm" => ctm = multiply(&operand_matrix(operands), &ctm),
+            "BT" => {
+                text_matrix = IDENTITY;
+                line_matrix = IDENTITY;
+            }
+            "Tf" => {
+                state.font = operands
+                    .first()
+                    .and_then(|o| o.as_name().ok())
+                    .map(|name| name.to_vec());
+                state.font_size = operand(operands, 1);
+            }
+            "Tc" => state.char_spacing = operand(operands, 0),
+            "Tw" => state.word_spacing = operand(operands, 0),
+            "Tz" => state.horizontal_scaling = operand(operands, 0) / 100.0,
+            "TL" => state.leading = operand(operands, 0),
+            "Ts" => state.rise = operand(operands, 0),
+            "Tm" => {
+                text_matrix = operand_matrix(operands);
+                line_matrix = text_matrix;
+            }
+            "Td" => {
+                line_matrix = multiply(
+                    &translate(operand(operands, 0), operand(operands, 1)),
+                    &line_matrix,
+                );
+                text_matrix = line_matrix;
+            }
+            "TD" => {
+                state.leading = -operand(operands, 1);
+                line_matrix = multiply(
+                    &translate(operand(operands, 0), operand(operands, 1)),
+                    &line_matrix,
+                );
+                text_matrix = line_matrix;
+            }
+            "T*" => {
+                line_matrix = multiply(&translate(0.0, -state.leading), &line_matrix);
+                text_matrix = line_matrix;
+            }
+            "Tj" => show(&mut text_matrix, &state, operands),
+            "TJ" => {
+                if let Some(Ok(items)) = operands.first().map(Object::as_array) {
+                    show(&mut text_matrix, &state, items);
+                }
+            }
+            "'" => {
+                line_matrix = multiply(&translate(0.0, -state.leading), &line_matrix);
+                text_matrix = line_matrix;
+                show(&mut text_matrix, &state, operands);
+            }
+            "\"" => {
+                state.word_spacing = operand(operands, 0);
+                state.char_spacing = operand(operands, 1);
+                line_matrix = multiply(&translate(0.0, -state.leading), &line_matrix);
+                text_matrix = line_matrix;
+                show(&mut text_matrix, &state, &operands[2.min(operands.len())..]);
+            }
+            _ => (),
+        }
+    }
+
+    Ok(chunks)
+}
+
+/// Lays the chunks out into rows from top to bottom, with the cells of each
+/// row separated by tabs.
+fn chunks_to_rows(mut chunks: Vec<TextChunk>) -> Vec<String> {
+    chunks.sort_by(|a, b| f32::total_cmp(&b.y, &a.y));
+
+    let mut rows: Vec<Vec<TextChunk>> = Vec::new();
+
+    for chunk in chunks {
+        match rows.last_mut() {
+            Some(row)
+                if (row[0].y - chunk.y).abs()
+                    < ROW_TOLERANCE * row[0].font_size.max(chunk.font_size) =>
+            {
+                row.push(chunk)
+            }
+            _ => rows.push(vec![chunk]),
+        }
+    }
+
+    rows.into_iter()
+        .map(|mut row| {
+            row.sort_by(|a, b| f32::total_cmp(&a.x_start, &b.x_start));
+
+            let mut line = String::new();
+            let mut previous_end: Option<f32> = None;
+
+            for chunk in row {
+                if let Some(previous_end) = previous_end {
+                    if chunk.x_start - previous_end > COLUMN_GAP * chunk.font_size {
+                        line.push('\t');
+                    }
+                }
+                line += chunk.text.as_str();
+                previous_end = Some(previous_end.map_or(chunk.x_end, |e| e.max(chunk.x_end)));
+            }
+
+            line
+        })
+        .collect_vec()
+}
+
+/// Squashes every run of whitespace into a single tab and drops the
+/// whitespace at the ends of the line.
+fn fix_whitespace(line: &str) -> String {
+    line.replace("\"\"", "").split_whitespace().join("\t")
+}
+
+/// How deep the `/Parent` chain of a page is followed for its MediaBox, far
+/// deeper than any real page tree, against loops.
+const MAX_PAGE_TREE_DEPTH: usize = 64;
+
+/// A4, the size of pages without a MediaBox.
+const DEFAULT_MEDIA_BOX: [f32; 4] = [0.0, 0.0, 595.0, 842.0];
+
+/// The `[left, bottom, right, top]` MediaBox of the page, inherited from its
+/// `/Pages` ancestors if the page doesn't have its own. `None` if the box has
+/// fewer than four numbers.
+fn media_box(doc: &Document, page_id: ObjectId) -> Option<[f32; 4]> {
+    let mut node = doc.get_dictionary(page_id).ok();
+
+    for _ in 0..MAX_PAGE_TREE_DEPTH {
+        let Some(dictionary) = node else {
+            break;
+        };
+
+        if let Ok(media_box) = dictionary.get(b"MediaBox") {
+            let (_, media_box) = doc.dereference(media_box).ok()?;
+            let numbers = media_box
+                .as_array()
+                .ok()?
+                .iter()
+                .map(|n| doc.dereference(n).ok()?.1.as_float().ok())
+                .collect::<Option<Vec<_>>>()?;
+
+            return numbers.get(0..4)?.try_into().ok();
+        }
+
+        node = dictionary
+            .get(b"Parent")
+            .and_then(Object::as_reference)
+            .and_then(|parent| doc.get_dictionary(parent))
+            .ok();
+    }
+
+    Some(DEFAULT_MEDIA_BOX)
+}
+
+/// Extracts the results table of every page of a publication PDF, in the
+/// tab separated format [`crate::read::read_publication_tsv`] expects.
+pub fn extract_publication_tsv(input_file: &str, area: [f32; 4]) -> Result<String> {
+    let pdf_error = |source| Error::Pdf {
+        file: String::from(input_file),
+        source,
+    };
+
+    let doc = Document::load(input_file).map_err(pdf_error)?;
+
+    let [top, left, bottom, right] = area;
+
+    let mut lines = Vec::new();
+
+    for (page, page_id) in doc.get_pages() {
+        let [page_left, page_bottom, page_right, page_top] =
+            media_box(&doc, page_id).ok_or_else(|| Error::BadMediaBox {
+                file: String::from(input_file),
+                page,
+            })?;
+        let width = page_right - page_left;
+        let height = page_top - page_bottom;
+
+        let x_min = page_left + width * left / 100.0;
+        let x_max = page_left + width * right / 100.0;
+        let y_max = page_bottom + height * (1.0 - top / 100.0);
+        let y_min = page_bottom + height * (1.0 - bottom / 100.0);
+
+        let chunks = page_chunks(&doc, page_id)
+            .map_err(pdf_error)?
+            .into_iter()
+            .filter(|c| (x_min..=x_max).contains(&c.x_start) && (y_min..=y_max).contains(&c.y))
+            .collect_vec();
+
+        lines.extend(
+            chunks_to_rows(chunks)
+                .iter()
+                .map(|line| fix_whitespace(line))
+                .filter(|line| !line.is_empty()),
+        );
+    }
+
+    Ok(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use lopdf::dictionary;
+
+    use super::*;
+
+    /// A document with a single page under a `/Pages` node, each with the
+    /// given MediaBox if any. Returns the document and the page.
+    fn page_tree(pages_box: Option<Vec<i64>>, page_box: Option<Vec<i64>>) -> (Document, ObjectId) {
+        let as_objects = |numbers: Vec<i64>| numbers.into_iter().map(Object::from).collect_vec();
+
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+
+        let mut page = dictionary! { "Type" => "Page", "Parent" => pages_id };
+        if let Some(page_box) = page_box {
+            page.set("MediaBox", as_objects(page_box));
+        }
+        let page_id = doc.add_object(page);
+
+        let mut pages =
+            dictionary! { "Type" => "Pages", "Kids" => vec![page_id.into()], "Count" => 1 };
+        if let Some(pages_box) = pages_box {
+            pages.set("MediaBox", as_objects(pages_box));
+        }
+        doc.objects.insert(pages_id, Object::Dictionary(pages));
+
+        (doc, page_id)
+    }
+
+    #[test]
+    fn media_boxes_are_inherited() {
+        let (doc, page) = page_tree(Some(vec![0, 0, 612, 792]), None);
+        assert_eq!(media_box(&doc, page), Some([0.0, 0.0, 612.0, 792.0]));
+
+        let (doc, page) = page_tree(Some(vec![0, 0, 612, 792]), Some(vec![10, 20, 300, 400]));
+        assert_eq!(media_box(&doc, page), Some([10.0, 20.0, 300.0, 400.0]));
+
+        let (doc, page) = page_tree(None, None);
+        assert_eq!(media_box(&doc, page), Some(DEFAULT_MEDIA_BOX));
+    }
+
+    #[test]
+    fn short_media_boxes_are_errors() {
+        let (doc, page) = page_tree(Some(vec![0, 0, 612]), None);
+        assert_eq!(media_box(&doc, page), None);
+    }
+}
@@ -0,0 +1,415 @@
+use std::{collections::BTreeMap, collections::HashMap, fmt};
+
+use csv::{ReaderBuilder, StringRecord, WriterBuilder};
+use itertools::Itertools;
+
+use crate::distribution::{subject_distributions, SubjectDistribution};
+use crate::error::{Error, Result};
+use crate::parsing::*;
+
+/// How many students get a grant level.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GrantLimit {
+    /// The best this many students left after the higher levels.
+    Quota(usize),
+    /// Every student left after the higher levels with at least this grant
+    /// score, a national percentile.
+    Threshold(f32),
+}
+
+/// How grants are given out.
+#[derive(Debug, Clone)]
+pub struct GrantRules {
+    /// The limit of every grant level given out, in any order. Students who
+    /// don't get one of them get [`Grant::Zero`].
+    pub limits: Vec<(Grant, GrantLimit)>,
+    /// Subjects whose equalized scores make up the grant score, indexed by
+    /// subject. `None` counts the subjects of the student's faculty.
+    ///
+    /// Faculties count from two to four subjects, so the scores are turned
+    /// into national percentiles and averaged rather than summed, and a
+    /// student isn't ahead just for having taken more subjects.
+    pub subjects: Option<[bool; ALL_SUBJECTS.len()]>,
+}
+
+impl GrantRules {
+    /// Quotas of as many students as got every grant level in the
+    /// publication, counting the subjects of each student's faculty.
+    pub fn published(students: &[StudentData]) -> Self {
+        let counts = students.iter().counts_by(published_grant);
+
+        Self {
+            limits: ALL_GRANTS
+                .into_iter()
+                .filter(|grant| *grant != Grant::Zero)
+                .map(|grant| {
+                    let quota = counts.get(&grant).copied().unwrap_or_default();
+                    (grant, GrantLimit::Quota(quota))
+                })
+                .collect_vec(),
+            subjects: None,
+        }
+    }
+
+    /// The mean national percentile of the equalized scores of the counted
+    /// subjects, among the `distributions` of every subject. `None` if the
+    /// student lacks one of them, such students get no grant.
+    pub fn grant_score(
+        &self,
+        student: &StudentData,
+        faculty: Option<&Faculty>,
+        distributions: &[SubjectDistribution; ALL_SUBJECTS.len()],
+    ) -> Option<f32> {
+        let subjects = match self.subjects {
+            Some(subjects) => subjects,
+            None => faculty?.subjects,
+        };
+
+        let percentiles = ALL_SUBJECTS
+            .iter()
+            .filter(|subject| subjects[**subject as usize])
+            .map(|subject| {
+                let equalized = student.scores[*subject as usize]?.equalized()?;
+                let distribution = distributions[*subject as usize].equalized.as_ref()?;
+                Some(distribution.percentile_rank(equalized))
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        (!percentiles.is_empty())
+            .then(|| percentiles.iter().sum::<f32>() / percentiles.len() as f32)
+    }
+}
+
+/// A missing grant is published as no grant.
+fn published_grant(student: &StudentData) -> Grant {
+    student.grant.unwrap_or(Grant::Zero)
+}
+
+/// Reads `grant,limit,value` rows, with a header, where the limit is `quota`
+/// (a number of students) or `threshold` (a grant score).
+pub fn read_grant_limits(file_name: &str) -> Result<Vec<(Grant, GrantLimit)>> {
+    let mut limits = Vec::new();
+
+    let csv_error = |source| Error::Csv {
+        file: String::from(file_name),
+        source,
+    };
+
+    let mut reader = ReaderBuilder::new()
+        .from_path(file_name)
+        .map_err(csv_error)?;
+
+    while !reader.is_done() {
+        let mut csv_line = StringRecord::new();
+        reader.read_record(&mut csv_line).map_err(csv_error)?;
+
+        let line_number = csv_line.position().map_or(0, |p| p.line() as usize);
+        let csv_line = csv_line.iter().map(str::trim).collect_vec();
+
+        let [grant, limit, value] = csv_line[..] else {
+            continue;
+        };
+
+        let grant = Grant::from(grant).ok_or_else(|| Error::UnknownGrant {
+            file: String::from(file_name),
+            line: line_number,
+            value: String::from(grant),
+        })?;
+
+        let bad_number = || Error::BadNumber {
+            file: String::from(file_name),
+            line: line_number,
+            value: String::from(value),
+        };
+
+        let limit = match limit {
+            "quota" => GrantLimit::Quota(value.parse().map_err(|_| bad_number())?),
+            "threshold" => GrantLimit::Threshold(value.parse().map_err(|_| bad_number())?),
+            _ => {
+                return Err(Error::UnknownGrantLimit {
+                    file: String::from(file_name),
+                    line: line_number,
+                    limit: String::from(limit),
+                })
+            }
+        };
+
+        limits.push((grant, limit));
+    }
+
+    Ok(limits)
+}
+
+/// The grant a student was published with and the one the rules give them.
+#[derive(Debug, Clone)]
+pub struct GrantAllocation {
+    pub student_id: String,
+    pub faculty_id: String,
+    pub published: Grant,
+    pub simulated: Grant,
+    /// The score the student was ranked by, `None` if they lack a counted
+    /// subject.
+    pub score: Option<f32>,
+}
+
+impl fmt::Display for GrantAllocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}): {}% -> {}%",
+            self.student_id, self.faculty_id, self.published, self.simulated
+        )?;
+
+        match self.score {
+            Some(score) => write!(f, " ({score:.1})"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Gives out grants again by the grant scores of the rules, percentiles among
+/// all the `students`, the highest level first. Ties go to the higher
+/// published competitive score.
+pub fn simulate_grants(
+    students: &[StudentData],
+    faculties: &HashMap<String, Faculty>,
+    rules: &GrantRules,
+) -> Vec<GrantAllocation> {
+    let distributions = subject_distributions(students);
+    let scores = students
+        .iter()
+        .map(|s| rules.grant_score(s, faculties.get(&s.faculty_id), &distributions))
+        .collect_vec();
+
+    let mut ranked = (0..students.len())
+        .filter(|i| scores[*i].is_some())
+        .sorted_by(|a, b| {
+            f32::total_cmp(&scores[*b].unwrap(), &scores[*a].unwrap()).then(f32::total_cmp(
+                &students[*b].published_score().unwrap_or(f32::NEG_INFINITY),
+                &students[*a].published_score().unwrap_or(f32::NEG_INFINITY),
+            ))
+        })
+        .peekable();
+
+    let mut simulated = vec![Grant::Zero; students.len()];
+
+    for (grant, limit) in rules.limits.iter().sorted_by(|(a, _), (b, _)| b.cmp(a)) {
+        match *limit {
+            GrantLimit::Quota(quota) => {
+                for i in ranked.by_ref().take(quota) {
+                    simulated[i] = *grant;
+                }
+            }
+            GrantLimit::Threshold(threshold) => {
+                while let Some(i) = ranked.next_if(|i| scores[*i].unwrap() >= threshold) {
+                    simulated[i] = *grant;
+                }
+            }
+        }
+    }
+
+    students
+        .iter()
+        .enumerate()
+        .map(|(i, student)| GrantAllocation {
+            student_id: student.id.clone(),
+            faculty_id: student.faculty_id.clone(),
+            published: published_grant(student),
+            simulated: simulated[i],
+            score: scores[i],
+        })
+        .collect_vec()
+}
+
+/// How many students got every grant level, indexed by grant.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct GrantTotals {
+    pub published: [usize; ALL_GRANTS.len()],
+    pub simulated: [usize; ALL_GRANTS.len()],
+}
+
+impl fmt::Display for GrantTotals {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            ALL_GRANTS
+                .iter()
+                .rev()
+                .map(|grant| format!(
+                    "{grant}%: {} -> {}",
+                    self.published[*grant as usize], self.simulated[*grant as usize]
+                ))
+                .join(", ")
+        )
+    }
+}
+
+/// Writes the allocations as CSV, a student per row.
+pub fn write_grant_allocations(file_name: &str, allocations: &[GrantAllocation]) -> Result<()> {
+    let csv_error = |source| Error::Csv {
+        file: String::from(file_name),
+        source,
+    };
+
+    let mut writer = WriterBuilder::new()
+        .from_path(file_name)
+        .map_err(csv_error)?;
+
+    writer
+        .write_record(["id", "faculty_id", "score", "published", "simulated"])
+        .map_err(csv_error)?;
+
+    for allocation in allocations {
+        writer
+            .write_record([
+                allocation.student_id.clone(),
+                allocation.faculty_id.clone(),
+                allocation.score.map(|s| s.to_string()).unwrap_or_default(),
+                allocation.published.to_string(),
+                allocation.simulated.to_string(),
+            ])
+            .map_err(csv_error)?;
+    }
+
+    writer.flush().map_err(|source| Error::Io {
+        file: String::from(file_name),
+        source,
+    })
+}
+
+/// Totals of the allocations grouped by `key`, e.g. the faculty or school id.
+pub fn grant_totals(
+    allocations: &[GrantAllocation],
+    key: impl Fn(&GrantAllocation) -> &str,
+) -> BTreeMap<String, GrantTotals> {
+    let mut totals: BTreeMap<String, GrantTotals> = BTreeMap::new();
+
+    for allocation in allocations {
+        let group = totals.entry(String::from(key(allocation))).or_default();
+        group.published[allocation.published as usize] += 1;
+        group.simulated[allocation.simulated as usize] += 1;
+    }
+
+    totals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TWO_SUBJECTS: &str = "00101015";
+    const FOUR_SUBJECTS: &str = "00101016";
+
+    fn faculties() -> HashMap<String, Faculty> {
+        let faculty = |id: &str, counted: &[Subject]| {
+            let mut subjects = [false; ALL_SUBJECTS.len()];
+            for subject in counted {
+                subjects[*subject as usize] = true;
+            }
+
+            let faculty = Faculty {
+                id: String::from(id),
+                name: String::new(),
+                subjects,
+            };
+            (String::from(id), faculty)
+        };
+
+        HashMap::from([
+            faculty(TWO_SUBJECTS, &[Subject::Georgian, Subject::English]),
+            faculty(
+                FOUR_SUBJECTS,
+                &[
+                    Subject::Georgian,
+                    Subject::English,
+                    Subject::Math,
+                    Subject::History,
+                ],
+            ),
+        ])
+    }
+
+    /// A student of the faculty with the same equalized score in each of its
+    /// subjects.
+    fn student(id: &str, faculty_id: &str, equalized: f32) -> StudentData {
+        let mut scores = [None; ALL_SUBJECTS.len()];
+        for subject in ALL_SUBJECTS {
+            if faculties()[faculty_id].subjects[subject as usize] {
+                scores[subject as usize] = Some(Score::Equalized(equalized));
+            }
+        }
+
+        StudentData {
+            id: String::from(id),
+            scores,
+            overall_score: String::new(),
+            placement: None,
+            faculty_id: String::from(faculty_id),
+            grant: None,
+        }
+    }
+
+    fn rules(limits: Vec<(Grant, GrantLimit)>) -> GrantRules {
+        GrantRules {
+            limits,
+            subjects: None,
+        }
+    }
+
+    #[test]
+    fn the_number_of_subjects_doesnt_change_the_grant_score() {
+        // both are in the middle of every subject they took
+        let students = [
+            student("two", TWO_SUBJECTS, 60.0),
+            student("four", FOUR_SUBJECTS, 60.0),
+            student("below", FOUR_SUBJECTS, 40.0),
+            student("above", FOUR_SUBJECTS, 80.0),
+        ];
+        let (faculties, distributions) = (faculties(), subject_distributions(&students));
+        let score = |student: &StudentData| {
+            rules(Vec::new()).grant_score(
+                student,
+                faculties.get(&student.faculty_id),
+                &distributions,
+            )
+        };
+
+        assert_eq!(score(&students[0]), Some(50.0));
+        assert_eq!(score(&students[1]), Some(50.0));
+    }
+
+    #[test]
+    fn grants_go_by_standing_not_by_the_number_of_subjects() {
+        let mut students = vec![
+            student("two", TWO_SUBJECTS, 90.0),
+            student("four", FOUR_SUBJECTS, 60.0),
+            student("last", FOUR_SUBJECTS, 40.0),
+        ];
+        let mut without_scores = student("none", TWO_SUBJECTS, 0.0);
+        without_scores.scores = [None; ALL_SUBJECTS.len()];
+        students.push(without_scores);
+
+        let rules = rules(vec![
+            (Grant::Fifty, GrantLimit::Threshold(50.0)),
+            (Grant::Hundred, GrantLimit::Quota(1)),
+        ]);
+        let allocations = simulate_grants(&students, &faculties(), &rules);
+
+        // georgian and english: 40, 60, 90, math and history: 40, 60
+        let scores = allocations.iter().map(|a| a.score).collect_vec();
+        let expected = [Some(250.0 / 3.0), Some(62.5), Some(125.0 / 6.0), None];
+        for (score, expected) in scores.iter().zip(expected) {
+            match (score, expected) {
+                (Some(score), Some(expected)) => assert!((score - expected).abs() < 1e-3),
+                _ => assert_eq!(*score, expected),
+            }
+        }
+
+        let simulated = allocations.iter().map(|a| a.simulated).collect_vec();
+        assert_eq!(
+            simulated,
+            [Grant::Hundred, Grant::Fifty, Grant::Zero, Grant::Zero]
+        );
+    }
+}
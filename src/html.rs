@@ -0,0 +1,852 @@
+use std::{collections::HashMap, fs};
+
+use itertools::Itertools;
+
+use crate::comparison::YearComparison;
+use crate::correlation::*;
+use crate::distribution::*;
+use crate::error::{Error, Result};
+use crate::locale::{Catalog, Lang};
+use crate::parsing::*;
+use crate::plot::faculty_plot_svg;
+use crate::summary::{FacultySummary, SchoolSummary};
+
+const CHART_WIDTH: f32 = 320.0;
+const CHART_HEIGHT: f32 = 200.0;
+const CHART_MARGIN: f32 = 32.0;
+/// Most points a scatter plot draws, larger samples are thinned out evenly.
+const SCATTER_POINTS: usize = 2000;
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; margin-bottom: 2em; }
+th, td { padding: 0.2em 0.6em; text-align: center; border-bottom: 1px solid #ddd; }
+th { cursor: pointer; user-select: none; position: sticky; top: 0; background: white; }
+th[data-order=ascending]::after { content: ' ▲'; }
+th[data-order=descending]::after { content: ' ▼'; }
+small, .gray { color: gray; }
+small.scaled { margin-left: 0.3em; }
+#search { position: sticky; top: 0; font-size: 1.1em; padding: 0.3em; z-index: 1; }
+.charts { display: flex; flex-wrap: wrap; gap: 1em; }
+.chart text { font-size: 10px; }
+";
+
+const SCRIPT: &str = r#"
+document.querySelectorAll("table.sortable th").forEach((th, column) => {
+  th.addEventListener("click", () => {
+    const table = th.closest("table");
+    const body = table.tBodies[0];
+    const ascending = th.dataset.order !== "ascending";
+    table.querySelectorAll("th").forEach(other => delete other.dataset.order);
+    th.dataset.order = ascending ? "ascending" : "descending";
+
+    const key = row => {
+      const cell = row.cells[column];
+      const value = cell.dataset.sort ?? cell.textContent;
+      const number = parseFloat(value);
+      return isNaN(number) ? value : number;
+    };
+    const rows = Array.from(body.rows, row => [key(row), row]).sort(([a], [b]) => {
+      const order = typeof a === "number" && typeof b === "number"
+        ? a - b
+        : String(a).localeCompare(String(b));
+      return ascending ? order : -order;
+    });
+    body.append(...rows.map(([, row]) => row));
+  });
+});
+
+document.getElementById("search").addEventListener("input", event => {
+  const query = event.target.value.trim();
+  document.querySelectorAll("tr[data-id]").forEach(row => {
+    row.hidden = query !== "" && !row.dataset.id.includes(query);
+  });
+  document.querySelectorAll("section.faculty").forEach(section => {
+    section.hidden = query !== "" && !section.querySelector("tr[data-id]:not([hidden])");
+  });
+});
+"#;
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn faculty_subjects(faculty: &Faculty) -> Vec<Subject> {
+    faculty
+        .subjects
+        .iter()
+        .enumerate()
+        .filter_map(|(i, &s)| if s { Some(ALL_SUBJECTS[i]) } else { None })
+        .rev()
+        .collect_vec()
+}
+
+fn score_cell(score: Option<Score>, show_uncertainty: bool) -> String {
+    match score {
+        Some(score) => format!(
+            "<td data-sort=\"{}\">{}</td>",
+            score.sort_key(),
+            score.to_html(show_uncertainty)
+        ),
+        None => String::from("<td></td>"),
+    }
+}
+
+/// A histogram of the bins with a bar per bin, its range and count shown on
+/// hover.
+fn svg_histogram(caption: &str, color: &str, bins: &[Bin]) -> String {
+    let highest = bins
+        .iter()
+        .map(|bin| bin.count)
+        .max()
+        .unwrap_or_default()
+        .max(1);
+    let (min, max) = match (bins.first(), bins.last()) {
+        (Some(first), Some(last)) => (first.start, last.end),
+        _ => (0.0, 1.0),
+    };
+
+    let width = CHART_WIDTH - 2.0 * CHART_MARGIN;
+    let height = CHART_HEIGHT - 2.0 * CHART_MARGIN;
+    let bar_width = width / bins.len().max(1) as f32;
+
+    let mut res = format!(
+        "<svg class=\"chart\" width=\"{CHART_WIDTH}\" height=\"{CHART_HEIGHT}\" viewBox=\"0 0 {CHART_WIDTH} {CHART_HEIGHT}\" xmlns=\"http://www.w3.org/2000/svg\">"
+    );
+
+    res += format!(
+        "<rect x=\"{CHART_MARGIN}\" y=\"{CHART_MARGIN}\" width=\"{width}\" height=\"{height}\" fill=\"none\" stroke=\"#999\"/>\
+<text x=\"{}\" y=\"{}\" text-anchor=\"end\">{highest}</text>\
+<text x=\"{CHART_MARGIN}\" y=\"{}\">{min:.1}</text>\
+<text x=\"{}\" y=\"{}\" text-anchor=\"end\">{max:.1}</text>\
+<text x=\"{CHART_MARGIN}\" y=\"12\" fill=\"{color}\">{}</text>",
+        CHART_MARGIN - 3.0,
+        CHART_MARGIN + 4.0,
+        CHART_HEIGHT - 3.0,
+        CHART_MARGIN + width,
+        CHART_HEIGHT - 3.0,
+        escape(caption)
+    )
+    .as_str();
+
+    for (i, bin) in bins.iter().enumerate() {
+        let bar_height = height * bin.count as f32 / highest as f32;
+
+        res += format!(
+            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{bar_height:.1}\" fill=\"{color}\"><title>{:.1}–{:.1}: {}</title></rect>",
+            CHART_MARGIN + bar_width * i as f32,
+            CHART_MARGIN + height - bar_height,
+            bar_width,
+            bin.start,
+            bin.end,
+            bin.count
+        )
+        .as_str();
+    }
+
+    res += "</svg>";
+
+    res
+}
+
+/// A scatter plot of the pairs, the first value of a pair on the x axis.
+fn svg_scatter(subjects: (Subject, Subject), pairs: &[(f32, f32)], t: &Catalog) -> String {
+    let bounds = |values: &mut dyn Iterator<Item = f32>| {
+        let (min, max) = values.fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), v| {
+            (min.min(v), max.max(v))
+        });
+        if min < max {
+            (min, max)
+        } else {
+            (min - 1.0, min + 1.0)
+        }
+    };
+    let (min_x, max_x) = bounds(&mut pairs.iter().map(|(x, _)| *x));
+    let (min_y, max_y) = bounds(&mut pairs.iter().map(|(_, y)| *y));
+
+    let width = CHART_WIDTH - 2.0 * CHART_MARGIN;
+    let height = CHART_HEIGHT - 2.0 * CHART_MARGIN;
+    let x = |v: f32| CHART_MARGIN + width * (v - min_x) / (max_x - min_x);
+    let y = |v: f32| CHART_MARGIN + height * (max_y - v) / (max_y - min_y);
+
+    let mut res = format!(
+        "<svg class=\"chart\" width=\"{CHART_WIDTH}\" height=\"{CHART_HEIGHT}\" viewBox=\"0 0 {CHART_WIDTH} {CHART_HEIGHT}\" xmlns=\"http://www.w3.org/2000/svg\">"
+    );
+
+    res += format!(
+        "<rect x=\"{CHART_MARGIN}\" y=\"{CHART_MARGIN}\" width=\"{width}\" height=\"{height}\" fill=\"none\" stroke=\"#999\"/>\
+<text x=\"{}\" y=\"{}\" text-anchor=\"end\">{max_y:.1}</text>\
+<text x=\"{}\" y=\"{}\" text-anchor=\"end\">{min_y:.1}</text>\
+<text x=\"{CHART_MARGIN}\" y=\"{}\">{min_x:.1}</text>\
+<text x=\"{}\" y=\"{}\" text-anchor=\"end\">{max_x:.1}</text>\
+<text x=\"{CHART_MARGIN}\" y=\"12\">{} / {}</text>",
+        CHART_MARGIN - 3.0,
+        CHART_MARGIN + 4.0,
+        CHART_MARGIN - 3.0,
+        CHART_MARGIN + height,
+        CHART_HEIGHT - 3.0,
+        CHART_MARGIN + width,
+        CHART_HEIGHT - 3.0,
+        escape(t.subject(subjects.0)),
+        escape(t.subject(subjects.1))
+    )
+    .as_str();
+
+    for (px, py) in pairs
+        .iter()
+        .step_by(pairs.len().div_ceil(SCATTER_POINTS).max(1))
+    {
+        res += format!(
+            "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"1\" fill-opacity=\"0.3\"/>",
+            x(*px),
+            y(*py)
+        )
+        .as_str();
+    }
+
+    res += "</svg>";
+
+    res
+}
+
+/// Writes the descaled lists as a single self-contained HTML page, the same
+/// way `PDFMaker` of `descale-and-sort` writes the book.
+pub struct HTMLMaker {
+    output_file: String,
+    top_list_string: Option<String>,
+    summary_string: Option<String>,
+    schools_string: Option<String>,
+    distributions_string: Option<String>,
+    correlations_string: Option<String>,
+    comparison_string: Option<String>,
+    faculty_strings: Option<Vec<(String, String)>>,
+    graph_strings: HashMap<String, String>,
+    show_uncertainty: bool,
+    lang: Lang,
+}
+
+impl HTMLMaker {
+    /// `output_file` is the name of the page without the `.html` extension.
+    pub fn new(output_file: String) -> Self {
+        Self {
+            output_file,
+            top_list_string: None,
+            summary_string: None,
+            schools_string: None,
+            distributions_string: None,
+            correlations_string: None,
+            comparison_string: None,
+            faculty_strings: None,
+            graph_strings: HashMap::new(),
+            show_uncertainty: false,
+            lang: Lang::Ka,
+        }
+    }
+
+    pub fn show_uncertainty(&mut self, show_uncertainty: bool) -> &mut Self {
+        self.show_uncertainty = show_uncertainty;
+
+        self
+    }
+
+    /// The language of the page, set before anything is written.
+    pub fn lang(&mut self, lang: Lang) -> &mut Self {
+        self.lang = lang;
+
+        self
+    }
+
+    pub fn save(&mut self) -> Result<&mut Self> {
+        let t = self.lang.catalog();
+        let mut body = Vec::new();
+
+        if let Some(faculty_strings) = &self.faculty_strings {
+            body.push(format!(
+                "<details><summary>{}</summary><ul>{}</ul></details>",
+                t.faculties,
+                faculty_strings
+                    .iter()
+                    .map(|(faculty_id, _)| format!(
+                        "<li><a href=\"#faculty-{faculty_id}\">{faculty_id}</a></li>"
+                    ))
+                    .join("")
+            ));
+        }
+
+        if let Some(top_list) = &self.top_list_string {
+            body.push(top_list.clone());
+        }
+
+        if let Some(summary) = &self.summary_string {
+            body.push(summary.clone());
+        }
+
+        if let Some(schools) = &self.schools_string {
+            body.push(schools.clone());
+        }
+
+        if let Some(distributions) = &self.distributions_string {
+            body.push(distributions.clone());
+        }
+
+        if let Some(correlations) = &self.correlations_string {
+            body.push(correlations.clone());
+        }
+
+        if let Some(comparison) = &self.comparison_string {
+            body.push(comparison.clone());
+        }
+
+        if let Some(faculty_strings) = &self.faculty_strings {
+            for (faculty_id, faculty_string) in faculty_strings {
+                body.push(format!(
+                    "<section class=\"faculty\" id=\"faculty-{faculty_id}\">{faculty_string}{}</section>",
+                    self.graph_strings
+                        .get(faculty_id)
+                        .map_or("", String::as_str)
+                ));
+            }
+        }
+
+        let page = format!(
+            "<!DOCTYPE html>
+<html lang=\"{}\">
+<head>
+<meta charset=\"utf-8\">
+<title>{}</title>
+<style>{STYLE}</style>
+</head>
+<body>
+<input id=\"search\" type=\"search\" placeholder=\"{}\">
+{}
+<script>{SCRIPT}</script>
+</body>
+</html>
+",
+            t.code,
+            t.title,
+            t.search,
+            if !body.is_empty() {
+                body.join("\n")
+            } else {
+                String::from("no data")
+            }
+        );
+
+        let html_file = format!("{}.html", self.output_file);
+        fs::write(&html_file, page).map_err(|source| Error::Io {
+            file: html_file.clone(),
+            source,
+        })?;
+
+        Ok(self)
+    }
+
+    pub fn write_graphs(
+        &mut self,
+        faculty_buckets: &[(&String, &[StudentData])],
+        faculties: &HashMap<String, Faculty>,
+    ) -> Result<&mut Self> {
+        println!("writing graphs... ");
+
+        for (faculty_id, students) in faculty_buckets {
+            let Some(faculty) = faculties.get(*faculty_id) else {
+                continue;
+            };
+
+            if students.len() > 1 {
+                let plot = faculty_plot_svg(students, faculty, self.lang.catalog())?;
+
+                self.graph_strings.insert(
+                    String::from(faculty_id.as_str()),
+                    format!("<div class=\"charts\">{plot}</div>"),
+                );
+            }
+        }
+
+        println!("done.");
+        Ok(self)
+    }
+
+    pub fn write_faculties(
+        &mut self,
+        faculty_buckets: &[(&String, &[StudentData])],
+        schools: &HashMap<String, School>,
+        faculties: &HashMap<String, Faculty>,
+    ) -> &mut Self {
+        let t = self.lang.catalog();
+        let mut full_res = Vec::new();
+
+        for (faculty_id, students) in faculty_buckets {
+            let faculty_id = String::from(faculty_id.as_str());
+            let Some(faculty) = faculties.get(&faculty_id) else {
+                continue;
+            };
+            let school_name = schools
+                .get(school_id(&faculty_id))
+                .map_or("", |school| school.name.trim());
+
+            let subjects = faculty_subjects(faculty);
+
+            let mut res = format!(
+                "<h2>{} - {}</h2>\n<h3>{}</h3>\n<table class=\"sortable\">\n<thead><tr>{}</tr></thead>\n<tbody>",
+                faculty.id,
+                escape(school_name),
+                escape(faculty.name.trim()),
+                [
+                    vec!["", t.place, t.id],
+                    subjects.iter().map(|a| t.subject(*a)).collect_vec(),
+                    vec![t.competitive, t.grant]
+                ]
+                .concat()
+                .iter()
+                .map(|header| format!("<th>{header}</th>"))
+                .join("")
+            );
+
+            for (student_index, student_data) in students.iter().enumerate() {
+                res += format!(
+                    "\n<tr data-id=\"{}\"><td class=\"gray\">{}</td><td>{}</td><td class=\"gray\">{}</td>{}<td>{}</td><td>{}</td></tr>",
+                    escape(&student_data.id),
+                    student_index + 1,
+                    student_data
+                        .placement
+                        .map(|p| p.to_string())
+                        .unwrap_or_default(),
+                    escape(&student_data.id),
+                    subjects
+                        .iter()
+                        .map(|subject| score_cell(
+                            student_data.scores[*subject as usize],
+                            self.show_uncertainty
+                        ))
+                        .join(""),
+                    student_data.overall_score,
+                    match student_data.grant {
+                        Some(x) => x.to_string(),
+                        None => String::from(""),
+                    }
+                )
+                .as_str();
+            }
+
+            res += "\n</tbody>\n</table>";
+
+            full_res.push((faculty_id, res));
+        }
+
+        self.faculty_strings = Some(full_res);
+
+        self
+    }
+
+    pub fn write_summary(
+        &mut self,
+        summaries: &[FacultySummary],
+        schools: &HashMap<String, School>,
+    ) -> &mut Self {
+        let t = self.lang.catalog();
+        let mut res = format!(
+            "<section id=\"summary\">
+<h2>{}</h2>
+<table class=\"sortable\">
+<thead><tr><th>{}</th><th>{}</th><th>{}</th><th>{}</th><th>{} 100/70/50</th><th>{}: {} / {}</th></tr></thead>
+<tbody>",
+            t.faculty_summary,
+            t.code_label,
+            t.faculty,
+            t.admitted,
+            t.competitive,
+            t.grant,
+            t.subject,
+            t.minimum,
+            t.median,
+        );
+
+        for summary in summaries {
+            let school = faculty_school(schools, &summary.faculty_id);
+            let school_name = school.short_name.unwrap_or(school.name);
+
+            let subjects = ALL_SUBJECTS
+                .iter()
+                .rev()
+                .filter_map(|subject| {
+                    let stats = summary.subjects[*subject as usize]?;
+                    Some(format!(
+                        "{}: {:.1} / {:.1}",
+                        t.subject(*subject),
+                        stats.cutoff,
+                        stats.median
+                    ))
+                })
+                .join("<br>");
+
+            res += format!(
+                "\n<tr><td><a href=\"#faculty-{}\">{}</a></td><td>{}<br><small>{}</small></td><td>{}</td><td data-sort=\"{}\">{}</td><td>{}</td><td>{subjects}</td></tr>",
+                summary.faculty_id,
+                summary.faculty_id,
+                escape(&summary.name),
+                escape(&school_name),
+                summary.admitted,
+                summary.lowest_score.unwrap_or_default(),
+                match (summary.lowest_score, summary.highest_score) {
+                    (Some(lowest), Some(highest)) => format!("{lowest:.1} – {highest:.1}"),
+                    _ => String::new(),
+                },
+                [Grant::Hundred, Grant::Seventy, Grant::Fifty]
+                    .map(|grant| summary.grants[grant as usize].to_string())
+                    .join("/"),
+            )
+            .as_str();
+        }
+
+        res += "\n</tbody>\n</table>\n</section>";
+
+        self.summary_string = Some(res);
+
+        self
+    }
+
+    pub fn write_schools(
+        &mut self,
+        summaries: &[SchoolSummary],
+        schools: &HashMap<String, School>,
+    ) -> &mut Self {
+        let t = self.lang.catalog();
+        let mut res = format!(
+            "<section id=\"schools\">
+<h2>{}</h2>
+<table class=\"sortable\">
+<thead><tr><th>#</th><th>{}</th><th>{}</th><th>{}</th><th>{}</th><th>{}</th><th>{}: {} / {}</th></tr></thead>
+<tbody>",
+            t.school_ranking,
+            t.school,
+            t.faculty,
+            t.admitted,
+            t.grant,
+            t.percentile,
+            t.subject,
+            t.mean,
+            t.median,
+        );
+
+        for summary in summaries {
+            let school = school_or_id(schools, &summary.school_id);
+
+            let subjects = ALL_SUBJECTS
+                .iter()
+                .rev()
+                .filter_map(|subject| {
+                    let stats = summary.subjects[*subject as usize]?;
+                    Some(format!(
+                        "{}: {:.1} / {:.1}",
+                        t.subject(*subject),
+                        stats.mean,
+                        stats.median
+                    ))
+                })
+                .join("<br>");
+
+            res += format!(
+                "\n<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td data-sort=\"{}\">{:.0}%</td><td>{}</td><td>{subjects}</td></tr>",
+                summary.rank.map(|r| r.to_string()).unwrap_or_default(),
+                escape(&school.short_name.unwrap_or(school.name)),
+                summary.faculties,
+                summary.enrolled,
+                summary.grant_share,
+                summary.grant_share * 100.0,
+                summary
+                    .selectivity
+                    .map(|s| format!("{s:.1}"))
+                    .unwrap_or_default(),
+            )
+            .as_str();
+        }
+
+        res += "\n</tbody>\n</table>\n</section>";
+
+        self.schools_string = Some(res);
+
+        self
+    }
+
+    pub fn write_distributions(
+        &mut self,
+        distributions: &[SubjectDistribution],
+        graphs: bool,
+    ) -> &mut Self {
+        let t = self.lang.catalog();
+        let mut res = format!(
+            "<section id=\"distributions\">
+<h2>{}</h2>
+<table>
+<thead><tr><th>{}</th><th></th><th>{}</th><th>{}</th><th>{}</th>{}</tr></thead>
+<tbody>",
+            t.distributions,
+            t.subject,
+            t.count,
+            t.mean,
+            t.standard_deviation,
+            KEY_PERCENTILES
+                .iter()
+                .map(|p| format!("<th>p{p}</th>"))
+                .join("")
+        );
+        let mut charts = Vec::new();
+
+        for distribution in distributions {
+            for (caption, color, scores) in [
+                (t.scaled, String::from("gray"), &distribution.scaled),
+                (
+                    t.equalized,
+                    distribution.subject.color(),
+                    &distribution.equalized,
+                ),
+            ] {
+                let Some(scores) = scores else {
+                    continue;
+                };
+
+                res += format!(
+                    "\n<tr><td>{}</td><td>{caption}</td><td>{}</td><td>{:.1}</td><td>{:.1}</td>{}</tr>",
+                    t.subject(distribution.subject),
+                    scores.len(),
+                    scores.mean(),
+                    scores.standard_deviation(),
+                    KEY_PERCENTILES
+                        .iter()
+                        .map(|p| format!("<td>{:.1}</td>", scores.percentile(*p)))
+                        .join("")
+                )
+                .as_str();
+
+                if graphs {
+                    charts.push(svg_histogram(
+                        &format!("{} ({caption})", t.subject(distribution.subject)),
+                        &color,
+                        &scores.histogram(HISTOGRAM_BINS),
+                    ));
+                }
+            }
+        }
+
+        res += "\n</tbody>\n</table>";
+
+        if !charts.is_empty() {
+            res += format!("\n<div class=\"charts\">{}</div>", charts.join("")).as_str();
+        }
+
+        res += "\n</section>";
+
+        self.distributions_string = Some(res);
+
+        self
+    }
+
+    pub fn write_correlations(
+        &mut self,
+        students: &[StudentData],
+        correlations: &[Correlation],
+        combinations: &[SubjectCombination],
+        graphs: bool,
+    ) -> &mut Self {
+        let optional = |value: Option<f32>| value.map(|v| format!("{v:.2}")).unwrap_or_default();
+
+        let t = self.lang.catalog();
+        let mut res = format!(
+            "<section id=\"correlations\">
+<h2>{}</h2>
+<table class=\"sortable\">
+<thead><tr><th>{}</th><th>{}</th><th>{}</th><th>{}</th></tr></thead>
+<tbody>",
+            t.correlations, t.subjects_label, t.students, t.pearson, t.spearman,
+        );
+
+        for correlation in correlations {
+            res += format!(
+                "\n<tr><td>{} – {}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                t.subject(correlation.subjects.0),
+                t.subject(correlation.subjects.1),
+                correlation.students,
+                optional(correlation.pearson),
+                optional(correlation.spearman),
+            )
+            .as_str();
+        }
+
+        res += format!(
+            "\n</tbody>\n</table>
+<h3>{}</h3>
+<table class=\"sortable\">
+<thead><tr><th>{}</th><th>{}</th><th>{}</th></tr></thead>
+<tbody>",
+            t.combinations, t.subjects_label, t.faculty, t.admitted,
+        )
+        .as_str();
+
+        for combination in combinations {
+            res += format!(
+                "\n<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+                combination.subjects().map(|s| t.subject(s)).join(", "),
+                combination.faculties,
+                combination.students,
+            )
+            .as_str();
+        }
+
+        res += "\n</tbody>\n</table>";
+
+        if graphs {
+            res += format!(
+                "\n<div class=\"charts\">{}</div>",
+                correlations
+                    .iter()
+                    .map(|c| svg_scatter(c.subjects, &paired_scores(students, c.subjects), t))
+                    .join("")
+            )
+            .as_str();
+        }
+
+        res += "\n</section>";
+
+        self.correlations_string = Some(res);
+
+        self
+    }
+
+    /// A row per subject, faculty and year, with the changes since the year
+    /// before in parentheses.
+    pub fn write_comparisons(&mut self, comparisons: &[YearComparison]) -> &mut Self {
+        let optional = |value: Option<f32>| value.map(|v| format!("{v:.1}")).unwrap_or_default();
+        let optional_change = |value: Option<f32>| {
+            value
+                .map(|v| format!(" <small>({v:+.1})</small>"))
+                .unwrap_or_default()
+        };
+
+        let t = self.lang.catalog();
+        let mut res = format!(
+            "<section id=\"comparison\">
+<h2>{}</h2>
+<table class=\"sortable\">
+<thead><tr><th>{}</th><th>{}</th><th>{}</th><th>{}</th><th>{}</th><th>{}</th><th>{}</th></tr></thead>
+<tbody>",
+            t.comparison,
+            t.faculty,
+            t.subject,
+            t.year,
+            t.students,
+            t.cutoff,
+            t.median,
+            t.grant_threshold,
+        );
+
+        for comparison in comparisons {
+            let faculty = match &comparison.faculty_id {
+                Some(faculty_id) => format!(
+                    "{faculty_id}<br><small>{}</small>",
+                    escape(&comparison.name)
+                ),
+                None => String::from(t.all_students),
+            };
+
+            for (year, summary, change) in comparison.with_changes() {
+                res += format!(
+                    "\n<tr><td>{faculty}</td><td>{}</td><td>{year}</td><td>{}</td><td data-sort=\"{}\">{:.1}{}</td><td data-sort=\"{}\">{:.1}{}</td><td data-sort=\"{}\">{}{}</td></tr>",
+                    t.subject(comparison.subject),
+                    summary.students,
+                    summary.cutoff,
+                    summary.cutoff,
+                    optional_change(change.map(|c| c.cutoff)),
+                    summary.median,
+                    summary.median,
+                    optional_change(change.map(|c| c.median)),
+                    summary.grant_threshold.unwrap_or_default(),
+                    optional(summary.grant_threshold),
+                    optional_change(change.and_then(|c| c.grant_threshold)),
+                )
+                .as_str();
+            }
+        }
+
+        res += "\n</tbody>\n</table>\n</section>";
+
+        self.comparison_string = Some(res);
+
+        self
+    }
+
+    pub fn write_top_list(
+        &mut self,
+        students: &[StudentData],
+        schools: &HashMap<String, School>,
+        faculties: &HashMap<String, Faculty>,
+    ) -> &mut Self {
+        let t = self.lang.catalog();
+        let mut res = format!(
+            "<section id=\"top-list\">
+<h2>{}</h2>
+<table class=\"sortable\">
+<thead><tr><th>#</th><th>{}</th>{}<th>{}</th><th>{}</th><th>{}</th></tr></thead>
+<tbody>",
+            t.top_list,
+            t.id,
+            (1..=4)
+                .map(|i| format!("<th>{} {i}</th>", t.subject))
+                .join(""),
+            t.competitive,
+            t.faculty,
+            t.grant,
+        );
+
+        for (student_index, student) in students.iter().enumerate() {
+            let Some(faculty) = faculties.get(&student.faculty_id) else {
+                continue;
+            };
+            let school_name = schools
+                .get(school_id(&student.faculty_id))
+                .map_or(String::new(), |school| {
+                    school.short_name.clone().unwrap_or(school.name.clone())
+                });
+
+            let subjects = faculty_subjects(faculty);
+
+            let scores = (0..4)
+                .map(|i| match subjects.get(i) {
+                    Some(subject) => match student.scores[*subject as usize] {
+                        Some(score) => format!(
+                            "<td data-sort=\"{}\">{}<br><small>{}</small></td>",
+                            score.sort_key(),
+                            score.to_html(self.show_uncertainty),
+                            t.subject(*subject)
+                        ),
+                        None => String::from("<td></td>"),
+                    },
+                    None => String::from("<td></td>"),
+                })
+                .join("");
+
+            res += format!(
+                "\n<tr data-id=\"{}\"><td>{}</td><td class=\"gray\">{}</td>{scores}<td>{}</td><td><a href=\"#faculty-{}\">{}</a><br><small>{}</small></td><td>{}</td></tr>",
+                escape(&student.id),
+                student_index + 1,
+                escape(&student.id),
+                student.overall_score,
+                faculty.id,
+                escape(&faculty.name),
+                escape(&school_name),
+                student.grant.unwrap_or(Grant::Zero)
+            )
+            .as_str();
+        }
+
+        res += "\n</tbody>\n</table>\n</section>";
+
+        self.top_list_string = Some(res);
+
+        self
+    }
+}
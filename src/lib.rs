@@ -0,0 +1,65 @@
+//! Reading, descaling and sorting of the national exams' ranked results
+//! publications.
+//!
+//! A publication is read with [`read::read_publication_tsv`] (after
+//! [`read::parse_publication_pdf`] or [`cache::Cache::publication_tsv`] has
+//! turned the PDF into TSV) or from a workbook with
+//! [`spreadsheet::read_publication_sheet`], its scaled scores are turned back
+//! into equalized ones with
+//! [`processing::descale_with_independent_data`] (using one of the
+//! [`descaling::DescalingModel`]s) and the students are ranked
+//! with [`processing::sort_students`], or by the competitive score recomputed
+//! from equalized scores with the [`competitive::faculty_weights`] using
+//! [`processing::sort_students_by`]. The result can be kept as a
+//! [`dataset::Dataset`] file. How well the anchors of the descaling
+//! data agree with each other is checked with
+//! [`validation::validate_anchors`]. The results can be written out as CSV or
+//! JSON with [`export::export_students`], or as a self-contained HTML page with
+//! [`html::HTMLMaker`]. What it took to get into every faculty is summarized
+//! with [`summary::summarize_faculties`], and schools are compared with
+//! [`summary::summarize_schools`]. The spread of every subject's scores is
+//! described by [`distribution::subject_distributions`], and where a single
+//! score stands, nationally and in every faculty, by
+//! [`query::score_standing`]. How subjects relate is seen from
+//! [`correlation::subject_correlations`] and
+//! [`correlation::subject_combinations`]. What admissions would have looked
+//! like with equalized scores is simulated with
+//! [`admission::simulate_admissions`], and grants are given out again with
+//! [`grants::simulate_grants`]. Datasets of several exam years, or their
+//! publications and descaling data, are read together with
+//! [`dataset::read_datasets`] and compared with
+//! [`comparison::compare_years`]. The parts of the book are written from
+//! [`templates::Templates`], in the language of a [`locale::Lang`], and its
+//! plots are drawn with [`plot::save_faculty_plot`] and the others of
+//! [`plot`].
+
+pub mod admission;
+pub mod cache;
+pub mod comparison;
+pub mod competitive;
+pub mod correlation;
+pub mod dataset;
+pub mod descaling;
+pub mod distribution;
+pub mod error;
+pub mod estimation;
+pub mod export;
+pub mod extract;
+pub mod grants;
+pub mod html;
+pub mod locale;
+pub mod parsing;
+pub mod plot;
+pub mod processing;
+pub mod query;
+pub mod read;
+pub mod spreadsheet;
+pub mod summary;
+pub mod templates;
+pub mod validation;
+
+pub use descaling::{DescalingCurve, DescalingModel};
+pub use error::{Error, Result};
+pub use parsing::{
+    Faculty, Grant, School, Score, StudentData, Subject, SubjectStats, ALL_GRANTS, ALL_SUBJECTS,
+};
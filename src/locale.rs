@@ -0,0 +1,227 @@
+use std::{
+    fmt,
+    str::FromStr,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+use serde::Serialize;
+
+use crate::parsing::{Subject, ALL_SUBJECTS};
+
+/// The language of the console, see [`Lang::console`].
+static CONSOLE_LANG: AtomicU8 = AtomicU8::new(0);
+
+/// Language the reports are written in.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lang {
+    #[default]
+    Ka,
+    En,
+}
+
+impl Lang {
+    pub fn catalog(&self) -> &'static Catalog {
+        match self {
+            Lang::Ka => &GEORGIAN,
+            Lang::En => &ENGLISH,
+        }
+    }
+
+    /// The language subjects are printed in by their `Display`, and so the
+    /// messages and the errors naming them, Georgian unless
+    /// [`Lang::set_console`] was called.
+    pub fn console() -> Self {
+        match CONSOLE_LANG.load(Ordering::Relaxed) {
+            1 => Lang::En,
+            _ => Lang::Ka,
+        }
+    }
+
+    pub fn set_console(self) {
+        let code = match self {
+            Lang::Ka => 0,
+            Lang::En => 1,
+        };
+
+        CONSOLE_LANG.store(code, Ordering::Relaxed);
+    }
+}
+
+impl fmt::Display for Lang {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.catalog().code)
+    }
+}
+
+impl FromStr for Lang {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "ka" => Ok(Lang::Ka),
+            "en" => Ok(Lang::En),
+            _ => Err(format!("unknown language `{s}`, expected `ka` or `en`")),
+        }
+    }
+}
+
+/// The words of the reports in one language. Templates get it as `t`.
+#[derive(Debug, Serialize)]
+pub struct Catalog {
+    /// ISO 639-1 code, as HTML and Typst take it.
+    pub code: &'static str,
+    /// The language's name for LaTeX's babel.
+    pub babel: &'static str,
+    /// Names of the subjects, indexed by subject. Not the names
+    /// [`Subject::from`] reads from publications, those stay Georgian.
+    pub subjects: [&'static str; ALL_SUBJECTS.len()],
+
+    pub title: &'static str,
+    pub search: &'static str,
+    pub top_list: &'static str,
+    pub faculty_summary: &'static str,
+    pub school_ranking: &'static str,
+    pub distributions: &'static str,
+    pub correlations: &'static str,
+    pub combinations: &'static str,
+    pub comparison: &'static str,
+
+    pub place: &'static str,
+    pub id: &'static str,
+    pub code_label: &'static str,
+    pub subject: &'static str,
+    pub subjects_label: &'static str,
+    pub competitive: &'static str,
+    pub grant: &'static str,
+    pub faculty: &'static str,
+    pub faculties: &'static str,
+    pub school: &'static str,
+    pub admitted: &'static str,
+    pub students: &'static str,
+    pub percentile: &'static str,
+    pub count: &'static str,
+    pub mean: &'static str,
+    pub median: &'static str,
+    pub minimum: &'static str,
+    pub standard_deviation: &'static str,
+    pub scaled: &'static str,
+    pub equalized: &'static str,
+    pub pearson: &'static str,
+    pub spearman: &'static str,
+    pub year: &'static str,
+    pub cutoff: &'static str,
+    pub grant_threshold: &'static str,
+    pub all_students: &'static str,
+}
+
+impl Catalog {
+    pub fn subject(&self, subject: Subject) -> &'static str {
+        self.subjects[subject as usize]
+    }
+}
+
+pub const GEORGIAN: Catalog = Catalog {
+    code: "ka",
+    babel: "georgian",
+    subjects: [
+        "მათემატიკა",
+        "ისტორია",
+        "ფიზიკა",
+        "ბიოლოგია",
+        "ქიმია",
+        "გეოგრაფია",
+        "ლიტერატურა",
+        "უცხოური",
+        "ქართული",
+    ],
+
+    title: "დესკალირებული სია",
+    search: "აბიტურიენტის ნომერი",
+    top_list: "აბიტურიენტები საკონკურსო ქულის მიხედვით კლებადობით",
+    faculty_summary: "ფაკულტეტების შეჯამება",
+    school_ranking: "უნივერსიტეტები სელექციურობის მიხედვით",
+    distributions: "საგნების ქულების განაწილება",
+    correlations: "საგნებს შორის კორელაცია",
+    combinations: "საგნების კომბინაციები",
+    comparison: "წლების შედარება",
+
+    place: "ადგილი",
+    id: "ნომერი",
+    code_label: "კოდი",
+    subject: "საგანი",
+    subjects_label: "საგნები",
+    competitive: "საკონკურსო",
+    grant: "გრანტი",
+    faculty: "ფაკულტეტი",
+    faculties: "ფაკულტეტები",
+    school: "უნივერსიტეტი",
+    admitted: "ჩარიცხული",
+    students: "აბიტურიენტი",
+    percentile: "პროცენტილი",
+    count: "რაოდენობა",
+    mean: "საშუალო",
+    median: "მედიანა",
+    minimum: "მინიმუმი",
+    standard_deviation: "სტ. გადახრა",
+    scaled: "სკალირებული",
+    equalized: "გათანაბრებული",
+    pearson: "პირსონი",
+    spearman: "სპირმენი",
+    year: "წელი",
+    cutoff: "ზღვარი",
+    grant_threshold: "გრანტის ზღვარი",
+    all_students: "ყველა აბიტურიენტი",
+};
+
+pub const ENGLISH: Catalog = Catalog {
+    code: "en",
+    babel: "english",
+    subjects: [
+        "Mathematics",
+        "History",
+        "Physics",
+        "Biology",
+        "Chemistry",
+        "Geography",
+        "Literature",
+        "Foreign language",
+        "Georgian",
+    ],
+
+    title: "Descaled list",
+    search: "Student ID",
+    top_list: "Students by competitive score, highest first",
+    faculty_summary: "Faculty summary",
+    school_ranking: "Universities by selectivity",
+    distributions: "Distribution of subject scores",
+    correlations: "Correlation between subjects",
+    combinations: "Subject combinations",
+    comparison: "Comparison of the years",
+
+    place: "Place",
+    id: "ID",
+    code_label: "Code",
+    subject: "Subject",
+    subjects_label: "Subjects",
+    competitive: "Competitive",
+    grant: "Grant",
+    faculty: "Faculty",
+    faculties: "Faculties",
+    school: "University",
+    admitted: "Admitted",
+    students: "Students",
+    percentile: "Percentile",
+    count: "Count",
+    mean: "Mean",
+    median: "Median",
+    minimum: "Minimum",
+    standard_deviation: "Std. dev.",
+    scaled: "scaled",
+    equalized: "equalized",
+    pearson: "Pearson",
+    spearman: "Spearman",
+    year: "Year",
+    cutoff: "Cutoff",
+    grant_threshold: "Grant threshold",
+    all_students: "All students",
+};
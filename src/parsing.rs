@@ -1,8 +1,11 @@
-use std::{cmp::Ordering, hash::Hash};
+use std::{cmp::Ordering, collections::HashMap, fmt, hash::Hash};
 
 use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, PartialEq, Clone, Copy, Hash, Eq)]
+use crate::locale::Lang;
+
+#[derive(Debug, PartialEq, Clone, Copy, Hash, Eq, Serialize, Deserialize)]
 pub enum Subject {
     Math,
     History,
@@ -27,24 +30,15 @@ pub const ALL_SUBJECTS: [Subject; 9] = [
     Subject::Georgian,
 ];
 
-impl ToString for Subject {
-    fn to_string(&self) -> String {
-        use Subject as S;
-        String::from(match self {
-            S::Georgian => "ქართული",
-            S::English => "უცხოური",
-            S::Math => "მათემატიკა",
-            S::History => "ისტორია",
-            S::Physics => "ფიზიკა",
-            S::Chemistry => "ქიმია",
-            S::Biology => "ბიოლოგია",
-            S::Geography => "გეოგრაფია",
-            S::Literature => "ლიტერატურა",
-        })
+/// The name in the language of the console, see [`Lang::console`].
+impl fmt::Display for Subject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(Lang::console().catalog().subject(*self))
     }
 }
 
 impl Subject {
+    /// The subject named `s` in publications and descaling data.
     pub fn from(s: &str) -> Option<Self> {
         use Subject as S;
         match s {
@@ -61,6 +55,29 @@ impl Subject {
         }
     }
 
+    /// Short ASCII name, for column names and keys in exported data.
+    pub fn key(&self) -> &'static str {
+        use Subject as S;
+        match self {
+            S::Georgian => "georgian",
+            S::English => "english",
+            S::Math => "math",
+            S::History => "history",
+            S::Physics => "physics",
+            S::Chemistry => "chemistry",
+            S::Biology => "biology",
+            S::Geography => "geography",
+            S::Literature => "literature",
+        }
+    }
+
+    /// The subject with the given [`Subject::key`].
+    pub fn from_key(key: &str) -> Option<Self> {
+        ALL_SUBJECTS
+            .into_iter()
+            .find(|subject| subject.key() == key)
+    }
+
     pub fn color(&self) -> String {
         use Subject as S;
         String::from(match self {
@@ -75,6 +92,22 @@ impl Subject {
             S::Literature => "yellow",
         })
     }
+
+    /// Same as [`Subject::color`], as red, green and blue.
+    pub fn rgb(&self) -> (u8, u8, u8) {
+        use Subject as S;
+        match self {
+            S::Georgian => (255, 192, 203),
+            S::English => (0, 0, 255),
+            S::Math => (0, 128, 0),
+            S::History => (255, 165, 0),
+            S::Physics => (255, 0, 0),
+            S::Chemistry => (128, 0, 128),
+            S::Biology => (238, 130, 238),
+            S::Geography => (0, 255, 255),
+            S::Literature => (255, 255, 0),
+        }
+    }
 }
 
 impl PartialOrd for Subject {
@@ -86,63 +119,156 @@ impl PartialOrd for Subject {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-pub struct ScaledScore {
-    pub scaled: f32,
-}
-
-#[derive(Debug, Clone, Copy)]
-pub struct EqualizedScore {
-    pub equalized: f32,
-    pub scaled: f32,
-}
-
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum Score {
     Scaled(f32),
     Equalized(f32),
-    EqualizedAndScaled { scaled: f32, equalized: f32 },
+    EqualizedAndScaled {
+        scaled: f32,
+        equalized: f32,
+        /// Estimated error of `equalized`, 0 when it is known exactly.
+        uncertainty: f32,
+    },
 }
 
-impl ToString for Score {
-    fn to_string(&self) -> String {
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Score::Scaled(score) | Score::Equalized(score) => format!("{score:.2}"),
-            Score::EqualizedAndScaled { scaled, equalized } => format!("{equalized:.2}-{scaled}"),
+            Score::Scaled(score) | Score::Equalized(score) => write!(f, "{score:.2}"),
+            Score::EqualizedAndScaled {
+                scaled, equalized, ..
+            } => write!(f, "{equalized:.2}-{scaled}"),
         }
     }
 }
 
 impl Score {
-    pub fn to_latex(self) -> String {
+    /// Uncertainties that would round to `±0.0` aren't shown.
+    pub fn to_latex(self, show_uncertainty: bool) -> String {
         match self {
             Score::Scaled(score) => format!("{{\\color{{gray}}\\scriptsize{score:.1}}}"),
             Score::Equalized(score) => format!("{score:.1}"),
-            Score::EqualizedAndScaled { scaled, equalized } => format!(
-                "{:.1}{{\\color{{gray}}\\scriptsize({scaled:.1})}}",
-                (equalized * 10.0).round() / 10.0
+            Score::EqualizedAndScaled {
+                scaled,
+                equalized,
+                uncertainty,
+            } => format!(
+                "{:.1}{}{{\\color{{gray}}\\scriptsize({scaled:.1})}}",
+                (equalized * 10.0).round() / 10.0,
+                if show_uncertainty && uncertainty >= 0.05 {
+                    format!("{{\\scriptsize$\\pm${uncertainty:.1}}}")
+                } else {
+                    String::new()
+                }
+            ),
+        }
+    }
+
+    /// Same as [`Score::to_latex`], as HTML.
+    pub fn to_html(self, show_uncertainty: bool) -> String {
+        match self {
+            Score::Scaled(score) => format!("<small class=\"scaled\">{score:.1}</small>"),
+            Score::Equalized(score) => format!("{score:.1}"),
+            Score::EqualizedAndScaled {
+                scaled,
+                equalized,
+                uncertainty,
+            } => format!(
+                "{:.1}{}<small class=\"scaled\">({scaled:.1})</small>",
+                (equalized * 10.0).round() / 10.0,
+                if show_uncertainty && uncertainty >= 0.05 {
+                    format!("<small>±{uncertainty:.1}</small>")
+                } else {
+                    String::new()
+                }
+            ),
+        }
+    }
+
+    /// Same as [`Score::to_latex`], as Typst markup.
+    pub fn to_typst(self, show_uncertainty: bool) -> String {
+        match self {
+            Score::Scaled(score) => format!("#text(fill: gray, size: 0.8em)[{score:.1}]"),
+            Score::Equalized(score) => format!("{score:.1}"),
+            Score::EqualizedAndScaled {
+                scaled,
+                equalized,
+                uncertainty,
+            } => format!(
+                "{:.1}{}#text(fill: gray, size: 0.8em)[({scaled:.1})]",
+                (equalized * 10.0).round() / 10.0,
+                if show_uncertainty && uncertainty >= 0.05 {
+                    format!("#text(size: 0.8em)[±{uncertainty:.1}]")
+                } else {
+                    String::new()
+                }
             ),
         }
     }
+
+    pub fn scaled(self) -> Option<f32> {
+        match self {
+            Score::Scaled(scaled) | Score::EqualizedAndScaled { scaled, .. } => Some(scaled),
+            Score::Equalized(_) => None,
+        }
+    }
+
+    pub fn equalized(self) -> Option<f32> {
+        match self {
+            Score::Equalized(equalized) | Score::EqualizedAndScaled { equalized, .. } => {
+                Some(equalized)
+            }
+            Score::Scaled(_) => None,
+        }
+    }
+
+    /// The value tables are sorted by: the equalized score if there is one.
+    pub fn sort_key(self) -> f32 {
+        match self {
+            Score::Scaled(score) | Score::Equalized(score) => score,
+            Score::EqualizedAndScaled { equalized, .. } => equalized,
+        }
+    }
 }
 
-#[derive(Debug, Default, Hash, PartialEq, Eq)]
+#[derive(Debug, Default, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Faculty {
     pub id: String,
     pub name: String,
     pub subjects: [bool; 9],
 }
 
-#[derive(Debug, Default, Hash, PartialEq, Eq)]
+#[derive(Debug, Default, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub struct School {
     pub id: String,
     pub name: String,
     pub short_name: Option<String>,
 }
 
+/// The id of the school a faculty belongs to: the first three digits of the
+/// faculty's id.
+pub fn school_id(faculty_id: &str) -> &str {
+    faculty_id.get(0..3).unwrap_or_default()
+}
+
+/// The school with the id, named by its id if the publication doesn't list it.
+pub fn school_or_id(schools: &HashMap<String, School>, id: &str) -> School {
+    schools.get(id).cloned().unwrap_or_else(|| School {
+        id: String::from(id),
+        name: String::from(id),
+        short_name: None,
+    })
+}
+
+/// The school a faculty belongs to, named by its id if the publication
+/// doesn't list it.
+pub fn faculty_school(schools: &HashMap<String, School>, faculty_id: &str) -> School {
+    school_or_id(schools, school_id(faculty_id))
+}
+
 pub const SCHOOLS_SHORT_NAMES_CSV: &str = include_str!("data/schools.csv");
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum Grant {
     Zero,
     Fifty,
@@ -150,9 +276,24 @@ pub enum Grant {
     Hundred,
 }
 
-impl ToString for Grant {
-    fn to_string(&self) -> String {
-        String::from(match self {
+pub const ALL_GRANTS: [Grant; 4] = [Grant::Zero, Grant::Fifty, Grant::Seventy, Grant::Hundred];
+
+impl Grant {
+    /// Parses the percentage the grant covers, as it's published.
+    pub fn from(s: &str) -> Option<Self> {
+        match s {
+            "100" => Some(Grant::Hundred),
+            "70" => Some(Grant::Seventy),
+            "50" => Some(Grant::Fifty),
+            "0" => Some(Grant::Zero),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Grant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
             Grant::Zero => "0",
             Grant::Fifty => "50",
             Grant::Seventy => "70",
@@ -161,7 +302,7 @@ impl ToString for Grant {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StudentData {
     pub id: String,
     pub scores: [Option<Score>; 9],
@@ -171,9 +312,21 @@ pub struct StudentData {
     pub grant: Option<Grant>,
 }
 
+impl StudentData {
+    /// The competitive score as published, `None` if it isn't a number.
+    pub fn published_score(&self) -> Option<f32> {
+        self.overall_score.parse().ok()
+    }
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct SubjectStats {
     pub min: Option<Score>,
     pub max: Option<Score>,
     pub anchors: Vec<Score>,
+    /// The minimum wasn't published but made up from the maximum.
+    pub estimated_minimum: bool,
+    /// The descaling data file and the line the subject was first read from.
+    pub file: String,
+    pub line: usize,
 }
@@ -0,0 +1,474 @@
+use std::{error, ops::Range, sync::Once};
+
+use itertools::Itertools;
+use plotters::coord::Shift;
+use plotters::prelude::*;
+
+use crate::distribution::{SubjectDistribution, HISTOGRAM_BINS};
+use crate::error::{Error, Result};
+use crate::locale::Catalog;
+use crate::parsing::*;
+
+/// The font the plots are written with, it has Georgian letters.
+const FONT: &[u8] = include_bytes!("data/fonts/DejaVuSans.ttf");
+
+const SVG_PIXELS_PER_INCH: f32 = 100.0;
+/// PNG plots are printed, they need more pixels than SVG ones are drawn with.
+const PNG_PIXELS_PER_INCH: f32 = 200.0;
+
+const GRAY: RGBColor = RGBColor(128, 128, 128);
+
+/// File format of the plots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PlotFormat {
+    Svg,
+    Png,
+}
+
+impl PlotFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            PlotFormat::Svg => "svg",
+            PlotFormat::Png => "png",
+        }
+    }
+
+    fn pixels_per_inch(&self) -> f32 {
+        match self {
+            PlotFormat::Svg => SVG_PIXELS_PER_INCH,
+            PlotFormat::Png => PNG_PIXELS_PER_INCH,
+        }
+    }
+}
+
+type DrawResult = std::result::Result<(), Box<dyn error::Error>>;
+
+/// A plot that can be drawn on any of the backends.
+trait Plot {
+    /// Width and height, in inches.
+    fn size(&self) -> (f32, f32);
+
+    /// Lengths given in pixels at [`SVG_PIXELS_PER_INCH`] are multiplied by
+    /// `scale`.
+    fn draw<DB: DrawingBackend>(&self, root: &DrawingArea<DB, Shift>, scale: f32) -> DrawResult
+    where
+        DB::ErrorType: 'static;
+}
+
+fn register_font() {
+    static REGISTERED: Once = Once::new();
+
+    REGISTERED.call_once(|| {
+        plotters::style::register_font("sans-serif", FontStyle::Normal, FONT)
+            .unwrap_or_else(|_| panic!("the bundled font is broken"));
+    });
+}
+
+fn draw_on<DB: DrawingBackend>(
+    plot: &impl Plot,
+    root: DrawingArea<DB, Shift>,
+    scale: f32,
+) -> DrawResult
+where
+    DB::ErrorType: 'static,
+{
+    root.fill(&WHITE)?;
+    plot.draw(&root, scale)?;
+    root.present()?;
+
+    Ok(())
+}
+
+fn pixel_size(plot: &impl Plot, format: PlotFormat) -> (u32, u32) {
+    let (width, height) = plot.size();
+    let pixels_per_inch = format.pixels_per_inch();
+
+    (
+        (width * pixels_per_inch) as u32,
+        (height * pixels_per_inch) as u32,
+    )
+}
+
+fn save(plot: &impl Plot, file: &str, format: PlotFormat) -> Result<()> {
+    register_font();
+
+    let size = pixel_size(plot, format);
+    let scale = format.pixels_per_inch() / SVG_PIXELS_PER_INCH;
+
+    match format {
+        PlotFormat::Svg => draw_on(plot, SVGBackend::new(file, size).into_drawing_area(), scale),
+        PlotFormat::Png => draw_on(
+            plot,
+            BitMapBackend::new(file, size).into_drawing_area(),
+            scale,
+        ),
+    }
+    .map_err(|error| Error::Plot {
+        file: String::from(file),
+        message: error.to_string(),
+    })
+}
+
+fn subject_color(subject: Subject) -> RGBColor {
+    let (r, g, b) = subject.rgb();
+    RGBColor(r, g, b)
+}
+
+/// From the lowest to the highest of `values`, with some room around them so
+/// that no point is drawn on the axes.
+fn padded_range(values: impl Iterator<Item = f32>) -> Range<f32> {
+    let (min, max) = values.fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), v| {
+        (min.min(v), max.max(v))
+    });
+
+    let (min, max) = match (min.is_finite() && max.is_finite(), min < max) {
+        (true, true) => (min, max),
+        (true, false) => (min - 1.0, max + 1.0),
+        (false, _) => (0.0, 1.0),
+    };
+    let padding = (max - min) * 0.05;
+
+    min - padding..max + padding
+}
+
+/// Axis labels, scores and counts are shown without decimals.
+fn whole(value: &f32) -> String {
+    format!("{value:.0}")
+}
+
+/// Caption, color and points of a line.
+type Series<'a> = (&'a str, RGBColor, Vec<(f32, f32)>);
+
+/// A line per series, with a legend.
+fn draw_lines<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    x_max: f32,
+    series: &[Series],
+    scale: f32,
+) -> DrawResult
+where
+    DB::ErrorType: 'static,
+{
+    let y_range = padded_range(
+        series
+            .iter()
+            .flat_map(|(_, _, points)| points.iter().map(|(_, y)| *y)),
+    );
+
+    let mut chart = ChartBuilder::on(area)
+        .margin(5.0 * scale)
+        .x_label_area_size(20.0 * scale)
+        .y_label_area_size(35.0 * scale)
+        .build_cartesian_2d(0.0..x_max, y_range)?;
+
+    chart
+        .configure_mesh()
+        .disable_mesh()
+        .label_style(("sans-serif", 10.0 * scale))
+        .axis_style(BLACK.stroke_width(scale as u32))
+        .x_labels(6)
+        .x_label_formatter(&whole)
+        .y_label_formatter(&whole)
+        .draw()?;
+
+    for (caption, color, points) in series {
+        let style = color.stroke_width(scale.round() as u32);
+
+        chart
+            .draw_series(LineSeries::new(points.iter().copied(), style))?
+            .label(*caption)
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], style));
+
+        chart.draw_series(
+            points
+                .iter()
+                .map(|point| Circle::new(*point, 1.5 * scale, color.filled())),
+        )?;
+    }
+
+    chart
+        .configure_series_labels()
+        .position(SeriesLabelPosition::UpperRight)
+        .label_font(("sans-serif", 10.0 * scale))
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()?;
+
+    Ok(())
+}
+
+struct FacultyPlot<'a> {
+    students: &'a [StudentData],
+    faculty: &'a Faculty,
+    t: &'a Catalog,
+}
+
+impl Plot for FacultyPlot<'_> {
+    fn size(&self) -> (f32, f32) {
+        (6.0, 8.0)
+    }
+
+    fn draw<DB: DrawingBackend>(&self, root: &DrawingArea<DB, Shift>, scale: f32) -> DrawResult
+    where
+        DB::ErrorType: 'static,
+    {
+        let subjects = self
+            .faculty
+            .subjects
+            .iter()
+            .enumerate()
+            .filter_map(|(i, &s)| if s { Some(ALL_SUBJECTS[i]) } else { None })
+            .rev()
+            .collect_vec();
+
+        let panels = root.split_evenly((3, 2));
+        let x_max = self.students.len() as f32 + 1.0;
+
+        let mut students = self.students.iter().collect_vec();
+        for (subject, panel) in subjects.iter().zip(&panels) {
+            // sorted by the scores as published, drawn equalized
+            students.sort_by(|a, b| {
+                let key = |s: &StudentData| match s.scores[*subject as usize] {
+                    Some(Score::Scaled(scaled)) => scaled,
+                    Some(Score::EqualizedAndScaled { scaled, .. }) => scaled,
+                    Some(Score::Equalized(equalized)) => equalized,
+                    None => f32::NEG_INFINITY,
+                };
+
+                f32::total_cmp(&key(b), &key(a))
+            });
+
+            let series = subjects
+                .iter()
+                .map(|subject| {
+                    (
+                        self.t.subject(*subject),
+                        subject_color(*subject),
+                        students
+                            .iter()
+                            .enumerate()
+                            .filter_map(|(i, s)| {
+                                Some(((i + 1) as f32, s.scores[*subject as usize]?.sort_key()))
+                            })
+                            .collect_vec(),
+                    )
+                })
+                .collect_vec();
+
+            draw_lines(panel, x_max, &series, scale)?;
+        }
+
+        if let Some(panel) = panels.get(subjects.len()) {
+            let competitive = self
+                .students
+                .iter()
+                .enumerate()
+                .filter_map(|(i, s)| Some(((i + 1) as f32, s.published_score()?)))
+                .collect_vec();
+
+            draw_lines(
+                panel,
+                x_max,
+                &[(self.t.competitive, BLACK, competitive)],
+                scale,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A panel per competitive subject of the faculty, with the students sorted
+/// by it and every subject's equalized scores drawn, and a panel of the
+/// competitive scores.
+pub fn save_faculty_plot(
+    students: &[StudentData],
+    faculty: &Faculty,
+    t: &Catalog,
+    file: &str,
+    format: PlotFormat,
+) -> Result<()> {
+    save(
+        &FacultyPlot {
+            students,
+            faculty,
+            t,
+        },
+        file,
+        format,
+    )
+}
+
+/// Same as [`save_faculty_plot`], as the text of an SVG image.
+pub fn faculty_plot_svg(
+    students: &[StudentData],
+    faculty: &Faculty,
+    t: &Catalog,
+) -> Result<String> {
+    register_font();
+
+    let plot = FacultyPlot {
+        students,
+        faculty,
+        t,
+    };
+    let mut svg = String::new();
+
+    draw_on(
+        &plot,
+        SVGBackend::with_string(&mut svg, pixel_size(&plot, PlotFormat::Svg)).into_drawing_area(),
+        1.0,
+    )
+    .map_err(|error| Error::Plot {
+        file: format!("{} plot", faculty.id),
+        message: error.to_string(),
+    })?;
+
+    Ok(svg)
+}
+
+struct DistributionPlot<'a> {
+    distribution: &'a SubjectDistribution,
+    t: &'a Catalog,
+}
+
+impl DistributionPlot<'_> {
+    fn histograms(&self) -> Vec<(&str, RGBColor, Vec<crate::distribution::Bin>)> {
+        [
+            (self.t.scaled, GRAY, &self.distribution.scaled),
+            (
+                self.t.equalized,
+                subject_color(self.distribution.subject),
+                &self.distribution.equalized,
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(caption, color, scores)| {
+            Some((caption, color, scores.as_ref()?.histogram(HISTOGRAM_BINS)))
+        })
+        .collect_vec()
+    }
+}
+
+impl Plot for DistributionPlot<'_> {
+    fn size(&self) -> (f32, f32) {
+        (6.0, 2.5 * self.histograms().len().max(1) as f32)
+    }
+
+    fn draw<DB: DrawingBackend>(&self, root: &DrawingArea<DB, Shift>, scale: f32) -> DrawResult
+    where
+        DB::ErrorType: 'static,
+    {
+        let histograms = self.histograms();
+
+        for ((caption, color, bins), panel) in histograms
+            .iter()
+            .zip(root.split_evenly((histograms.len().max(1), 1)))
+        {
+            let (x_min, x_max) = match (bins.first(), bins.last()) {
+                (Some(first), Some(last)) if first.start < last.end => (first.start, last.end),
+                _ => (0.0, 1.0),
+            };
+            let highest = bins.iter().map(|bin| bin.count).max().unwrap_or_default();
+
+            let mut chart = ChartBuilder::on(&panel)
+                .margin(5.0 * scale)
+                .x_label_area_size(20.0 * scale)
+                .y_label_area_size(35.0 * scale)
+                .build_cartesian_2d(x_min..x_max, 0.0..(highest.max(1) as f32 * 1.05))?;
+
+            chart
+                .configure_mesh()
+                .disable_mesh()
+                .label_style(("sans-serif", 10.0 * scale))
+                .x_label_formatter(&whole)
+                .y_label_formatter(&whole)
+                .draw()?;
+
+            let style = color.filled();
+            chart
+                .draw_series(bins.iter().map(|bin| {
+                    Rectangle::new([(bin.start, 0.0), (bin.end, bin.count as f32)], style)
+                }))?
+                .label(*caption)
+                .legend(move |(x, y)| Rectangle::new([(x, y - 4), (x + 12, y + 4)], style));
+
+            chart
+                .configure_series_labels()
+                .position(SeriesLabelPosition::UpperRight)
+                .label_font(("sans-serif", 10.0 * scale))
+                .background_style(WHITE.mix(0.8))
+                .border_style(BLACK)
+                .draw()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Histograms of the scaled and the equalized scores of the subject, one above
+/// the other.
+pub fn save_distribution_plot(
+    distribution: &SubjectDistribution,
+    t: &Catalog,
+    file: &str,
+    format: PlotFormat,
+) -> Result<()> {
+    save(&DistributionPlot { distribution, t }, file, format)
+}
+
+struct CorrelationPlot<'a> {
+    pairs: &'a [(f32, f32)],
+    subjects: (Subject, Subject),
+    t: &'a Catalog,
+}
+
+impl Plot for CorrelationPlot<'_> {
+    fn size(&self) -> (f32, f32) {
+        (3.0, 3.0)
+    }
+
+    fn draw<DB: DrawingBackend>(&self, root: &DrawingArea<DB, Shift>, scale: f32) -> DrawResult
+    where
+        DB::ErrorType: 'static,
+    {
+        let x_range = padded_range(self.pairs.iter().map(|(x, _)| *x));
+        let y_range = padded_range(self.pairs.iter().map(|(_, y)| *y));
+
+        let mut chart = ChartBuilder::on(root)
+            .margin(5.0 * scale)
+            .x_label_area_size(35.0 * scale)
+            .y_label_area_size(45.0 * scale)
+            .build_cartesian_2d(x_range, y_range)?;
+
+        chart
+            .configure_mesh()
+            .disable_mesh()
+            .label_style(("sans-serif", 10.0 * scale))
+            .x_label_formatter(&whole)
+            .y_label_formatter(&whole)
+            .x_desc(self.t.subject(self.subjects.0))
+            .y_desc(self.t.subject(self.subjects.1))
+            .draw()?;
+
+        chart.draw_series(
+            self.pairs
+                .iter()
+                .map(|point| Circle::new(*point, scale, BLACK.filled())),
+        )?;
+
+        Ok(())
+    }
+}
+
+/// The equalized scores of one subject against the other's, a point per
+/// student.
+pub fn save_correlation_plot(
+    pairs: &[(f32, f32)],
+    subjects: (Subject, Subject),
+    t: &Catalog,
+    file: &str,
+    format: PlotFormat,
+) -> Result<()> {
+    save(&CorrelationPlot { pairs, subjects, t }, file, format)
+}
@@ -1,21 +1,70 @@
-use core::panic;
 use std::collections::HashMap;
 
 use itertools::Itertools;
 
+use crate::descaling::{typical_error_rate, DescalingCurve, DescalingModel};
+use crate::error::{Error, Result};
 use crate::parsing::*;
 
-fn map_range(v: &f32, from_min: &f32, from_max: &f32, to_min: &f32, to_max: &f32) -> f32 {
-    (v - from_min) / (from_max - from_min) * (to_max - to_min) + to_min
+/// The file the descaling data was read from, for errors about the subjects
+/// it lacks.
+pub(crate) fn descaling_data_file(data: &[Option<SubjectStats>; ALL_SUBJECTS.len()]) -> String {
+    data.iter()
+        .flatten()
+        .map(|stats| stats.file.clone())
+        .find(|file| !file.is_empty())
+        .unwrap_or_default()
 }
 
-pub fn descale_with_independent_data(
-    students: Vec<StudentData>,
-    independent_data: [Option<SubjectStats>; ALL_SUBJECTS.len()],
-) -> Vec<StudentData> {
-    let mut subject_stats = independent_data.map(Option::unwrap);
+/// The curve through the minimum, the anchors and the maximum of a subject.
+/// `None` if no student took the subject, so the minimum and maximum never got
+/// their scaled counterparts.
+fn descaling_curve(
+    stats: &SubjectStats,
+    subject: Subject,
+    model: DescalingModel,
+) -> Result<Option<DescalingCurve>> {
+    let mut knots = Vec::new();
+
+    for score in stats.min.iter().chain(&stats.anchors).chain(&stats.max) {
+        match *score {
+            Score::EqualizedAndScaled {
+                scaled, equalized, ..
+            } => knots.push((scaled, equalized)),
+            Score::Equalized(_) => return Ok(None),
+            Score::Scaled(_) => {
+                return Err(Error::BadDescalingData {
+                    file: stats.file.clone(),
+                    line: stats.line,
+                    subject,
+                })
+            }
+        }
+    }
 
-    for student in &students {
+    if knots.is_empty() {
+        return Err(Error::MissingMinMax {
+            file: stats.file.clone(),
+            line: stats.line,
+            subject,
+        });
+    }
+
+    DescalingCurve::new(&knots, model)
+        .map(|curve| Some(curve.with_estimated_minimum(stats.estimated_minimum)))
+}
+
+/// The descaling curve of every subject, indexed by subject. The minimum and
+/// maximum of each subject are paired with the lowest and highest scaled
+/// scores in the publication.
+pub fn descaling_curves(
+    students: &[StudentData],
+    independent_data: &[Option<SubjectStats>; ALL_SUBJECTS.len()],
+    model: DescalingModel,
+) -> Result<Vec<Option<DescalingCurve>>> {
+    let mut subject_stats = independent_data.clone();
+
+    for student in students {
         for (subject_index, score) in student.scores.iter().enumerate() {
             let Some(score) = score else {
                 continue;
@@ -23,140 +72,172 @@ pub fn descale_with_independent_data(
 
             let score = match score {
                 Score::Scaled(scaled) => scaled,
-                Score::EqualizedAndScaled {
-                    scaled,
-                    equalized: _,
-                } => scaled,
+                Score::EqualizedAndScaled { scaled, .. } => scaled,
                 Score::Equalized(_) => continue,
             };
 
-            subject_stats[subject_index].min = match subject_stats[subject_index].min.unwrap() {
-                Score::EqualizedAndScaled { scaled, equalized } => {
-                    Some(Score::EqualizedAndScaled {
-                        scaled: scaled.min(*score),
-                        equalized,
-                    })
-                }
+            let subject = ALL_SUBJECTS[subject_index];
+
+            let Some(stats) = &mut subject_stats[subject_index] else {
+                return Err(Error::MissingDescalingData {
+                    file: descaling_data_file(independent_data),
+                    subject,
+                });
+            };
+
+            let bad_descaling_data = |stats: &SubjectStats| Error::BadDescalingData {
+                file: stats.file.clone(),
+                line: stats.line,
+                subject,
+            };
+
+            let (Some(min), Some(max)) = (stats.min, stats.max) else {
+                return Err(Error::MissingMinMax {
+                    file: stats.file.clone(),
+                    line: stats.line,
+                    subject,
+                });
+            };
+
+            stats.min = match min {
+                Score::EqualizedAndScaled {
+                    scaled,
+                    equalized,
+                    uncertainty,
+                } => Some(Score::EqualizedAndScaled {
+                    scaled: scaled.min(*score),
+                    equalized,
+                    uncertainty,
+                }),
                 Score::Equalized(equalized) => Some(Score::EqualizedAndScaled {
                     scaled: *score,
                     equalized,
+                    uncertainty: 0.0,
                 }),
-                _ => panic!("bad data"),
+                _ => return Err(bad_descaling_data(stats)),
             };
 
-            subject_stats[subject_index].max = match subject_stats[subject_index].max.unwrap() {
-                Score::EqualizedAndScaled { scaled, equalized } => {
-                    Some(Score::EqualizedAndScaled {
-                        scaled: scaled.max(*score),
-                        equalized,
-                    })
-                }
+            stats.max = match max {
+                Score::EqualizedAndScaled {
+                    scaled,
+                    equalized,
+                    uncertainty,
+                } => Some(Score::EqualizedAndScaled {
+                    scaled: scaled.max(*score),
+                    equalized,
+                    uncertainty,
+                }),
                 Score::Equalized(equalized) => Some(Score::EqualizedAndScaled {
                     scaled: *score,
                     equalized,
+                    uncertainty: 0.0,
                 }),
-                _ => panic!("bad data"),
+                _ => return Err(bad_descaling_data(stats)),
             };
         }
     }
 
+    subject_stats
+        .iter()
+        .zip(ALL_SUBJECTS)
+        .map(|(stats, subject)| match stats {
+            Some(stats) => descaling_curve(stats, subject, model),
+            None => Ok(None),
+        })
+        .collect()
+}
+
+/// The typical error rate of every curve, indexed by subject, for
+/// [`DescalingCurve::uncertainty`]. `None` for subjects without anchors to
+/// measure it on, other subjects' rates say nothing about theirs.
+pub fn descaling_error_rates(
+    curves: &[Option<DescalingCurve>],
+    model: DescalingModel,
+) -> Vec<Option<f32>> {
+    curves
+        .iter()
+        .map(|curve| typical_error_rate(&curve.as_ref()?.leave_one_out_error_rates(model)))
+        .collect_vec()
+}
+
+pub fn descale_with_independent_data(
+    students: Vec<StudentData>,
+    independent_data: [Option<SubjectStats>; ALL_SUBJECTS.len()],
+    model: DescalingModel,
+) -> Result<Vec<StudentData>> {
+    let curves = descaling_curves(&students, &independent_data, model)?;
+    let error_rates = descaling_error_rates(&curves, model);
+
     students
         .iter()
         .map(|x| {
             let mut scores = x.scores;
 
             for subject_index in 0..ALL_SUBJECTS.len() {
-                if let Some(score) = &scores[subject_index] {
-                    let scaled_score = match score {
-                        Score::Scaled(scaled) => scaled,
-                        _ => continue,
-                    };
-
-                    let subject_data = &subject_stats[subject_index];
-
-                    let Some(Score::EqualizedAndScaled { scaled, equalized }) = &subject_data.min
-                    else {
-                        continue;
-                    };
-                    let mut min_scaled = scaled;
-                    let mut min_equalized = equalized;
-                    let Some(Score::EqualizedAndScaled { scaled, equalized }) = &subject_data.max
-                    else {
-                        continue;
-                    };
-                    let mut max_scaled = scaled;
-                    let mut max_equalized = equalized;
-
-                    for anchor in &subject_data.anchors {
-                        let Score::EqualizedAndScaled { scaled, equalized } = anchor else {
-                            panic!("bad anchor")
-                        };
-
-                        if scaled <= scaled_score {
-                            min_scaled = scaled;
-                            min_equalized = equalized;
-                        }
-                    }
-
-                    for anchor in &subject_data.anchors {
-                        let Score::EqualizedAndScaled { scaled, equalized } = anchor else {
-                            panic!("bad anchor")
-                        };
-
-                        if scaled_score < scaled {
-                            max_scaled = scaled;
-                            max_equalized = equalized;
-                        }
-                    }
-
-                    let equalized_score = map_range(
-                        scaled_score,
-                        min_scaled,
-                        max_scaled,
-                        min_equalized,
-                        max_equalized,
-                    );
-
-                    scores[subject_index] = Some(Score::EqualizedAndScaled {
-                        scaled: *scaled_score,
-                        equalized: equalized_score,
+                let Some(Score::Scaled(scaled)) = scores[subject_index] else {
+                    continue;
+                };
+
+                let Some(curve) = &curves[subject_index] else {
+                    return Err(Error::MissingDescalingData {
+                        file: descaling_data_file(&independent_data),
+                        subject: ALL_SUBJECTS[subject_index],
                     });
-                }
+                };
+
+                scores[subject_index] = Some(Score::EqualizedAndScaled {
+                    scaled,
+                    equalized: curve.equalize(scaled),
+                    uncertainty: curve.uncertainty(scaled, error_rates[subject_index]),
+                });
             }
 
-            StudentData {
+            Ok(StudentData {
                 scores,
                 id: x.id.clone(),
                 overall_score: x.overall_score.clone(),
                 placement: x.placement,
                 faculty_id: x.faculty_id.clone(),
                 grant: x.grant,
-            }
+            })
         })
-        .collect_vec()
+        .collect()
 }
 
-pub fn sort_students(students: Vec<StudentData>) -> Vec<StudentData> {
-    let mut students = students;
+pub fn sort_students(students: Vec<StudentData>) -> Result<Vec<StudentData>> {
+    if let Some(s) = students.iter().find(|s| s.published_score().is_none()) {
+        return Err(Error::BadOverallScore {
+            student_id: s.id.clone(),
+            value: s.overall_score.clone(),
+        });
+    }
 
-    students.sort_by(|a, b| {
-        b.overall_score
-            .parse::<f32>()
-            .unwrap()
-            .partial_cmp(&a.overall_score.parse::<f32>().unwrap())
-            .unwrap()
-    });
+    Ok(sort_students_by(students, StudentData::published_score))
+}
+
+/// Ranks the students by `score`, highest first. Students `score` has no
+/// score for come last, and ties are broken by the published score.
+pub fn sort_students_by(
+    students: Vec<StudentData>,
+    score: impl Fn(&StudentData) -> Option<f32>,
+) -> Vec<StudentData> {
+    let key = |s: &StudentData| {
+        (
+            score(s).unwrap_or(f32::NEG_INFINITY),
+            s.published_score().unwrap_or(f32::NEG_INFINITY),
+        )
+    };
 
     students
-        .iter()
+        .into_iter()
+        .map(|s| (key(&s), s))
+        .sorted_by(|((a, a_published), _), ((b, b_published), _)| {
+            f32::total_cmp(b, a).then(f32::total_cmp(b_published, a_published))
+        })
         .enumerate()
-        .map(|(i, s)| StudentData {
-            scores: s.scores,
-            id: s.id.clone(),
-            overall_score: s.overall_score.clone(),
+        .map(|(i, (_, s))| StudentData {
             placement: Some(i + 1),
-            faculty_id: s.faculty_id.clone(),
-            grant: s.grant,
+            ..s
         })
         .collect_vec()
 }
@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+
+use itertools::Itertools;
+
+use crate::descaling::DescalingModel;
+use crate::distribution::{subject_distributions, Distribution};
+use crate::error::{Error, Result};
+use crate::parsing::*;
+use crate::processing::{descaling_curves, descaling_data_file, descaling_error_rates};
+
+/// Where a score stands among the students admitted to a faculty.
+#[derive(Debug, Clone)]
+pub struct FacultyStanding {
+    pub faculty_id: String,
+    /// Admitted students with an equalized score of the subject.
+    pub admitted: usize,
+    /// The lowest and the median equalized score of the subject admitted.
+    pub cutoff: f32,
+    pub median: f32,
+    /// The percentile the score sits at among the admitted students.
+    pub percentile: f32,
+}
+
+/// Where a subject score stands among all students and in every faculty that
+/// counts the subject.
+#[derive(Debug, Clone)]
+pub struct ScoreStanding {
+    pub subject: Subject,
+    /// The queried score, with the equalized score estimated if it was scaled.
+    pub score: Score,
+    /// The percentiles of the scaled and the equalized score among all
+    /// students who took the subject.
+    pub scaled_percentile: Option<f32>,
+    pub equalized_percentile: Option<f32>,
+    /// Faculties counting the subject, the highest cutoff first. Empty if the
+    /// score has no equalized part.
+    pub faculties: Vec<FacultyStanding>,
+}
+
+impl ScoreStanding {
+    /// Faculties whose cutoff the equalized score reaches.
+    pub fn competitive(&self) -> impl Iterator<Item = &FacultyStanding> {
+        let equalized = self.score.equalized().unwrap_or(f32::NEG_INFINITY);
+
+        self.faculties
+            .iter()
+            .filter(move |faculty| faculty.cutoff <= equalized)
+    }
+
+    /// Faculties whose cutoff the equalized score misses by no more than its
+    /// uncertainty.
+    pub fn borderline(&self) -> impl Iterator<Item = &FacultyStanding> {
+        let (equalized, uncertainty) = match self.score {
+            Score::EqualizedAndScaled {
+                equalized,
+                uncertainty,
+                ..
+            } => (equalized, uncertainty),
+            _ => (f32::NEG_INFINITY, 0.0),
+        };
+
+        self.faculties.iter().filter(move |faculty| {
+            faculty.cutoff > equalized && faculty.cutoff <= equalized + uncertainty
+        })
+    }
+}
+
+/// Where `score` of `subject` stands among the students, nationally and in
+/// the admitted cohort of every faculty counting the subject.
+pub fn score_standing(
+    students: &[StudentData],
+    faculties: &HashMap<String, Faculty>,
+    subject: Subject,
+    score: Score,
+) -> ScoreStanding {
+    let national = &subject_distributions(students)[subject as usize];
+
+    let faculty_standings = match score.equalized() {
+        Some(equalized) => students
+            .iter()
+            .filter(|s| {
+                faculties
+                    .get(&s.faculty_id)
+                    .is_some_and(|faculty| faculty.subjects[subject as usize])
+            })
+            .filter_map(|s| {
+                let score = s.scores[subject as usize]?.equalized()?;
+                Some((s.faculty_id.as_str(), score))
+            })
+            .into_group_map()
+            .into_iter()
+            .filter_map(|(faculty_id, scores)| {
+                let cohort = Distribution::new(scores)?;
+
+                Some(FacultyStanding {
+                    faculty_id: String::from(faculty_id),
+                    admitted: cohort.len(),
+                    cutoff: cohort.min(),
+                    median: cohort.median(),
+                    percentile: cohort.percentile_rank(equalized),
+                })
+            })
+            .sorted_by(|a, b| {
+                f32::total_cmp(&b.cutoff, &a.cutoff).then(
+                    (a.faculty_id.parse().unwrap_or(u64::MAX), &a.faculty_id)
+                        .cmp(&(b.faculty_id.parse().unwrap_or(u64::MAX), &b.faculty_id)),
+                )
+            })
+            .collect_vec(),
+        None => Vec::new(),
+    };
+
+    ScoreStanding {
+        subject,
+        score,
+        scaled_percentile: score
+            .scaled()
+            .zip(national.scaled.as_ref())
+            .map(|(scaled, scores)| scores.percentile_rank(scaled)),
+        equalized_percentile: score
+            .equalized()
+            .zip(national.equalized.as_ref())
+            .map(|(equalized, scores)| scores.percentile_rank(equalized)),
+        faculties: faculty_standings,
+    }
+}
+
+/// The equalized score of a scaled one, with its uncertainty, on the curve
+/// through the subject's anchors the students were descaled with.
+pub fn estimate_equalized(
+    students: &[StudentData],
+    independent_data: &[Option<SubjectStats>; ALL_SUBJECTS.len()],
+    model: DescalingModel,
+    subject: Subject,
+    scaled: f32,
+) -> Result<Score> {
+    let curves = descaling_curves(students, independent_data, model)?;
+    let error_rates = descaling_error_rates(&curves, model);
+
+    let Some(curve) = &curves[subject as usize] else {
+        return Err(Error::MissingDescalingData {
+            file: descaling_data_file(independent_data),
+            subject,
+        });
+    };
+
+    Ok(Score::EqualizedAndScaled {
+        scaled,
+        equalized: curve.equalize(scaled),
+        uncertainty: curve.uncertainty(scaled, error_rates[subject as usize]),
+    })
+}
@@ -1,22 +1,34 @@
-use std::{collections::HashMap, fs::File, io::Read};
+use std::{collections::HashMap, fs, path::Path};
 
 use csv::{ReaderBuilder, StringRecord};
 use itertools::Itertools;
 
+use crate::cache::Cache;
+use crate::error::{Error, Result};
+use crate::extract::*;
 use crate::parsing::*;
+use crate::spreadsheet::*;
+
+pub(crate) fn parse_number(value: &str, file_name: &str, line: usize) -> Result<f32> {
+    value.parse().map_err(|_| Error::BadNumber {
+        file: String::from(file_name),
+        line,
+        value: String::from(value),
+    })
+}
 
-pub fn read_publication_tsv(
-    file_name: &str,
-) -> (
+/// Students, schools and faculties as listed in a publication.
+pub type Publication = (
     Vec<StudentData>,
     HashMap<String, School>,
     HashMap<String, Faculty>,
-) {
-    let mut reader = File::open(file_name).unwrap();
-    let mut file_contents = String::new();
-    reader
-        .read_to_string(&mut file_contents)
-        .expect("couldn't read the file");
+);
+
+pub fn read_publication_tsv(file_name: &str) -> Result<Publication> {
+    let file_contents = fs::read_to_string(file_name).map_err(|source| Error::Io {
+        file: String::from(file_name),
+        source,
+    })?;
     let file_contents = file_contents.split('\n');
 
     let mut students = Vec::new();
@@ -27,37 +39,41 @@ pub fn read_publication_tsv(
     let mut faculty_id: Option<String> = None;
     let mut current_subjects: Vec<Subject> = Vec::new();
 
-    for raw_line in file_contents {
+    for (line_index, raw_line) in file_contents.enumerate() {
+        let line_number = line_index + 1;
         let line = raw_line.trim().split('\t').collect_vec();
 
         if line.iter().all(|col| col.parse::<f32>().is_ok()) {
-            if faculty_id.is_some() {
+            if let Some(faculty_id) = &faculty_id {
+                if line.len() < current_subjects.len() + 3 {
+                    return Err(Error::MalformedStudentRow {
+                        file: String::from(file_name),
+                        line: line_number,
+                        expected: current_subjects.len() + 3,
+                        found: line.len(),
+                    });
+                }
+
                 let student_id = String::from(line[1]);
                 let mut scores = [None; ALL_SUBJECTS.len()];
 
                 for (index, subject) in current_subjects.iter().enumerate() {
-                    scores[*subject as usize] =
-                        Some(Score::Scaled(line[index + 2].parse().unwrap()));
+                    scores[*subject as usize] = Some(Score::Scaled(parse_number(
+                        line[index + 2],
+                        file_name,
+                        line_number,
+                    )?));
                 }
 
                 let overall_score = String::from(line[current_subjects.len() + 2]);
-                let grant = if line.len() < current_subjects.len() + 2 {
-                    Some(Grant::Zero)
-                } else {
-                    match line[line.len() - 1] {
-                        "100" => Some(Grant::Hundred),
-                        "70" => Some(Grant::Seventy),
-                        "50" => Some(Grant::Fifty),
-                        _ => None,
-                    }
-                };
+                let grant = Grant::from(line[line.len() - 1]);
 
                 students.push(StudentData {
                     id: student_id,
                     scores,
                     overall_score,
                     placement: None,
-                    faculty_id: faculty_id.clone().unwrap(),
+                    faculty_id: faculty_id.clone(),
                     grant,
                 });
             }
@@ -67,22 +83,27 @@ pub fn read_publication_tsv(
             let mut subjects = [false; 9];
             current_subjects.clear();
 
-            for elem in line.iter().take(line.len() - 2).skip(1)  {
+            for elem in line.iter().take(line.len().saturating_sub(2)).skip(1) {
                 if let Some(subject) = Subject::from(elem) {
                     current_subjects.push(subject);
                     subjects[subject as usize] = true;
                 }
             }
 
-            let faculty_id = faculty_id.clone().unwrap();
+            let (Some(faculty_id), Some(faculty_name)) = (&faculty_id, &faculty_name) else {
+                return Err(Error::MissingFacultyHeader {
+                    file: String::from(file_name),
+                    line: line_number,
+                });
+            };
 
             let faculty: Faculty = Faculty {
                 id: faculty_id.clone(),
-                name: faculty_name.clone().unwrap(),
+                name: faculty_name.clone(),
                 subjects,
             };
 
-            if !faculties.contains_key(&faculty_id) {
+            if !faculties.contains_key(faculty_id) {
                 faculties.insert(faculty_id.clone(), faculty);
             }
         } else if line[0].parse::<f32>().is_ok() {
@@ -102,143 +123,245 @@ pub fn read_publication_tsv(
         }
     }
 
-    (students, schools, faculties)
+    Ok((students, schools, faculties))
+}
+
+/// Adds a `subject, datatype, data1, data2` row of descaling data to `data`.
+pub(crate) fn add_descaling_row(
+    data: &mut [Option<SubjectStats>; ALL_SUBJECTS.len()],
+    row: &[&str],
+    file_name: &str,
+    line_number: usize,
+) -> Result<()> {
+    let column = |index: usize| row.get(index).copied().unwrap_or_default();
+
+    let Some(subject) = Subject::from(column(0)) else {
+        return Err(Error::UnknownSubject {
+            file: String::from(file_name),
+            line: line_number,
+            subject: String::from(column(0)),
+        });
+    };
+
+    let mut min = None;
+    let mut max = None;
+    let mut anchor = None;
+
+    match column(1) {
+        "maximum" => {
+            max = Some(Score::Equalized(parse_number(
+                column(2),
+                file_name,
+                line_number,
+            )?))
+        }
+        "minimum" => {
+            min = Some(Score::Equalized(parse_number(
+                column(2),
+                file_name,
+                line_number,
+            )?))
+        }
+        "anchor" => {
+            anchor = Some(Score::EqualizedAndScaled {
+                equalized: parse_number(column(2), file_name, line_number)?,
+                scaled: parse_number(column(3), file_name, line_number)?,
+                uncertainty: 0.0,
+            })
+        }
+        datatype => {
+            return Err(Error::BadDatatype {
+                file: String::from(file_name),
+                line: line_number,
+                datatype: String::from(datatype),
+            })
+        }
+    }
+
+    match data[subject as usize] {
+        None => {
+            data[subject as usize] = Some(SubjectStats {
+                min,
+                max,
+                anchors: if let Some(anchor) = anchor {
+                    vec![anchor]
+                } else {
+                    Vec::new()
+                },
+                estimated_minimum: false,
+                file: String::from(file_name),
+                line: line_number,
+            });
+        }
+        Some(ref mut stats) => {
+            if min.is_some() {
+                stats.min = min
+            }
+            if max.is_some() {
+                stats.max = max
+            }
+            if let Some(anchor) = anchor {
+                stats.anchors.push(anchor)
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Subjects with only a maximum get a fifth of it as their minimum.
+pub(crate) fn fill_missing_minimums(data: &mut [Option<SubjectStats>; ALL_SUBJECTS.len()]) {
+    for ref mut idd in data.iter_mut().flatten() {
+        if idd.min.is_none() {
+            if let Some(max) = idd.max {
+                match max {
+                    Score::Equalized(max) => {
+                        idd.min = Some(Score::Equalized(max * 0.2));
+                        idd.estimated_minimum = true;
+                    }
+                    Score::EqualizedAndScaled { equalized, .. } => {
+                        idd.min = Some(Score::Equalized(equalized * 0.2));
+                        idd.estimated_minimum = true;
+                    }
+                    _ => (),
+                }
+            }
+        }
+    }
 }
 
 pub fn read_independent_descaling_data(
     file_name: &str,
-) -> [Option<SubjectStats>; ALL_SUBJECTS.len()] {
+) -> Result<[Option<SubjectStats>; ALL_SUBJECTS.len()]> {
     // READ INDEPENDENT DATA
 
     let mut independent_descaling_data = [None, None, None, None, None, None, None, None, None];
 
+    let csv_error = |source| Error::Csv {
+        file: String::from(file_name),
+        source,
+    };
+
     let mut csv_reader = ReaderBuilder::new()
         .from_path(file_name)
-        .expect("couldn't read the independent data file");
+        .map_err(csv_error)?;
 
     while !csv_reader.is_done() {
         let mut csv_line = StringRecord::new();
-        csv_reader
-            .read_record(&mut csv_line)
-            .expect("error while reading independent data");
+        csv_reader.read_record(&mut csv_line).map_err(csv_error)?;
 
+        let line_number = csv_line.position().map_or(0, |p| p.line() as usize);
         let csv_line = csv_line.iter().collect_vec();
 
         if csv_line.is_empty() {
             continue;
         }
 
-        let Some(subject) = Subject::from(csv_line[0]) else {
-            panic!("cmon")
-        };
+        add_descaling_row(
+            &mut independent_descaling_data,
+            &csv_line,
+            file_name,
+            line_number,
+        )?;
+    }
 
-        let mut min = None;
-        let mut max = None;
-        let mut anchor = None;
+    fill_missing_minimums(&mut independent_descaling_data);
 
-        match csv_line[1] {
-            "maximum" => {
-                max = Some(Score::Equalized(
-                    csv_line[2].parse().expect("invalid number"),
-                ))
-            }
-            "minimum" => {
-                min = Some(Score::Equalized(
-                    csv_line[2].parse().expect("invalid number"),
-                ))
-            }
-            "anchor" => {
-                anchor = Some(Score::EqualizedAndScaled {
-                    equalized: csv_line[2].parse().expect("invalid number"),
-                    scaled: csv_line[3].parse().expect("invalid number"),
-                })
-            }
-            _ => todo!(),
-        }
+    Ok(independent_descaling_data)
+}
 
-        match independent_descaling_data[subject as usize] {
-            None => {
-                independent_descaling_data[subject as usize] = Some(SubjectStats {
-                    min,
-                    max,
-                    anchors: if let Some(anchor) = anchor {
-                        vec![anchor]
-                    } else {
-                        Vec::new()
-                    },
-                });
-            }
-            Some(ref mut stats) => {
-                if min.is_some() {
-                    stats.min = min
-                }
-                if max.is_some() {
-                    stats.max = max
-                }
-                if let Some(anchor) = anchor {
-                    stats.anchors.push(anchor)
-                }
-            }
-        }
-    }
+/// Short names of the schools shipped with the crate, keyed by school id.
+pub fn read_school_short_names() -> Result<HashMap<String, String>> {
+    let mut map = HashMap::new();
 
-    for ref mut idd in independent_descaling_data.iter_mut().flatten() {
-        if idd.min.is_none() {
-            if let Some(max) = idd.max {
-                match max {
-                    Score::Equalized(max) => {
-                        idd.min = Some(Score::Equalized(max * 0.2));
-                    }
-                    Score::EqualizedAndScaled {
-                        scaled: _,
-                        equalized,
-                    } => {
-                        idd.min = Some(Score::Equalized(equalized * 0.2));
-                    }
-                    _ => (),
-                }
-            }
+    let mut reader = ReaderBuilder::new().from_reader(SCHOOLS_SHORT_NAMES_CSV.as_bytes());
+
+    while !reader.is_done() {
+        let mut csv_line = StringRecord::new();
+        reader
+            .read_record(&mut csv_line)
+            .map_err(|source| Error::Csv {
+                file: String::from("schools.csv"),
+                source,
+            })?;
+
+        if csv_line.len() != 2 {
+            continue;
         }
+
+        let csv_line = csv_line.iter().collect_vec();
+
+        map.insert(String::from(csv_line[0]), String::from(csv_line[1]));
     }
 
-    independent_descaling_data
+    Ok(map)
 }
 
-use cpython::{ObjectProtocol, PyModule, PyResult, Python};
+pub fn parse_publication_pdf(input_file: &str, output_file: &str) -> Result<()> {
+    println!("extracting data from {input_file} to {output_file}...");
+
+    let tsv = extract_publication_tsv(input_file, PUBLICATION_AREA)?;
 
-pub fn parse_publication_pdf(input_file: &str, output_file: &str) {
-    let gil = Python::acquire_gil();
-    let py = gil.python();
+    fs::write(output_file, tsv).map_err(|source| Error::Io {
+        file: String::from(output_file),
+        source,
+    })?;
 
-    let ppp = module_from_str(
-        py,
-        "parse_publication_pdf",
-        include_str!("python/parse_publication_pdf.py"),
-    )
-    .expect("This tool depends on tabula-py. `python -m pip install tabula-py`");
+    println!("done.");
 
-    ppp.call(py, "parse_publication_pdf", (input_file, output_file), None)
-        .unwrap();
+    Ok(())
 }
 
-fn module_from_str(py: Python<'_>, name: &str, source: &str) -> PyResult<PyModule> {
-    let m = PyModule::new(py, name)?;
+/// Reads the publication from a PDF (through a cached or temporary TSV file),
+/// a TSV file left by `extract` or a workbook.
+pub fn read_publication(
+    input_file: &str,
+    column_mapping: &ColumnMapping,
+    cache: Option<&Cache>,
+) -> Result<Publication> {
+    if is_spreadsheet(input_file) {
+        println!("reading {input_file}...");
+        let publication = read_publication_sheet(input_file, column_mapping)?;
+        println!("done.");
+
+        return Ok(publication);
+    }
+
+    if input_file.ends_with(".tsv") {
+        return read_publication_tsv(input_file);
+    }
+
+    if let Some(cache) = cache {
+        let publication_tsv = cache.publication_tsv(input_file)?;
+
+        return read_publication_tsv(&publication_tsv.to_string_lossy());
+    }
 
-    let builtins = cpython::PyModule::import(py, "builtins").unwrap();
-    m.dict(py).set_item(py, "__builtins__", &builtins).unwrap();
+    let publication_tsv_file_name = Path::new(input_file)
+        .with_extension("tsv")
+        .to_string_lossy()
+        .into_owned();
 
-    // OR
-    m.add(py, "__builtins__", py.import("builtins")?)?;
-    let m_locals = m.get(py, "__dict__")?.extract(py)?;
+    parse_publication_pdf(input_file, &publication_tsv_file_name)?;
 
-    // To avoid multiple import, and to add entry to the cache in `sys.modules`.
-    let sys = cpython::PyModule::import(py, "sys").unwrap();
-    sys.get(py, "modules")
-        .unwrap()
-        .set_item(py, name, &m)
-        .unwrap();
+    let publication = read_publication_tsv(publication_tsv_file_name.as_str())?;
 
-    // Finally, run the moduke
-    py.run(source, Some(&m_locals), None)?;
-    Ok(m)
+    fs::remove_file(&publication_tsv_file_name).map_err(|source| Error::Io {
+        file: publication_tsv_file_name.clone(),
+        source,
+    })?;
+
+    Ok(publication)
+}
+
+/// Descaling data from a CSV file or a workbook.
+pub fn read_descaling_data(
+    file_name: &str,
+    column_mapping: &ColumnMapping,
+) -> Result<[Option<SubjectStats>; ALL_SUBJECTS.len()]> {
+    if is_spreadsheet(file_name) {
+        read_descaling_data_sheet(file_name, column_mapping)
+    } else {
+        read_independent_descaling_data(file_name)
+    }
 }
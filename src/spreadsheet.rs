@@ -0,0 +1,428 @@
+use std::{collections::HashMap, ops::RangeInclusive, path::Path};
+
+use calamine::{open_workbook_auto, DataType, Range, Reader};
+use csv::{ReaderBuilder, StringRecord};
+use itertools::Itertools;
+
+use crate::error::{Error, Result};
+use crate::parsing::*;
+use crate::read::{add_descaling_row, fill_missing_minimums, parse_number, Publication};
+
+/// Extensions of the workbooks [`read_publication_sheet`] and
+/// [`read_descaling_data_sheet`] can read.
+pub const SPREADSHEET_EXTENSIONS: [&str; 5] = ["xlsx", "xlsm", "xlsb", "xls", "ods"];
+
+/// Faculty ids are eight to eleven digits long, the first three being the id
+/// of the school. Spreadsheets that store them as numbers lose the leading
+/// zeros.
+const FACULTY_ID_DIGITS: RangeInclusive<usize> = 8..=11;
+const SCHOOL_ID_DIGITS: usize = 3;
+
+/// Whether the file is a workbook, judging by its extension.
+pub fn is_spreadsheet(file_name: &str) -> bool {
+    Path::new(file_name)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            SPREADSHEET_EXTENSIONS.contains(&extension.to_lowercase().as_str())
+        })
+}
+
+/// Headers of the columns the spreadsheet readers take their data from. The
+/// defaults read back what [`crate::export`] writes as CSV, and the descaling
+/// data laid out like its CSV file.
+#[derive(Debug, Clone)]
+pub struct ColumnMapping {
+    /// Sheet of the publication workbook to read, the first one if `None`.
+    pub publication_sheet: Option<String>,
+    pub student_id: String,
+    pub faculty_id: String,
+    pub faculty: String,
+    /// Taken from the faculty id if the column is missing.
+    pub school_id: String,
+    pub school: String,
+    /// Scaled score column of every subject, indexed by subject.
+    pub scaled_scores: [String; ALL_SUBJECTS.len()],
+    pub overall_score: String,
+    pub grant: String,
+    /// Sheet of the descaling data workbook to read, the first one if `None`.
+    pub descaling_sheet: Option<String>,
+    pub descaling_subject: String,
+    pub descaling_datatype: String,
+    pub descaling_data1: String,
+    pub descaling_data2: String,
+}
+
+impl Default for ColumnMapping {
+    fn default() -> Self {
+        Self {
+            publication_sheet: None,
+            student_id: String::from("id"),
+            faculty_id: String::from("faculty_id"),
+            faculty: String::from("faculty"),
+            school_id: String::from("school_id"),
+            school: String::from("school"),
+            scaled_scores: ALL_SUBJECTS.map(|subject| format!("{}_scaled", subject.key())),
+            overall_score: String::from("overall_score"),
+            grant: String::from("grant"),
+            descaling_sheet: None,
+            descaling_subject: String::from("საგანი"),
+            descaling_datatype: String::from("datatype"),
+            descaling_data1: String::from("data1"),
+            descaling_data2: String::from("data2"),
+        }
+    }
+}
+
+/// Reads a column mapping from a `field,header` CSV file without a header
+/// row. Fields are named after the [`ColumnMapping`] members, except for the
+/// scaled scores which go by [`Subject::key`]. Fields that aren't listed keep
+/// their default headers.
+pub fn read_column_mapping(file_name: &str) -> Result<ColumnMapping> {
+    let mut mapping = ColumnMapping::default();
+
+    let csv_error = |source| Error::Csv {
+        file: String::from(file_name),
+        source,
+    };
+
+    let mut reader = ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_path(file_name)
+        .map_err(csv_error)?;
+
+    while !reader.is_done() {
+        let mut csv_line = StringRecord::new();
+        reader.read_record(&mut csv_line).map_err(csv_error)?;
+
+        let line_number = csv_line.position().map_or(0, |p| p.line() as usize);
+        let csv_line = csv_line.iter().collect_vec();
+
+        let [field, header] = csv_line[..] else {
+            continue;
+        };
+        let header = String::from(header);
+
+        match field {
+            "publication_sheet" => mapping.publication_sheet = Some(header),
+            "descaling_sheet" => mapping.descaling_sheet = Some(header),
+            "id" => mapping.student_id = header,
+            "faculty_id" => mapping.faculty_id = header,
+            "faculty" => mapping.faculty = header,
+            "school_id" => mapping.school_id = header,
+            "school" => mapping.school = header,
+            "overall_score" => mapping.overall_score = header,
+            "grant" => mapping.grant = header,
+            "descaling_subject" => mapping.descaling_subject = header,
+            "descaling_datatype" => mapping.descaling_datatype = header,
+            "descaling_data1" => mapping.descaling_data1 = header,
+            "descaling_data2" => mapping.descaling_data2 = header,
+            field => match Subject::from_key(field) {
+                Some(subject) => mapping.scaled_scores[subject as usize] = header,
+                None => {
+                    return Err(Error::UnknownColumnField {
+                        file: String::from(file_name),
+                        line: line_number,
+                        field: String::from(field),
+                    })
+                }
+            },
+        }
+    }
+
+    Ok(mapping)
+}
+
+/// A cell as a string, empty if the cell is. Whole numbers are written
+/// without a fractional part.
+struct Cell {
+    text: String,
+    /// The workbook stores the cell as a number, any leading zeros are gone.
+    number: bool,
+}
+
+/// The cells of the sheet, row by row.
+fn read_sheet(file_name: &str, sheet: Option<&str>) -> Result<Vec<Vec<Cell>>> {
+    let spreadsheet_error = |source| Error::Spreadsheet {
+        file: String::from(file_name),
+        source,
+    };
+
+    let mut workbook = open_workbook_auto(file_name).map_err(spreadsheet_error)?;
+
+    let range: Option<std::result::Result<Range<DataType>, _>> = match sheet {
+        Some(sheet) => workbook.worksheet_range(sheet),
+        None => workbook.worksheet_range_at(0),
+    };
+
+    let Some(range) = range else {
+        return Err(Error::MissingSheet {
+            file: String::from(file_name),
+            sheet: String::from(sheet.unwrap_or("1")),
+        });
+    };
+
+    Ok(range
+        .map_err(spreadsheet_error)?
+        .rows()
+        .map(|row| {
+            row.iter()
+                .map(|cell| Cell {
+                    text: cell.to_string().trim().to_string(),
+                    number: matches!(cell, DataType::Int(_) | DataType::Float(_)),
+                })
+                .collect_vec()
+        })
+        .collect_vec())
+}
+
+/// Positions of the columns in the header row, by header.
+struct Columns<'a> {
+    file_name: &'a str,
+    positions: HashMap<&'a str, usize>,
+}
+
+impl<'a> Columns<'a> {
+    fn new(file_name: &'a str, header: &'a [Cell]) -> Self {
+        Self {
+            file_name,
+            positions: header
+                .iter()
+                .enumerate()
+                .map(|(i, name)| (name.text.as_str(), i))
+                .collect(),
+        }
+    }
+
+    fn optional(&self, column: &str) -> Option<usize> {
+        self.positions.get(column).copied()
+    }
+
+    fn required(&self, column: &str) -> Result<usize> {
+        self.optional(column).ok_or_else(|| Error::MissingColumn {
+            file: String::from(self.file_name),
+            column: String::from(column),
+        })
+    }
+}
+
+fn cell(row: &[Cell], column: Option<usize>) -> &str {
+    column
+        .and_then(|column| row.get(column))
+        .map_or("", |cell| cell.text.as_str())
+}
+
+fn is_empty(row: &[Cell]) -> bool {
+    row.iter().all(|cell| cell.text.is_empty())
+}
+
+/// The faculty id of a cell. Ids stored as numbers get back the leading
+/// zeros they lost only when the id of the school they have to start with
+/// leaves a single way to do it, `None` otherwise.
+fn restore_faculty_id(cell: &Cell, school_id: Option<&str>) -> Option<String> {
+    if !cell.number || cell.text.len() >= *FACULTY_ID_DIGITS.end() {
+        return Some(cell.text.clone());
+    }
+
+    let school_id = format!("{:0>SCHOOL_ID_DIGITS$}", school_id?);
+
+    FACULTY_ID_DIGITS
+        .filter(|digits| *digits >= cell.text.len())
+        .map(|digits| format!("{:0>digits$}", cell.text))
+        .filter(|id| id.starts_with(&school_id))
+        .exactly_one()
+        .ok()
+}
+
+/// Reads a publication laid out one student per row, with the columns given
+/// by the mapping. The subjects of a faculty are the ones any of its students
+/// has a score in.
+pub fn read_publication_sheet(file_name: &str, mapping: &ColumnMapping) -> Result<Publication> {
+    let rows = read_sheet(file_name, mapping.publication_sheet.as_deref())?;
+
+    let mut students = Vec::new();
+    let mut schools = HashMap::new();
+    let mut faculties = HashMap::new();
+
+    let Some((header, rows)) = rows.split_first() else {
+        return Ok((students, schools, faculties));
+    };
+
+    let columns = Columns::new(file_name, header);
+
+    let student_id = columns.required(&mapping.student_id)?;
+    let faculty_id = columns.required(&mapping.faculty_id)?;
+    let overall_score = columns.required(&mapping.overall_score)?;
+    let faculty_name = columns.optional(&mapping.faculty);
+    let school_id = columns.optional(&mapping.school_id);
+    let school_name = columns.optional(&mapping.school);
+    let grant = columns.optional(&mapping.grant);
+    let scaled_scores = mapping
+        .scaled_scores
+        .iter()
+        .map(|column| columns.optional(column))
+        .collect_vec();
+
+    for (row_index, row) in rows.iter().enumerate() {
+        // the header is the first line
+        let line_number = row_index + 2;
+
+        if is_empty(row) {
+            continue;
+        }
+
+        let faculty_id = match row.get(faculty_id) {
+            Some(id) => {
+                let school_id = Some(cell(row, school_id)).filter(|id| !id.is_empty());
+
+                restore_faculty_id(id, school_id).ok_or_else(|| Error::NumericFacultyId {
+                    file: String::from(file_name),
+                    line: line_number,
+                    id: id.text.clone(),
+                })?
+            }
+            None => String::new(),
+        };
+        let school_id = match cell(row, school_id) {
+            "" => faculty_id.chars().take(SCHOOL_ID_DIGITS).collect(),
+            school_id => format!("{school_id:0>SCHOOL_ID_DIGITS$}"),
+        };
+
+        let mut scores = [None; ALL_SUBJECTS.len()];
+        for (subject_index, column) in scaled_scores.iter().enumerate() {
+            let score = cell(row, *column);
+
+            if !score.is_empty() {
+                scores[subject_index] =
+                    Some(Score::Scaled(parse_number(score, file_name, line_number)?));
+            }
+        }
+
+        let faculty = faculties
+            .entry(faculty_id.clone())
+            .or_insert_with(|| Faculty {
+                id: faculty_id.clone(),
+                name: String::from(cell(row, faculty_name)),
+                subjects: [false; ALL_SUBJECTS.len()],
+            });
+        for (subject_index, score) in scores.iter().enumerate() {
+            faculty.subjects[subject_index] |= score.is_some();
+        }
+
+        schools.entry(school_id.clone()).or_insert_with(|| School {
+            id: school_id.clone(),
+            name: String::from(cell(row, school_name)),
+            short_name: None,
+        });
+
+        students.push(StudentData {
+            id: String::from(cell(row, Some(student_id))),
+            scores,
+            overall_score: String::from(cell(row, Some(overall_score))),
+            placement: None,
+            faculty_id,
+            grant: Grant::from(cell(row, grant)),
+        });
+    }
+
+    Ok((students, schools, faculties))
+}
+
+/// Reads descaling data laid out like its CSV file, with the columns given by
+/// the mapping.
+pub fn read_descaling_data_sheet(
+    file_name: &str,
+    mapping: &ColumnMapping,
+) -> Result<[Option<SubjectStats>; ALL_SUBJECTS.len()]> {
+    let rows = read_sheet(file_name, mapping.descaling_sheet.as_deref())?;
+
+    let mut independent_descaling_data = [None, None, None, None, None, None, None, None, None];
+
+    let Some((header, rows)) = rows.split_first() else {
+        return Ok(independent_descaling_data);
+    };
+
+    let columns = Columns::new(file_name, header);
+
+    let subject = columns.required(&mapping.descaling_subject)?;
+    let datatype = columns.required(&mapping.descaling_datatype)?;
+    let data1 = columns.required(&mapping.descaling_data1)?;
+    let data2 = columns.optional(&mapping.descaling_data2);
+
+    for (row_index, row) in rows.iter().enumerate() {
+        if is_empty(row) {
+            continue;
+        }
+
+        add_descaling_row(
+            &mut independent_descaling_data,
+            &[
+                cell(row, Some(subject)),
+                cell(row, Some(datatype)),
+                cell(row, Some(data1)),
+                cell(row, data2),
+            ],
+            file_name,
+            row_index + 2,
+        )?;
+    }
+
+    fill_missing_minimums(&mut independent_descaling_data);
+
+    Ok(independent_descaling_data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn number(text: &str) -> Cell {
+        Cell {
+            text: String::from(text),
+            number: true,
+        }
+    }
+
+    #[test]
+    fn faculty_ids_stored_as_text_are_kept() {
+        let cell = Cell {
+            text: String::from("0010147104"),
+            number: false,
+        };
+
+        assert_eq!(
+            restore_faculty_id(&cell, None).as_deref(),
+            Some("0010147104")
+        );
+    }
+
+    #[test]
+    fn numeric_faculty_ids_get_their_zeros_back_from_the_school_id() {
+        let cell = number("10147104");
+
+        assert_eq!(
+            restore_faculty_id(&cell, Some("001")).as_deref(),
+            Some("0010147104")
+        );
+        // school ids stored as numbers lost their zeros too
+        assert_eq!(
+            restore_faculty_id(&cell, Some("1")).as_deref(),
+            Some("0010147104")
+        );
+        assert_eq!(
+            restore_faculty_id(&cell, Some("101")).as_deref(),
+            Some("10147104")
+        );
+        assert_eq!(restore_faculty_id(&cell, Some("002")), None);
+    }
+
+    #[test]
+    fn numeric_faculty_ids_are_not_guessed() {
+        assert_eq!(restore_faculty_id(&number("10147104"), None), None);
+        assert_eq!(
+            restore_faculty_id(&number("12345678901"), None).as_deref(),
+            Some("12345678901")
+        );
+    }
+}
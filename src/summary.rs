@@ -0,0 +1,414 @@
+use std::collections::HashMap;
+
+use csv::WriterBuilder;
+use itertools::Itertools;
+
+use crate::comparison::{median, ScoreSummary};
+use crate::distribution::subject_distributions;
+use crate::error::{Error, Result};
+use crate::parsing::*;
+
+/// What it took to get into a faculty.
+#[derive(Debug, Clone)]
+pub struct FacultySummary {
+    pub faculty_id: String,
+    /// The faculty's name, its id if the publication doesn't list it.
+    pub name: String,
+    pub admitted: usize,
+    /// The lowest and the highest published competitive scores.
+    pub lowest_score: Option<f32>,
+    pub highest_score: Option<f32>,
+    /// Number of students at every grant level, indexed by grant.
+    pub grants: [usize; ALL_GRANTS.len()],
+    /// Lowest and median equalized scores of the faculty's subjects, indexed
+    /// by subject.
+    pub subjects: [Option<ScoreSummary>; ALL_SUBJECTS.len()],
+}
+
+impl FacultySummary {
+    pub fn new(faculty: &Faculty, students: &[StudentData]) -> Self {
+        let published_scores = students
+            .iter()
+            .filter_map(StudentData::published_score)
+            .collect_vec();
+
+        let mut grants = [0; ALL_GRANTS.len()];
+        for student in students {
+            grants[student.grant.unwrap_or(Grant::Zero) as usize] += 1;
+        }
+
+        Self {
+            faculty_id: faculty.id.clone(),
+            name: faculty.name.clone(),
+            admitted: students.len(),
+            lowest_score: published_scores.iter().copied().min_by(f32::total_cmp),
+            highest_score: published_scores.iter().copied().max_by(f32::total_cmp),
+            grants,
+            subjects: ALL_SUBJECTS.map(|subject| {
+                if !faculty.subjects[subject as usize] {
+                    return None;
+                }
+
+                let scores = students
+                    .iter()
+                    .filter_map(|s| {
+                        let score = s.scores[subject as usize]?.equalized()?;
+                        Some((score, s.grant.unwrap_or(Grant::Zero)))
+                    })
+                    .collect_vec();
+
+                ScoreSummary::new(&scores)
+            }),
+        }
+    }
+}
+
+/// A faculty the publication doesn't list, named by its id and counting the
+/// subjects its students took.
+fn unlisted_faculty(faculty_id: &str, students: &[StudentData]) -> Faculty {
+    let mut subjects = [false; ALL_SUBJECTS.len()];
+    for student in students {
+        for subject in ALL_SUBJECTS {
+            subjects[subject as usize] |= student.scores[subject as usize].is_some();
+        }
+    }
+
+    Faculty {
+        id: String::from(faculty_id),
+        name: String::from(faculty_id),
+        subjects,
+    }
+}
+
+/// Summaries of the faculties, in the order of the buckets. Faculties missing
+/// from `faculties` are named by their ids and count the subjects their
+/// students took.
+pub fn summarize_faculties(
+    faculty_buckets: &[(&String, &[StudentData])],
+    faculties: &HashMap<String, Faculty>,
+) -> Vec<FacultySummary> {
+    faculty_buckets
+        .iter()
+        .map(|(faculty_id, students)| match faculties.get(*faculty_id) {
+            Some(faculty) => FacultySummary::new(faculty, students),
+            None => FacultySummary::new(&unlisted_faculty(faculty_id, students), students),
+        })
+        .collect_vec()
+}
+
+/// Writes the summaries as CSV, a faculty per row.
+pub fn write_faculty_summaries(
+    file_name: &str,
+    summaries: &[FacultySummary],
+    schools: &HashMap<String, School>,
+) -> Result<()> {
+    let csv_error = |source| Error::Csv {
+        file: String::from(file_name),
+        source,
+    };
+
+    let mut writer = WriterBuilder::new()
+        .from_path(file_name)
+        .map_err(csv_error)?;
+
+    let header = [
+        "faculty_id",
+        "faculty",
+        "school_id",
+        "school",
+        "admitted",
+        "lowest_score",
+        "highest_score",
+    ]
+    .map(String::from)
+    .into_iter()
+    .chain(
+        ALL_GRANTS
+            .iter()
+            .rev()
+            .map(|grant| format!("grant_{grant}")),
+    )
+    .chain(ALL_SUBJECTS.iter().flat_map(|subject| {
+        let key = subject.key();
+        [format!("{key}_lowest"), format!("{key}_median")]
+    }))
+    .collect_vec();
+
+    writer.write_record(&header).map_err(csv_error)?;
+
+    let optional = |value: Option<f32>| value.map(|v| format!("{v:.1}")).unwrap_or_default();
+
+    for summary in summaries {
+        let school = faculty_school(schools, &summary.faculty_id);
+
+        let row = [
+            summary.faculty_id.clone(),
+            summary.name.clone(),
+            school.id,
+            school.name,
+            summary.admitted.to_string(),
+            optional(summary.lowest_score),
+            optional(summary.highest_score),
+        ]
+        .into_iter()
+        .chain(
+            ALL_GRANTS
+                .iter()
+                .rev()
+                .map(|grant| summary.grants[*grant as usize].to_string()),
+        )
+        .chain(summary.subjects.iter().flat_map(|subject| {
+            [
+                optional(subject.map(|s| s.cutoff)),
+                optional(subject.map(|s| s.median)),
+            ]
+        }))
+        .collect_vec();
+
+        writer.write_record(&row).map_err(csv_error)?;
+    }
+
+    writer.flush().map_err(|source| Error::Io {
+        file: String::from(file_name),
+        source,
+    })
+}
+
+/// How a school's students did, over all of its faculties.
+#[derive(Debug, Clone)]
+pub struct SchoolSummary {
+    pub school_id: String,
+    pub faculties: usize,
+    pub enrolled: usize,
+    /// Share of the enrolled students with a grant of any level.
+    pub grant_share: f32,
+    /// Median over the enrolled students of the mean national percentile of
+    /// their equalized subject scores, from 0 to 100. Unlike competitive
+    /// scores it doesn't depend on the faculty weights, so schools can be
+    /// compared by it.
+    pub selectivity: Option<f32>,
+    /// Position by selectivity, from 1.
+    pub rank: Option<usize>,
+    /// Mean and median equalized scores of every subject, indexed by subject.
+    pub subjects: [Option<ScoreSummary>; ALL_SUBJECTS.len()],
+}
+
+/// Summaries of the schools, faculties being grouped by [`school_id`], ranked
+/// by selectivity.
+pub fn summarize_schools(students: &[StudentData]) -> Vec<SchoolSummary> {
+    let national = subject_distributions(students);
+
+    let mean_percentile = |student: &StudentData| {
+        let percentiles = ALL_SUBJECTS
+            .iter()
+            .filter_map(|subject| {
+                let score = student.scores[*subject as usize]?.equalized()?;
+                Some(
+                    national[*subject as usize]
+                        .equalized
+                        .as_ref()?
+                        .percentile_rank(score),
+                )
+            })
+            .collect_vec();
+
+        (!percentiles.is_empty())
+            .then(|| percentiles.iter().sum::<f32>() / percentiles.len() as f32)
+    };
+
+    let mut summaries = students
+        .iter()
+        .into_group_map_by(|s| school_id(&s.faculty_id))
+        .into_iter()
+        .map(|(school_id, students)| {
+            let percentiles = students
+                .iter()
+                .filter_map(|s| mean_percentile(s))
+                .sorted_by(f32::total_cmp)
+                .collect_vec();
+
+            SchoolSummary {
+                school_id: String::from(school_id),
+                faculties: students.iter().map(|s| &s.faculty_id).unique().count(),
+                enrolled: students.len(),
+                grant_share: students
+                    .iter()
+                    .filter(|s| s.grant.is_some_and(|grant| grant != Grant::Zero))
+                    .count() as f32
+                    / students.len() as f32,
+                selectivity: median(&percentiles),
+                rank: None,
+                subjects: ALL_SUBJECTS.map(|subject| {
+                    let scores = students
+                        .iter()
+                        .filter_map(|s| {
+                            let score = s.scores[subject as usize]?.equalized()?;
+                            Some((score, s.grant.unwrap_or(Grant::Zero)))
+                        })
+                        .collect_vec();
+
+                    ScoreSummary::new(&scores)
+                }),
+            }
+        })
+        .sorted_by(|a, b| {
+            f32::total_cmp(
+                &b.selectivity.unwrap_or(f32::NEG_INFINITY),
+                &a.selectivity.unwrap_or(f32::NEG_INFINITY),
+            )
+            .then(a.school_id.cmp(&b.school_id))
+        })
+        .collect_vec();
+
+    for (rank, summary) in summaries.iter_mut().enumerate() {
+        summary.rank = summary.selectivity.map(|_| rank + 1);
+    }
+
+    summaries
+}
+
+/// Writes the summaries as CSV, a school per row.
+pub fn write_school_summaries(
+    file_name: &str,
+    summaries: &[SchoolSummary],
+    schools: &HashMap<String, School>,
+) -> Result<()> {
+    let csv_error = |source| Error::Csv {
+        file: String::from(file_name),
+        source,
+    };
+
+    let mut writer = WriterBuilder::new()
+        .from_path(file_name)
+        .map_err(csv_error)?;
+
+    let header = [
+        "rank",
+        "school_id",
+        "school",
+        "faculties",
+        "enrolled",
+        "grant_share",
+        "selectivity",
+    ]
+    .map(String::from)
+    .into_iter()
+    .chain(ALL_SUBJECTS.iter().flat_map(|subject| {
+        let key = subject.key();
+        [format!("{key}_mean"), format!("{key}_median")]
+    }))
+    .collect_vec();
+
+    writer.write_record(&header).map_err(csv_error)?;
+
+    let optional = |value: Option<f32>| value.map(|v| format!("{v:.1}")).unwrap_or_default();
+
+    for summary in summaries {
+        let row = [
+            summary.rank.map(|r| r.to_string()).unwrap_or_default(),
+            summary.school_id.clone(),
+            school_or_id(schools, &summary.school_id).name,
+            summary.faculties.to_string(),
+            summary.enrolled.to_string(),
+            format!("{:.3}", summary.grant_share),
+            optional(summary.selectivity),
+        ]
+        .into_iter()
+        .chain(summary.subjects.iter().flat_map(|subject| {
+            [
+                optional(subject.map(|s| s.mean)),
+                optional(subject.map(|s| s.median)),
+            ]
+        }))
+        .collect_vec();
+
+        writer.write_record(&row).map_err(csv_error)?;
+    }
+
+    writer.flush().map_err(|source| Error::Io {
+        file: String::from(file_name),
+        source,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn student(faculty_id: &str, math: f32, grant: Grant) -> StudentData {
+        let mut scores = [None; ALL_SUBJECTS.len()];
+        scores[Subject::Math as usize] = Some(Score::Equalized(math));
+
+        StudentData {
+            id: String::new(),
+            scores,
+            overall_score: format!("{math}"),
+            placement: None,
+            faculty_id: String::from(faculty_id),
+            grant: Some(grant),
+        }
+    }
+
+    #[test]
+    fn unlisted_faculties_are_summarized() {
+        let students = [
+            student("001001", 30.0, Grant::Hundred),
+            student("001001", 20.0, Grant::Zero),
+        ];
+        let faculty_id = String::from("001001");
+
+        let summaries = summarize_faculties(&[(&faculty_id, &students)], &HashMap::new());
+
+        let summary = summaries.iter().exactly_one().unwrap();
+        assert_eq!(summary.name, "001001");
+        assert_eq!(summary.admitted, 2);
+        assert_eq!(summary.lowest_score, Some(20.0));
+        assert_eq!(summary.grants[Grant::Hundred as usize], 1);
+
+        // the subjects its students took are counted
+        let math = summary.subjects[Subject::Math as usize].unwrap();
+        assert_eq!(math.cutoff, 20.0);
+        assert_eq!(math.grant_threshold, Some(30.0));
+        assert!(summary.subjects[Subject::English as usize].is_none());
+    }
+
+    #[test]
+    fn schools_are_ranked_by_selectivity() {
+        // math percentiles of 10, 30, 50, 70 and 90
+        let mut students = vec![
+            student("001001", 10.0, Grant::Zero),
+            student("001002", 50.0, Grant::Hundred),
+            student("002001", 30.0, Grant::Zero),
+            student("002001", 40.0, Grant::Fifty),
+            student("003001", 20.0, Grant::Zero),
+        ];
+        // no equalized scores, so no selectivity
+        let mut unranked = student("004001", 0.0, Grant::Zero);
+        unranked.scores = [None; ALL_SUBJECTS.len()];
+        students.push(unranked);
+
+        let summaries = summarize_schools(&students);
+
+        let ranking = summaries
+            .iter()
+            .map(|s| (s.school_id.as_str(), s.selectivity.map(f32::round), s.rank))
+            .collect_vec();
+        assert_eq!(
+            ranking,
+            [
+                ("002", Some(60.0), Some(1)),
+                ("001", Some(50.0), Some(2)),
+                ("003", Some(30.0), Some(3)),
+                ("004", None, None),
+            ]
+        );
+
+        assert_eq!(summaries[0].faculties, 1);
+        assert_eq!(summaries[1].faculties, 2);
+        assert_eq!(summaries[1].grant_share, 0.5);
+        assert_eq!(
+            summaries[0].subjects[Subject::Math as usize].unwrap().mean,
+            35.0
+        );
+    }
+}
@@ -0,0 +1,134 @@
+use std::{fs, path::Path};
+
+use minijinja::{syntax::SyntaxConfig, Environment};
+use serde::Serialize;
+
+use crate::error::{Error, Result};
+
+/// The templates the book is written with, by name, as shipped with the crate.
+pub const DEFAULT_TEMPLATES: [(&str, &str); 16] = [
+    ("main.tex", include_str!("templates/main.tex")),
+    ("top-list.tex", include_str!("templates/top-list.tex")),
+    ("summary.tex", include_str!("templates/summary.tex")),
+    ("schools.tex", include_str!("templates/schools.tex")),
+    (
+        "distributions.tex",
+        include_str!("templates/distributions.tex"),
+    ),
+    (
+        "correlations.tex",
+        include_str!("templates/correlations.tex"),
+    ),
+    ("comparison.tex", include_str!("templates/comparison.tex")),
+    ("faculty.tex", include_str!("templates/faculty.tex")),
+    ("main.typ", include_str!("templates/main.typ")),
+    ("top-list.typ", include_str!("templates/top-list.typ")),
+    ("summary.typ", include_str!("templates/summary.typ")),
+    ("schools.typ", include_str!("templates/schools.typ")),
+    (
+        "distributions.typ",
+        include_str!("templates/distributions.typ"),
+    ),
+    (
+        "correlations.typ",
+        include_str!("templates/correlations.typ"),
+    ),
+    ("comparison.typ", include_str!("templates/comparison.typ")),
+    ("faculty.typ", include_str!("templates/faculty.typ")),
+];
+
+/// A Typst string literal of `s`, so that no character of it is read as
+/// markup. Templates have it as the `typst_string` filter.
+pub fn typst_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn template_error(template: &str) -> impl Fn(minijinja::Error) -> Error + '_ {
+    move |source| Error::Template {
+        template: String::from(template),
+        source,
+    }
+}
+
+/// The [`DEFAULT_TEMPLATES`], some of them possibly replaced by files of the
+/// same name.
+///
+/// Blocks are written `<% %>`, variables `<< >>` and comments `<# #>`, so
+/// that they aren't mistaken for LaTeX braces. Lines with only a block on
+/// them leave nothing behind.
+pub struct Templates {
+    environment: Environment<'static>,
+}
+
+impl Templates {
+    /// The default templates, overridden by the files in `directory` named
+    /// like them. Other files in `directory` are ignored.
+    pub fn new(directory: Option<&str>) -> Result<Self> {
+        let mut environment = Environment::new();
+
+        environment.set_syntax(
+            SyntaxConfig::builder()
+                .block_delimiters("<%", "%>")
+                .variable_delimiters("<<", ">>")
+                .comment_delimiters("<#", "#>")
+                .build()
+                .map_err(template_error("syntax"))?,
+        );
+        environment.set_trim_blocks(true);
+        environment.set_lstrip_blocks(true);
+        environment.set_keep_trailing_newline(true);
+        environment.add_filter("typst_string", |s: &str| typst_string(s));
+
+        for (name, default) in DEFAULT_TEMPLATES {
+            let file = directory.map(|directory| Path::new(directory).join(name));
+
+            match file.filter(|file| file.exists()) {
+                Some(file) => {
+                    let file = file.to_string_lossy().to_string();
+                    let source = fs::read_to_string(&file).map_err(|source| Error::Io {
+                        file: file.clone(),
+                        source,
+                    })?;
+
+                    environment
+                        .add_template_owned(name, source)
+                        .map_err(template_error(&file))?;
+                }
+                None => environment
+                    .add_template(name, default)
+                    .map_err(template_error(name))?,
+            }
+        }
+
+        Ok(Self { environment })
+    }
+
+    pub fn render(&self, name: &str, context: impl Serialize) -> Result<String> {
+        self.environment
+            .get_template(name)
+            .and_then(|template| template.render(context))
+            .map_err(template_error(name))
+    }
+}
+
+/// Writes the [`DEFAULT_TEMPLATES`] to `directory`, for them to be changed
+/// and given back to [`Templates::new`]. Files already there are kept.
+pub fn write_default_templates(directory: &str) -> Result<()> {
+    fs::create_dir_all(directory).map_err(|source| Error::Io {
+        file: String::from(directory),
+        source,
+    })?;
+
+    for (name, default) in DEFAULT_TEMPLATES {
+        let file = Path::new(directory).join(name);
+
+        if !file.exists() {
+            fs::write(&file, default).map_err(|source| Error::Io {
+                file: file.to_string_lossy().to_string(),
+                source,
+            })?;
+        }
+    }
+
+    Ok(())
+}
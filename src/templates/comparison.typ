@@ -0,0 +1,18 @@
+<# Same as comparison.tex. #>
+#heading(level: 1, << t.comparison | typst_string >>)
+
+#[
+#set text(size: 7pt)
+#table(
+	columns: (30%, 12%, 6%, 8%, 14%, 14%, 14%),
+	stroke: none,
+	table.header(<< t.faculty | typst_string >>, << t.subject | typst_string >>, << t.year | typst_string >>, << t.students | typst_string >>, << t.cutoff | typst_string >>, << t.median | typst_string >>, << t.grant_threshold | typst_string >>),
+	table.hline(stroke: 1.5pt),
+<% for comparison in comparisons %>
+<% for year in comparison.years %>
+	<% if loop.first %><< (comparison.faculty_id ~ " " ~ (comparison.name | trim) if comparison.faculty_id else t.all_students) | typst_string >>, << comparison.subject | typst_string >><% else %>[], []<% endif %>, [<< year.year >>], [<< year.students >>], << (year.cutoff ~ (" (" ~ year.cutoff_change ~ ")" if year.cutoff_change else "")) | typst_string >>, << (year.median ~ (" (" ~ year.median_change ~ ")" if year.median_change else "")) | typst_string >>, << (year.grant_threshold ~ (" (" ~ year.grant_threshold_change ~ ")" if year.grant_threshold_change else "")) | typst_string >>,
+<% endfor %>
+	table.hline(),
+<% endfor %>
+)
+]
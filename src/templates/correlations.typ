@@ -0,0 +1,43 @@
+<# Same as correlations.tex. #>
+#heading(level: 1, << t.correlations | typst_string >>)
+
+#[
+#set text(size: 7pt)
+#table(
+	columns: (40%, 12%, 12%, 12%),
+	align: center,
+	stroke: none,
+	table.header(<< t.subjects_label | typst_string >>, << t.students | typst_string >>, << t.pearson | typst_string >>, << t.spearman | typst_string >>),
+	table.hline(stroke: 1.5pt),
+<% for correlation in correlations %>
+	<< correlation.subjects | join(" – ") | typst_string >>, [<< correlation.students >>], [<< correlation.pearson >>], [<< correlation.spearman >>],
+	table.hline(),
+<% endfor %>
+)
+]
+
+#heading(level: 2, << t.combinations | typst_string >>)
+
+#[
+#set text(size: 7pt)
+#table(
+	columns: (50%, 12%, 12%),
+	align: center,
+	stroke: none,
+	table.header(<< t.subjects_label | typst_string >>, << t.faculty | typst_string >>, << t.admitted | typst_string >>),
+	table.hline(stroke: 1.5pt),
+<% for combination in combinations %>
+	<< combination.subjects | join(", ") | typst_string >>, [<< combination.faculties >>], [<< combination.admitted >>],
+	table.hline(),
+<% endfor %>
+)
+]
+<% if plots %>
+
+#grid(
+	columns: 3,
+<% for plot in plots %>
+	image(<< plot | typst_string >>),
+<% endfor %>
+)
+<% endif %>
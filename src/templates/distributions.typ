@@ -0,0 +1,20 @@
+<# Same as distributions.tex. #>
+#heading(level: 1, << t.distributions | typst_string >>)
+<% for distribution in distributions %>
+
+#heading(level: 2, << distribution.subject | typst_string >>)
+#table(
+	columns: << percentiles | length + 4 >>,
+	align: center,
+	stroke: none,
+	table.header([], << t.count | typst_string >>, << t.mean | typst_string >>, << t.standard_deviation | typst_string >>, <% for percentile in percentiles %>[p<< percentile >>], <% endfor %>),
+	table.hline(),
+<% for row in distribution.rows %>
+	<< row.caption | typst_string >>, [<< row.count >>], [<< row.mean >>], [<< row.standard_deviation >>], <% for score in row.scores %>[<< score >>], <% endfor %>
+<% endfor %>
+)
+<% if distribution.plot %>
+
+#align(center, image(<< distribution.plot | typst_string >>, width: 90%))
+<% endif %>
+<% endfor %>
@@ -0,0 +1,18 @@
+<# Same as faculty.tex. #>
+#heading(level: 1, << (faculty.id ~ " - " ~ (school.name | trim)) | typst_string >>)
+#heading(level: 2, << faculty.name | trim | typst_string >>)
+
+#table(
+	columns: (3%, 7%, 8%, <% for _ in range(subjects | length + 1) %>10%, <% endfor %>7%),
+	align: center,
+	stroke: none,
+	table.header("", << t.place | typst_string >>, << t.id | typst_string >>, <% for subject in subjects %><< subject | typst_string >>, <% endfor %><< t.competitive | typst_string >>, << t.grant | typst_string >>),
+	table.hline(),
+<% for student in students %>
+	text(fill: gray)[<< loop.index >>], [<< student.placement >>], text(fill: gray, << student.id | typst_string >>), <% for score in student.scores %>[<< score >>], <% endfor %><< student.overall_score | typst_string >>, [<< student.grant >>],
+<% endfor %>
+)
+<% if plot %>
+
+#align(center, image(<< plot | typst_string >>))
+<% endif %>
@@ -0,0 +1,9 @@
+<# Same as main.tex. #>
+#set page(margin: 2cm)
+#set text(font: "GA Sylvia", lang: << t.code | typst_string >>)
+
+<% for input in inputs %>
+#include "<< input >>.typ"
+<% else %>
+no data
+<% endfor %>
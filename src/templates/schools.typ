@@ -0,0 +1,16 @@
+<# Same as schools.tex. #>
+#heading(level: 1, << t.school_ranking | typst_string >>)
+
+#[
+#set text(size: 7pt)
+#table(
+	columns: (5%, 34%, 8%, 8%, 8%, 8%, 22%),
+	stroke: none,
+	table.header([\#], << t.school | typst_string >>, << t.faculty | typst_string >>, << t.admitted | typst_string >>, << t.grant | typst_string >>, << t.percentile | typst_string >>, << (t.subject ~ ": " ~ t.mean ~ " / " ~ t.median) | typst_string >>),
+	table.hline(stroke: 1.5pt),
+<% for school in schools %>
+	[<< school.rank >>], << school.name | trim | typst_string >>, [<< school.faculties >>], [<< school.enrolled >>], [<< school.grant_share >>%], [<< school.selectivity >>], [<% for subject in school.subjects %>#<< (subject.subject ~ ": " ~ subject.score ~ " / " ~ subject.median) | typst_string >><% if not loop.last %> \ <% endif %><% endfor %>],
+	table.hline(),
+<% endfor %>
+)
+]
@@ -0,0 +1,16 @@
+<# Same as summary.tex. #>
+#heading(level: 1, << t.faculty_summary | typst_string >>)
+
+#[
+#set text(size: 7pt)
+#table(
+	columns: (9%, 30%, 7%, 12%, 10%, 22%),
+	stroke: none,
+	table.header(<< t.code_label | typst_string >>, << t.faculty | typst_string >>, << t.admitted | typst_string >>, << t.competitive | typst_string >>, << (t.grant ~ " 100/70/50") | typst_string >>, << (t.subject ~ ": " ~ t.minimum ~ " / " ~ t.median) | typst_string >>),
+	table.hline(stroke: 1.5pt),
+<% for faculty in faculties %>
+	[<< faculty.id >>], [#<< faculty.name | trim | typst_string >> \ #text(fill: gray, << faculty.school | trim | typst_string >>)], [<< faculty.admitted >>], << ((faculty.lowest_score ~ " – " ~ faculty.highest_score) if faculty.lowest_score else "") | typst_string >>, [<< faculty.grants | join("/") >>], [<% for subject in faculty.subjects %>#<< (subject.subject ~ ": " ~ subject.score ~ " / " ~ subject.median) | typst_string >><% if not loop.last %> \ <% endif %><% endfor %>],
+	table.hline(),
+<% endfor %>
+)
+]
@@ -0,0 +1,18 @@
+<# Same as top-list.tex. #>
+#heading(level: 1, << t.top_list | typst_string >>)
+
+#[
+#set text(size: 7pt)
+#table(
+	columns: (4%, 7%, 7%, 8%, 8%, 8%, 35%, 6%),
+	align: center,
+	stroke: none,
+	table.header([\#], <% for i in range(1, 5) %><< (t.subject ~ " " ~ i) | typst_string >>, <% endfor %><< t.competitive | typst_string >>, << t.faculty | typst_string >>, << t.grant | typst_string >>),
+	table.hline(stroke: 1.5pt),
+<% for student in students %>
+	[], <% for i in range(4) %><% if student.subjects[i] %>text(fill: gray)[<< student.subjects[i] >>]<% else %>[]<% endif %>, <% endfor %>[], text(fill: gray, << student.school | typst_string >>), [],
+	[<< loop.index >>], <% for i in range(4) %>[<< student.scores[i] >>], <% endfor %><< student.overall_score | typst_string >>, << student.faculty | typst_string >>, [<< student.grant >>],
+	table.hline(),
+<% endfor %>
+)
+]
@@ -1,227 +1,345 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
+use std::fmt;
 use std::fs;
 use std::fs::File;
 use std::io::{self, Write};
 use std::path::Path;
 use std::process::Command;
+use std::str::FromStr;
 
-// use cpython::{PyResult, Python};
-use csv::{ReaderBuilder, StringRecord};
-use gnuplot::AutoOption::Fix;
-use gnuplot::PlotOption::{Caption, Color};
-use gnuplot::{AxesCommon, Figure};
 use itertools::Itertools;
-
-// mod lib;
-mod parsing;
-mod processing;
-mod read;
-use parsing::*;
-use processing::*;
-use read::*;
+use minijinja::context;
+use serde::Serialize;
+
+use national_exams::admission::*;
+use national_exams::cache::{content_hash, Cache};
+use national_exams::comparison::*;
+use national_exams::competitive::*;
+use national_exams::correlation::*;
+use national_exams::dataset::*;
+use national_exams::descaling::DescalingModel;
+use national_exams::distribution::*;
+use national_exams::error::{Error, Result};
+use national_exams::estimation::*;
+use national_exams::export::*;
+use national_exams::grants::*;
+use national_exams::html::HTMLMaker;
+use national_exams::locale::{Catalog, Lang};
+use national_exams::parsing::{self, *};
+use national_exams::plot::*;
+use national_exams::processing::*;
+use national_exams::query::*;
+use national_exams::read::*;
+use national_exams::spreadsheet::*;
+use national_exams::summary::*;
+use national_exams::templates::*;
+use national_exams::validation::*;
 
 use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
 
-fn save_faculty_plot(
-    students: &[StudentData],
-    faculty: &Faculty,
-    name: &str,
-) -> Result<(), gnuplot::GnuplotInitError> {
-    let subjects = faculty
-        .subjects
-        .iter()
-        .enumerate()
-        .filter_map(|(i, &s)| if s { Some(ALL_SUBJECTS[i]) } else { None })
-        .rev()
-        .collect_vec();
+/// Bumped whenever the plots change, so that stale ones aren't picked up from
+/// the cache.
+const PLOT_VERSION: &str = "faculty-plot-2";
 
-    let mut fg = Figure::new();
+/// Same as [`PLOT_VERSION`], for the histograms of the subjects.
+const DISTRIBUTION_PLOT_VERSION: &str = "distribution-plot-2";
 
-    fg.set_multiplot_layout(3, 2);
-    {
-        let mut students = students.iter().collect_vec();
-
-        for subject in &subjects {
-            // let subject = subjects.iter().fold(&subjects[0], |a, students| {
-            //     ALL_SUBJECTS
-            //         .iter()
-            //         .find(|&x| x == a || x == students)
-            //         .unwrap()
-            // });
-
-            students.sort_by(|a, b| {
-                let a = match a.scores[*subject as usize].unwrap() {
-                    Score::Scaled(scaled) => scaled,
-                    Score::EqualizedAndScaled {
-                        scaled,
-                        equalized: _,
-                    } => scaled,
-                    Score::Equalized(equalized) => equalized,
-                };
-                let b = match b.scores[*subject as usize].unwrap() {
-                    Score::Scaled(scaled) => scaled,
-                    Score::EqualizedAndScaled {
-                        scaled,
-                        equalized: _,
-                    } => scaled,
-                    Score::Equalized(equalized) => equalized,
-                };
+/// Same as [`PLOT_VERSION`], for the scatter plots of subject pairs.
+const CORRELATION_PLOT_VERSION: &str = "correlation-plot-2";
 
-                f32::partial_cmp(&b, &a).unwrap()
-            });
+fn io_error(file: &str) -> impl Fn(io::Error) -> Error + '_ {
+    move |source| Error::Io {
+        file: String::from(file),
+        source,
+    }
+}
 
-            let axes2d = fg
-                .axes2d()
-                .set_x_range(Fix(0.0), Fix(students.len() as f64 + 1.0));
+fn compile_pdf(book_path: &str, backend: Backend) -> Result<()> {
+    let (program, args) = match backend {
+        Backend::Typst => ("typst", vec!["compile", "main.typ"]),
+        _ => ("xelatex", vec!["main.tex"]),
+    };
 
-            for subject in &subjects {
-                let color = subject.color();
-                let subject_string = subject.to_string();
-                axes2d.lines_points(
-                    1..=students.len(),
-                    students
-                        .iter()
-                        .map(|sd| match sd.scores[*subject as usize].unwrap() {
-                            Score::Scaled(scaled) => scaled,
-                            Score::EqualizedAndScaled {
-                                scaled: _,
-                                equalized,
-                            } => equalized,
-                            Score::Equalized(equalized) => equalized,
-                        }),
-                    &[Caption(subject_string.as_str()), Color(color.as_str())],
-                );
-            }
+    println!("compiling {backend}... ");
+    Command::new(program)
+        .current_dir(Path::new(book_path))
+        .args(args)
+        .output()
+        .map_err(io_error(program))?;
+    println!("done.");
+
+    Ok(())
+}
+
+/// What the descaled lists are written out with.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum Backend {
+    /// A PDF book compiled with XeLaTeX.
+    #[default]
+    Latex,
+    /// A PDF book compiled with Typst, faster and without a TeX installation.
+    Typst,
+    /// A single self-contained HTML page.
+    Html,
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "latex" => Ok(Backend::Latex),
+            "typst" => Ok(Backend::Typst),
+            "html" => Ok(Backend::Html),
+            _ => Err(format!(
+                "unknown backend `{s}`, expected `latex`, `typst` or `html`"
+            )),
         }
     }
-    let axes2d = fg.axes2d();
-
-    axes2d
-        .lines_points(
-            1..=students.len(),
-            students
-                .iter()
-                .map(|sd| sd.overall_score.parse::<f32>().unwrap()),
-            &[Caption("საკონკურსო"), Color("black")],
-        )
-        .set_x_range(Fix(0.0), Fix(students.len() as f64 + 1.0));
+}
 
-    fg.save_to_eps(name, 6.0, 8.0)
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Backend::Latex => "latex",
+            Backend::Typst => "typst",
+            Backend::Html => "html",
+        })
+    }
 }
 
-fn compile_pdf(book_path: &str) {
-    println!("compiling latex... ");
-    Command::new("xelatex")
-        .current_dir(Path::new(book_path))
-        .arg("main.tex")
-        .output()
-        .expect("couldn't compile latex");
-    println!("done.");
+impl Backend {
+    /// Extension of the files the book is compiled from.
+    fn source_extension(&self) -> &'static str {
+        match self {
+            Backend::Typst => "typ",
+            _ => "tex",
+        }
+    }
+
+    /// Format of the plots the book includes.
+    fn plot_format(&self) -> PlotFormat {
+        match self {
+            Backend::Typst => PlotFormat::Svg,
+            _ => PlotFormat::Png,
+        }
+    }
 }
 
 struct PDFMaker {
     work_path: String,
     output_file: String,
     top_list_string: Option<String>,
+    summary_string: Option<String>,
+    schools_string: Option<String>,
+    distributions_string: Option<String>,
+    correlations_string: Option<String>,
+    comparison_string: Option<String>,
     faculty_strings: Option<Vec<(String, String)>>,
-    has_graphs: HashSet<String>,
+    /// Faculties with a plot, with the cache key of the plot.
+    has_graphs: HashMap<String, String>,
+    show_uncertainty: bool,
+    /// [`Backend::Latex`] or [`Backend::Typst`].
+    backend: Backend,
+    /// Every part of the book is written with.
+    templates: Templates,
+    lang: Lang,
+    cache: Option<Cache>,
+    keep_work_dir: bool,
+    /// Cache key of the compiled book, known once it's saved.
+    book_key: Option<String>,
 }
 
 impl PDFMaker {
-    fn new(work_path: String, output_file: String) -> Self {
+    fn new(work_path: String, output_file: String) -> Result<Self> {
         if !Path::new(work_path.as_str()).exists() {
-            fs::create_dir(work_path.as_str()).unwrap();
+            fs::create_dir(work_path.as_str()).map_err(io_error(&work_path))?;
         }
 
         let chapter_forlder = format!("{}/chapters", work_path.as_str());
         if !Path::new(chapter_forlder.as_str()).exists() {
-            fs::create_dir(&chapter_forlder).unwrap();
+            fs::create_dir(&chapter_forlder).map_err(io_error(&chapter_forlder))?;
         }
 
-        Self {
+        Ok(Self {
             work_path,
             output_file,
             top_list_string: None,
+            summary_string: None,
+            schools_string: None,
+            distributions_string: None,
+            correlations_string: None,
+            comparison_string: None,
             faculty_strings: None,
-            has_graphs: HashSet::new(),
-        }
+            has_graphs: HashMap::new(),
+            show_uncertainty: false,
+            backend: Backend::Latex,
+            templates: Templates::new(None)?,
+            lang: Lang::Ka,
+            cache: None,
+            keep_work_dir: false,
+            book_key: None,
+        })
     }
 
-    fn compile(&mut self) -> &mut Self {
-        compile_pdf(self.work_path.as_str());
+    fn show_uncertainty(&mut self, show_uncertainty: bool) -> &mut Self {
+        self.show_uncertainty = show_uncertainty;
 
-        fs::rename(
-            format!("{}/main.pdf", self.work_path),
-            format!("{}.pdf", self.output_file),
-        )
-        .unwrap();
-        fs::remove_dir_all(self.work_path.as_str()).unwrap();
+        self
+    }
+
+    /// What the book is typeset with, set before anything is written.
+    fn backend(&mut self, backend: Backend) -> &mut Self {
+        self.backend = backend;
+
+        self
+    }
+
+    fn templates(&mut self, templates: Templates) -> &mut Self {
+        self.templates = templates;
+
+        self
+    }
+
+    /// The language of the book, set before anything is written.
+    fn lang(&mut self, lang: Lang) -> &mut Self {
+        self.lang = lang;
+
+        self
+    }
+
+    /// Plots and books made before are taken from the cache instead of being
+    /// drawn and compiled again.
+    fn cache(&mut self, cache: Option<Cache>) -> &mut Self {
+        self.cache = cache;
+
+        self
+    }
+
+    fn keep_work_dir(&mut self, keep_work_dir: bool) -> &mut Self {
+        self.keep_work_dir = keep_work_dir;
 
         self
     }
 
-    fn save(&mut self) -> io::Result<&mut Self> {
+    fn compile(&mut self) -> Result<&mut Self> {
+        let output_pdf = format!("{}.pdf", self.output_file);
+
+        let cached_pdf = match (&self.cache, &self.book_key) {
+            (Some(cache), Some(key)) => cache.get(key, "pdf"),
+            _ => None,
+        };
+
+        if let Some(cached_pdf) = cached_pdf {
+            println!("using the cached book.");
+            fs::copy(&cached_pdf, &output_pdf).map_err(io_error(&output_pdf))?;
+        } else {
+            compile_pdf(self.work_path.as_str(), self.backend)?;
+
+            let main_pdf = format!("{}/main.pdf", self.work_path);
+            if let (Some(cache), Some(key)) = (&self.cache, &self.book_key) {
+                cache.put(key, "pdf", Path::new(&main_pdf))?;
+            }
+            fs::rename(&main_pdf, &output_pdf).map_err(io_error(&main_pdf))?;
+        }
+
+        if !self.keep_work_dir {
+            fs::remove_dir_all(self.work_path.as_str()).map_err(io_error(&self.work_path))?;
+        }
+
+        Ok(self)
+    }
+
+    fn save(&mut self) -> Result<&mut Self> {
+        let extension = self.backend.source_extension();
+
         let mut main_file_inputs = Vec::new();
+        // everything the book is compiled from
+        let mut book_parts = Vec::new();
 
         if let Some(top_list) = &self.top_list_string {
-            main_file_inputs.push(String::from("\\input{top-list}"));
+            main_file_inputs.push(String::from("top-list"));
+
+            let top_list_file = format!("{}/top-list.{extension}", self.work_path);
+            let mut writer = File::create(&top_list_file).map_err(io_error(&top_list_file))?;
+            writer
+                .write_all(top_list.as_bytes())
+                .map_err(io_error(&top_list_file))?;
+            book_parts.push(top_list.clone());
+        }
 
-            let mut writer = File::create(format!("{}/top-list.tex", self.work_path))?;
-            writer.write_all(top_list.as_bytes())?;
+        if let Some(summary) = &self.summary_string {
+            main_file_inputs.push(String::from("summary"));
+
+            let summary_file = format!("{}/summary.{extension}", self.work_path);
+            fs::write(&summary_file, summary).map_err(io_error(&summary_file))?;
+            book_parts.push(summary.clone());
         }
 
-        if let Some(faculty_strings) = &self.faculty_strings {
-            for (faculty_id, faculty_string) in faculty_strings {
-                let mut faculty_string = String::from(faculty_string);
+        if let Some(schools) = &self.schools_string {
+            main_file_inputs.push(String::from("schools"));
 
-                main_file_inputs.push(format!("\\input{{chapters/{}}}", faculty_id));
+            let schools_file = format!("{}/schools.{extension}", self.work_path);
+            fs::write(&schools_file, schools).map_err(io_error(&schools_file))?;
+            book_parts.push(schools.clone());
+        }
 
-                let mut writer =
-                    File::create(format!("{}/chapters/{}.tex", self.work_path, faculty_id))?;
+        if let Some(distributions) = &self.distributions_string {
+            main_file_inputs.push(String::from("distributions"));
 
-                if self.has_graphs.contains(faculty_id) {
-                    faculty_string += format!(
-                        "\n\\begin{{figure}}[H]\\centering
-    \\includegraphics{{chapters/{faculty_id}.eps}}
-\\end{{figure}}"
-                    )
-                    .as_str();
-                }
+            let distributions_file = format!("{}/distributions.{extension}", self.work_path);
+            fs::write(&distributions_file, distributions).map_err(io_error(&distributions_file))?;
+            // the plots are named after their cache keys
+            book_parts.push(distributions.clone());
+        }
 
-                writer.write_all(faculty_string.as_bytes())?;
-            }
+        if let Some(correlations) = &self.correlations_string {
+            main_file_inputs.push(String::from("correlations"));
+
+            let correlations_file = format!("{}/correlations.{extension}", self.work_path);
+            fs::write(&correlations_file, correlations).map_err(io_error(&correlations_file))?;
+            book_parts.push(correlations.clone());
         }
 
-        let main_file = format!(
-            "\\documentclass{{article}}
+        if let Some(comparison) = &self.comparison_string {
+            main_file_inputs.push(String::from("comparison"));
 
-\\usepackage[margin=2cm]{{geometry}}
+            let comparison_file = format!("{}/comparison.{extension}", self.work_path);
+            fs::write(&comparison_file, comparison).map_err(io_error(&comparison_file))?;
+            book_parts.push(comparison.clone());
+        }
 
-\\usepackage{{fontspec}}
-\\usepackage{{float}}
-\\usepackage{{graphics}}
-\\usepackage{{xcolor}}
+        if let Some(faculty_strings) = &self.faculty_strings {
+            for (faculty_id, faculty_string) in faculty_strings {
+                main_file_inputs.push(format!("chapters/{faculty_id}"));
 
-\\usepackage[T1]{{fontenc}}
-\\setmainfont{{GA Sylvia}}
-\\usepackage[georgian]{{babel}}
-\\usepackage{{longtable,array}}
+                let chapter_file = format!("{}/chapters/{faculty_id}.{extension}", self.work_path);
+                let mut writer = File::create(&chapter_file).map_err(io_error(&chapter_file))?;
 
-\\newcolumntype{{C}}[1]{{>{{\\centering\\arraybackslash}}p{{#1}}}}
+                if let Some(plot_key) = self.has_graphs.get(faculty_id) {
+                    book_parts.push(plot_key.clone());
+                }
 
-\\begin{{document}}
-\t{}
-\\end{{document}}",
-            if !main_file_inputs.is_empty() {
-                main_file_inputs.join("\n\t")
-            } else {
-                String::from("no data")
+                writer
+                    .write_all(faculty_string.as_bytes())
+                    .map_err(io_error(&chapter_file))?;
+                book_parts.push(faculty_string.clone());
             }
-        );
+        }
+
+        let main_file = self.templates.render(
+            &format!("main.{extension}"),
+            context! { inputs => main_file_inputs, t => self.lang.catalog() },
+        )?;
 
-        let mut book_writer = File::create(format!("{}/main.tex", self.work_path))
-            .expect("couldn't create book file");
-        book_writer.write_all(main_file.as_bytes())?;
+        let book_file = format!("{}/main.{extension}", self.work_path);
+        let mut book_writer = File::create(&book_file).map_err(io_error(&book_file))?;
+        book_writer
+            .write_all(main_file.as_bytes())
+            .map_err(io_error(&book_file))?;
+        book_parts.push(main_file);
+
+        self.book_key = Some(content_hash(&book_parts));
 
         Ok(self)
     }
@@ -230,31 +348,61 @@ impl PDFMaker {
         &mut self,
         faculty_buckets: &Vec<(&String, &[StudentData])>,
         faculties: &HashMap<String, Faculty>,
-    ) -> &mut Self {
+    ) -> Result<&mut Self> {
         println!("writing graphs... ");
 
         for (faculty_id, students) in faculty_buckets {
             if students.len() > 1 {
-                self.has_graphs.insert(String::from(faculty_id.to_owned()));
+                let plotted = students
+                    .iter()
+                    .map(|s| (s.scores, &s.overall_score))
+                    .collect_vec();
+                let plot_key = content_hash(&[
+                    PLOT_VERSION.as_bytes(),
+                    self.lang.to_string().as_bytes(),
+                    format!("{:?}", student_faculty(faculties, &students[0])?.subjects).as_bytes(),
+                    format!("{plotted:?}").as_bytes(),
+                ]);
+
+                self.has_graphs
+                    .insert(String::from(faculty_id.to_owned()), plot_key);
             }
         }
 
         faculty_buckets
             .par_iter()
             // .iter()
-            .for_each(|(faculty_id, students)| {
-                if students.len() > 1 {
-                    save_faculty_plot(
-                        students,
-                        faculties.get(*faculty_id).unwrap(),
-                        format!("{}/chapters/{faculty_id}.eps", self.work_path).as_str(),
-                    )
-                    .unwrap();
+            .try_for_each(|(faculty_id, students)| {
+                let Some(plot_key) = self.has_graphs.get(*faculty_id) else {
+                    return Ok(());
+                };
+                let extension = self.backend.plot_format().extension();
+                let plot_file = format!("{}/chapters/{faculty_id}.{extension}", self.work_path);
+
+                match self.cache.as_ref().and_then(|c| c.get(plot_key, extension)) {
+                    Some(cached_plot) => {
+                        fs::copy(&cached_plot, &plot_file).map_err(io_error(&plot_file))?;
+                    }
+                    None => {
+                        save_faculty_plot(
+                            students,
+                            student_faculty(faculties, &students[0])?,
+                            self.lang.catalog(),
+                            plot_file.as_str(),
+                            self.backend.plot_format(),
+                        )?;
+
+                        if let Some(cache) = &self.cache {
+                            cache.put(plot_key, extension, Path::new(&plot_file))?;
+                        }
+                    }
                 }
-            });
+
+                Ok(())
+            })?;
 
         println!("done.");
-        self
+        Ok(self)
     }
 
     fn write_faculties(
@@ -262,14 +410,18 @@ impl PDFMaker {
         faculty_buckets: &Vec<(&String, &[StudentData])>,
         schools: &HashMap<String, School>,
         faculties: &HashMap<String, Faculty>,
-    ) -> &mut Self {
+    ) -> Result<&mut Self> {
+        let template = format!("faculty.{}", self.backend.source_extension());
+        let t = self.lang.catalog();
+
         let mut full_res = Vec::new();
         for (faculty_id, students) in faculty_buckets {
             let faculty_id = String::from(*faculty_id);
-            let faculty = faculties.get(&faculty_id).unwrap();
-            let school = schools.get(&faculty_id[0..3]).unwrap();
-
-            let mut res = String::new();
+            let Some(first_student) = students.first() else {
+                continue;
+            };
+            let faculty = student_faculty(faculties, first_student)?;
+            let school = faculty_school(schools, &faculty_id);
 
             let subjects = faculty
                 .subjects
@@ -279,73 +431,298 @@ impl PDFMaker {
                 .rev()
                 .collect_vec();
 
-            res += format!(
-                "\\section*{{{} - {}}}\n\\subsection*{{{}}}\n",
-                faculty.id,
-                school.name.trim(),
-                faculty.name.trim()
-            )
-            .as_str();
-
-            res += format!(
-                    "\n\\begin{{longtable}}{{ C{{0.03\\textwidth}} C{{0.07\\textwidth}} C{{0.08\\textwidth}}{}C{{0.07\\textwidth}}}}",
-                    " C{0.1\\textwidth} ".repeat(2 + subjects.len())
-                )
-                .as_str();
-
-            res += format!(
-                "\n\t{} \\\\\\hline",
-                [
-                    vec![
-                        String::from(""),
-                        String::from("ადგილი"),
-                        String::from("ნომერი")
-                    ],
-                    subjects.iter().map(|a| a.to_string()).collect_vec(),
-                    vec![String::from("საკონკურსო"), String::from("გრანტი")]
-                ]
-                .concat()
-                .join(" & ")
-            )
-            .as_str();
-
-            for (student_index, student_data) in students.iter().enumerate() {
-                let scores = subjects
-                    .iter()
-                    .map(|subject| student_data.scores[*subject as usize].unwrap().to_latex())
-                    .collect_vec();
+            let students = students
+                .iter()
+                .map(|student| ChapterStudent {
+                    placement: student.placement.map(|p| p.to_string()).unwrap_or_default(),
+                    id: &student.id,
+                    scores: subjects
+                        .iter()
+                        .map(|subject| self.score_cell(student.scores[*subject as usize]))
+                        .collect_vec(),
+                    overall_score: &student.overall_score,
+                    grant: student.grant.map(|x| x.to_string()).unwrap_or_default(),
+                })
+                .collect_vec();
 
-                res += format!(
-                    "\n\t{} \\\\",
-                    [
-                        vec![
-                            format!("\\color{{gray}}{}", student_index + 1),
-                            student_data.placement.unwrap().to_string(),
-                            format!("\\color{{gray}}{}", student_data.id)
-                        ],
-                        scores,
-                        vec![
-                            student_data.overall_score.clone(),
-                            match student_data.grant {
-                                Some(x) => x.to_string(),
-                                None => String::from(""),
-                            }
-                        ]
-                    ]
-                    .concat()
-                    .join(" & ")
-                )
-                .as_str();
-            }
+            // plots are drawn before the chapters, the typst chapters include
+            // them from their own folder
+            let plot = self.has_graphs.contains_key(&faculty_id).then(|| {
+                let extension = self.backend.plot_format().extension();
+                match self.backend {
+                    Backend::Typst => format!("{faculty_id}.{extension}"),
+                    _ => format!("chapters/{faculty_id}.{extension}"),
+                }
+            });
 
-            res += "\n\\end{longtable}";
+            let res = self.templates.render(
+                &template,
+                context! {
+                    faculty => faculty,
+                    school => school,
+                    subjects => subjects.iter().map(|a| t.subject(*a)).collect_vec(),
+                    students => students,
+                    plot => plot,
+                    t => t,
+                },
+            )?;
 
             full_res.push((faculty_id, res));
         }
 
         self.faculty_strings = Some(full_res);
 
-        self
+        Ok(self)
+    }
+
+    fn write_summary(
+        &mut self,
+        summaries: &[FacultySummary],
+        schools: &HashMap<String, School>,
+    ) -> Result<&mut Self> {
+        let t = self.lang.catalog();
+
+        let faculties = summaries
+            .iter()
+            .map(|summary| {
+                let school = faculty_school(schools, &summary.faculty_id);
+
+                SummaryFaculty {
+                    id: &summary.faculty_id,
+                    name: &summary.name,
+                    school: school.short_name.unwrap_or(school.name),
+                    admitted: summary.admitted,
+                    lowest_score: summary
+                        .lowest_score
+                        .map(|score| format!("{score:.1}"))
+                        .unwrap_or_default(),
+                    highest_score: summary
+                        .highest_score
+                        .map(|score| format!("{score:.1}"))
+                        .unwrap_or_default(),
+                    grants: [Grant::Hundred, Grant::Seventy, Grant::Fifty]
+                        .map(|grant| summary.grants[grant as usize]),
+                    subjects: subject_scores(&summary.subjects, t, |stats| stats.cutoff),
+                }
+            })
+            .collect_vec();
+
+        self.summary_string = Some(self.templates.render(
+            &format!("summary.{}", self.backend.source_extension()),
+            context! { faculties => faculties, t => t },
+        )?);
+
+        Ok(self)
+    }
+
+    fn write_schools(
+        &mut self,
+        summaries: &[SchoolSummary],
+        schools: &HashMap<String, School>,
+    ) -> Result<&mut Self> {
+        let t = self.lang.catalog();
+
+        let ranked = summaries
+            .iter()
+            .map(|summary| {
+                let school = school_or_id(schools, &summary.school_id);
+
+                RankedSchool {
+                    rank: summary.rank.map(|r| r.to_string()).unwrap_or_default(),
+                    name: school.short_name.unwrap_or(school.name),
+                    faculties: summary.faculties,
+                    enrolled: summary.enrolled,
+                    grant_share: format!("{:.0}", summary.grant_share * 100.0),
+                    selectivity: summary
+                        .selectivity
+                        .map(|s| format!("{s:.1}"))
+                        .unwrap_or_default(),
+                    subjects: subject_scores(&summary.subjects, t, |stats| stats.mean),
+                }
+            })
+            .collect_vec();
+
+        self.schools_string = Some(self.templates.render(
+            &format!("schools.{}", self.backend.source_extension()),
+            context! { schools => ranked, t => t },
+        )?);
+
+        Ok(self)
+    }
+
+    fn write_distributions(
+        &mut self,
+        distributions: &[SubjectDistribution],
+        graphs: bool,
+    ) -> Result<&mut Self> {
+        let plot_folder = format!("{}/distributions", self.work_path);
+        let format = self.backend.plot_format();
+        let extension = format.extension();
+        if graphs && !Path::new(&plot_folder).exists() {
+            fs::create_dir(&plot_folder).map_err(io_error(&plot_folder))?;
+        }
+
+        let t = self.lang.catalog();
+        let mut chapters = Vec::new();
+
+        for distribution in distributions {
+            if distribution.scaled.is_none() && distribution.equalized.is_none() {
+                continue;
+            }
+
+            let rows = [
+                (t.scaled, &distribution.scaled),
+                (t.equalized, &distribution.equalized),
+            ]
+            .into_iter()
+            .filter_map(|(caption, scores)| {
+                let scores = scores.as_ref()?;
+
+                Some(DistributionRow {
+                    caption,
+                    count: scores.len(),
+                    mean: format!("{:.1}", scores.mean()),
+                    standard_deviation: format!("{:.1}", scores.standard_deviation()),
+                    scores: KEY_PERCENTILES
+                        .iter()
+                        .map(|p| format!("{:.1}", scores.percentile(*p)))
+                        .collect_vec(),
+                })
+            })
+            .collect_vec();
+
+            let plot = if graphs {
+                let plot_key = content_hash(&[
+                    DISTRIBUTION_PLOT_VERSION.as_bytes(),
+                    self.lang.to_string().as_bytes(),
+                    format!("{distribution:?}").as_bytes(),
+                ]);
+                let plot_file = format!("{plot_folder}/{plot_key}.{extension}");
+
+                match self
+                    .cache
+                    .as_ref()
+                    .and_then(|c| c.get(&plot_key, extension))
+                {
+                    Some(cached_plot) => {
+                        fs::copy(&cached_plot, &plot_file).map_err(io_error(&plot_file))?;
+                    }
+                    None => {
+                        save_distribution_plot(distribution, t, &plot_file, format)?;
+
+                        if let Some(cache) = &self.cache {
+                            cache.put(&plot_key, extension, Path::new(&plot_file))?;
+                        }
+                    }
+                }
+
+                Some(format!("distributions/{plot_key}.{extension}"))
+            } else {
+                None
+            };
+
+            chapters.push(DistributionChapter {
+                subject: t.subject(distribution.subject),
+                rows,
+                plot,
+            });
+        }
+
+        self.distributions_string = Some(self.templates.render(
+            &format!("distributions.{}", self.backend.source_extension()),
+            context! {
+                distributions => chapters,
+                percentiles => KEY_PERCENTILES.iter().map(|p| p.to_string()).collect_vec(),
+                t => t,
+            },
+        )?);
+
+        Ok(self)
+    }
+
+    fn write_correlations(
+        &mut self,
+        students: &[StudentData],
+        correlations: &[Correlation],
+        combinations: &[SubjectCombination],
+        graphs: bool,
+    ) -> Result<&mut Self> {
+        let optional = |value: Option<f32>| value.map(|v| format!("{v:.2}")).unwrap_or_default();
+
+        let t = self.lang.catalog();
+        let mut plots = Vec::new();
+
+        if graphs {
+            let plot_folder = format!("{}/correlations", self.work_path);
+            let format = self.backend.plot_format();
+            let extension = format.extension();
+            if !Path::new(&plot_folder).exists() {
+                fs::create_dir(&plot_folder).map_err(io_error(&plot_folder))?;
+            }
+
+            for correlation in correlations {
+                let pairs = paired_scores(students, correlation.subjects);
+
+                let plot_key = content_hash(&[
+                    CORRELATION_PLOT_VERSION.as_bytes(),
+                    self.lang.to_string().as_bytes(),
+                    format!("{:?}{pairs:?}", correlation.subjects).as_bytes(),
+                ]);
+                let plot_file = format!("{plot_folder}/{plot_key}.{extension}");
+
+                match self
+                    .cache
+                    .as_ref()
+                    .and_then(|c| c.get(&plot_key, extension))
+                {
+                    Some(cached_plot) => {
+                        fs::copy(&cached_plot, &plot_file).map_err(io_error(&plot_file))?;
+                    }
+                    None => {
+                        save_correlation_plot(&pairs, correlation.subjects, t, &plot_file, format)?;
+
+                        if let Some(cache) = &self.cache {
+                            cache.put(&plot_key, extension, Path::new(&plot_file))?;
+                        }
+                    }
+                }
+
+                plots.push(format!("correlations/{plot_key}.{extension}"));
+            }
+        }
+
+        let correlations = correlations
+            .iter()
+            .map(|correlation| CorrelationRow {
+                subjects: [
+                    t.subject(correlation.subjects.0),
+                    t.subject(correlation.subjects.1),
+                ],
+                students: correlation.students,
+                pearson: optional(correlation.pearson),
+                spearman: optional(correlation.spearman),
+            })
+            .collect_vec();
+        let combinations = combinations
+            .iter()
+            .map(|combination| CombinationRow {
+                subjects: combination.subjects().map(|s| t.subject(s)).collect_vec(),
+                faculties: combination.faculties,
+                admitted: combination.students,
+            })
+            .collect_vec();
+
+        self.correlations_string = Some(self.templates.render(
+            &format!("correlations.{}", self.backend.source_extension()),
+            context! {
+                correlations => correlations,
+                combinations => combinations,
+                plots => plots,
+                t => t,
+            },
+        )?);
+
+        Ok(self)
     }
 
     fn write_top_list(
@@ -353,136 +730,1052 @@ impl PDFMaker {
         students: &[StudentData],
         schools: &HashMap<String, School>,
         faculties: &HashMap<String, Faculty>,
-    ) -> &mut Self {
-        let mut res = String::new();
+    ) -> Result<&mut Self> {
+        let t = self.lang.catalog();
+        let students = students
+            .iter()
+            .map(|student| {
+                let faculty = student_faculty(faculties, student)?;
+                let school = faculty_school(schools, &student.faculty_id);
 
-        res += "\\section*{აბიტურიენტები საკონკურსო ქულის მიხედვით კლებადობით}
+                let subjects = faculty
+                    .subjects
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, &s)| if s { Some(ALL_SUBJECTS[i]) } else { None })
+                    .rev()
+                    .take(4)
+                    .collect_vec();
 
-{
-\\scriptsize
-\\begin{longtable}{C{0.04\\textwidth} | C{0.07\\textwidth} | C{0.07\\textwidth} | C{0.08\\textwidth} | C{0.08\\textwidth} | C{0.08\\textwidth} | C{0.35\\textwidth} | C{0.06\\textwidth}}
-    \\# & საგანი 1 & საგანი 2 & საგანი 3 & საგანი 4 & საკონკურსო & ფაკულტეტი & გრანტი \\\\ \\hline\\hline";
+                Ok(TopListStudent {
+                    subjects: subjects.iter().map(|a| t.subject(*a)).collect_vec(),
+                    scores: subjects
+                        .iter()
+                        .map(|subject| self.score_cell(student.scores[*subject as usize]))
+                        .collect_vec(),
+                    overall_score: &student.overall_score,
+                    faculty: &faculty.name,
+                    school: school.short_name.unwrap_or(school.name),
+                    grant: student.grant.unwrap_or(parsing::Grant::Zero).to_string(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        self.top_list_string = Some(self.templates.render(
+            &format!("top-list.{}", self.backend.source_extension()),
+            context! { students => students, t => t },
+        )?);
 
-        for (student_index, student) in students.iter().enumerate() {
-            let faculty = faculties.get(&student.faculty_id).unwrap();
-            let school = schools.get(&student.faculty_id[0..3]).unwrap();
+        Ok(self)
+    }
 
-            let subjects = faculty
-                .subjects
-                .iter()
-                .enumerate()
-                .filter_map(|(i, &s)| if s { Some(ALL_SUBJECTS[i]) } else { None })
-                .rev()
-                .collect_vec();
+    fn write_comparisons(&mut self, comparisons: &[YearComparison]) -> Result<&mut Self> {
+        let t = self.lang.catalog();
+        let optional = |value: Option<f32>| value.map(|v| format!("{v:.1}")).unwrap_or_default();
+        let optional_change =
+            |value: Option<f32>| value.map(|v| format!("{v:+.1}")).unwrap_or_default();
 
-            let student_line = format!("\t & \\color{{gray}}{} & \\color{{gray}}{} & \\color{{gray}}{} & \\color{{gray}}{} & & \\color{{gray}}{} & \\\\\n\t{} & {} & {} & {} & {} & {} & {} & {}\\\\\\hline\n",
-                    match subjects.get(0) {Some(subject) => subject.to_string(), None => String::default()},
-                    match subjects.get(1) {Some(subject) => subject.to_string(), None => String::default()},
-                    match subjects.get(2) {Some(subject) => subject.to_string(), None => String::default()},
-                    match subjects.get(3) {Some(subject) => subject.to_string(), None => String::default()},
-                    school.short_name.clone().unwrap_or(school.name.clone()),
-                    student_index + 1,
-                    match subjects.get(0) {Some(subject) => student.scores[*subject as usize].unwrap().to_latex(), None => String::default()},
-                    match subjects.get(1) {Some(subject) => student.scores[*subject as usize].unwrap().to_latex(), None => String::default()},
-                    match subjects.get(2) {Some(subject) => student.scores[*subject as usize].unwrap().to_latex(), None => String::default()},
-                    match subjects.get(3) {Some(subject) => student.scores[*subject as usize].unwrap().to_latex(), None => String::default()},
-                    student.overall_score,
-                    faculty.name,
-                    student.grant.unwrap_or(parsing::Grant::Zero).to_string()
-                );
+        let comparisons = comparisons
+            .iter()
+            .map(|comparison| ChapterComparison {
+                faculty_id: comparison.faculty_id.as_deref().unwrap_or_default(),
+                name: &comparison.name,
+                subject: t.subject(comparison.subject),
+                years: comparison
+                    .with_changes()
+                    .map(|(year, summary, change)| ChapterYear {
+                        year,
+                        students: summary.students,
+                        cutoff: format!("{:.1}", summary.cutoff),
+                        median: format!("{:.1}", summary.median),
+                        grant_threshold: optional(summary.grant_threshold),
+                        cutoff_change: optional_change(change.map(|c| c.cutoff)),
+                        median_change: optional_change(change.map(|c| c.median)),
+                        grant_threshold_change: optional_change(
+                            change.and_then(|c| c.grant_threshold),
+                        ),
+                    })
+                    .collect_vec(),
+            })
+            .collect_vec();
+
+        self.comparison_string = Some(self.templates.render(
+            &format!("comparison.{}", self.backend.source_extension()),
+            context! { comparisons => comparisons, t => t },
+        )?);
 
-            res += student_line.as_str();
+        Ok(self)
+    }
+
+    /// `score` as a table cell of the backend, empty if there is none.
+    fn score_cell(&self, score: Option<Score>) -> String {
+        match (score, self.backend) {
+            (None, _) => String::new(),
+            (Some(score), Backend::Typst) => score.to_typst(self.show_uncertainty),
+            (Some(score), _) => score.to_latex(self.show_uncertainty),
         }
-        res += "\\end{longtable}\n}";
+    }
+}
 
-        self.top_list_string = Some(res);
+/// A student of a faculty chapter, as the templates see them.
+#[derive(Serialize)]
+struct ChapterStudent<'a> {
+    /// Empty if the student wasn't placed.
+    placement: String,
+    id: &'a str,
+    /// Of the faculty's subjects, in the order of the columns.
+    scores: Vec<String>,
+    overall_score: &'a str,
+    grant: String,
+}
 
-        self
-    }
+/// The faculty of the student, an error if the dataset doesn't have it.
+fn student_faculty<'a>(
+    faculties: &'a HashMap<String, Faculty>,
+    student: &StudentData,
+) -> Result<&'a Faculty> {
+    faculties
+        .get(&student.faculty_id)
+        .ok_or_else(|| Error::UnknownFaculty {
+            student_id: student.id.clone(),
+            faculty_id: student.faculty_id.clone(),
+        })
+}
+
+/// A faculty of the summary, as the templates see it.
+#[derive(Serialize)]
+struct SummaryFaculty<'a> {
+    id: &'a str,
+    name: &'a str,
+    school: String,
+    admitted: usize,
+    /// Empty if there are no published scores.
+    lowest_score: String,
+    highest_score: String,
+    /// Of 100, 70 and 50.
+    grants: [usize; 3],
+    subjects: Vec<SubjectScores>,
+}
+
+/// A school of the ranking, as the templates see it.
+#[derive(Serialize)]
+struct RankedSchool {
+    /// Empty if the school has no selectivity.
+    rank: String,
+    name: String,
+    faculties: usize,
+    enrolled: usize,
+    /// In percent.
+    grant_share: String,
+    selectivity: String,
+    subjects: Vec<SubjectScores>,
+}
+
+/// A subject's scores in the summary or the ranking.
+#[derive(Serialize)]
+struct SubjectScores {
+    subject: &'static str,
+    /// The lowest score in the summary, the mean in the ranking.
+    score: String,
+    median: String,
+}
+
+/// Of the subjects that have them, in the order of the columns.
+fn subject_scores(
+    summaries: &[Option<ScoreSummary>; ALL_SUBJECTS.len()],
+    t: &Catalog,
+    score: fn(&ScoreSummary) -> f32,
+) -> Vec<SubjectScores> {
+    ALL_SUBJECTS
+        .iter()
+        .rev()
+        .filter_map(|subject| {
+            let stats = summaries[*subject as usize].as_ref()?;
+
+            Some(SubjectScores {
+                subject: t.subject(*subject),
+                score: format!("{:.1}", score(stats)),
+                median: format!("{:.1}", stats.median),
+            })
+        })
+        .collect_vec()
+}
+
+/// A subject's distribution, as the templates see it.
+#[derive(Serialize)]
+struct DistributionChapter {
+    subject: &'static str,
+    /// Of the scaled and of the equalized scores, if there are any.
+    rows: Vec<DistributionRow>,
+    plot: Option<String>,
+}
+
+#[derive(Serialize)]
+struct DistributionRow {
+    caption: &'static str,
+    count: usize,
+    mean: String,
+    standard_deviation: String,
+    /// At [`KEY_PERCENTILES`].
+    scores: Vec<String>,
+}
+
+/// A pair of subjects, as the templates see it.
+#[derive(Serialize)]
+struct CorrelationRow {
+    subjects: [&'static str; 2],
+    students: usize,
+    /// Empty if it can't be told.
+    pearson: String,
+    spearman: String,
+}
+
+/// A combination of subjects, as the templates see it.
+#[derive(Serialize)]
+struct CombinationRow {
+    subjects: Vec<&'static str>,
+    faculties: usize,
+    admitted: usize,
+}
+
+/// A subject compared over the years, as the templates see it.
+#[derive(Serialize)]
+struct ChapterComparison<'a> {
+    /// Empty for all the students.
+    faculty_id: &'a str,
+    name: &'a str,
+    subject: &'static str,
+    years: Vec<ChapterYear>,
+}
+
+/// A year of a [`ChapterComparison`], the changes since the year before empty
+/// for the first one.
+#[derive(Serialize)]
+struct ChapterYear {
+    year: u16,
+    students: usize,
+    cutoff: String,
+    median: String,
+    /// Empty if no one got a grant.
+    grant_threshold: String,
+    cutoff_change: String,
+    median_change: String,
+    grant_threshold_change: String,
+}
+
+/// A student of the top list, as the templates see them.
+#[derive(Serialize)]
+struct TopListStudent<'a> {
+    /// Names of the faculty's subjects, at most four.
+    subjects: Vec<&'static str>,
+    scores: Vec<String>,
+    overall_score: &'a str,
+    faculty: &'a str,
+    school: String,
+    grant: String,
 }
 
 //
 
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
 
 /// გადააქციე ჩარიცხვებისა და რანჟირებული ქულების
-/// PDF ფაილი დესკალირებული და დახარისხებული სიად
+/// PDF ფაილი დესკალირებული და დახარისხებული სიად /
+/// turn the PDF of the admissions and the ranked scores into a descaled and
+/// sorted list
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
-    /// ჩარიცხვების PDF ფაილი
+    #[command(subcommand)]
+    command: Commands,
+    /// სიების და შეტყობინებების ენა: ka ან en / language of the lists and
+    /// the messages: ka or en
+    #[arg(short, long, global = true, default_value = "ka")]
+    lang: Lang,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// ამოიღოს ჩარიცხვების PDF ფაილიდან მონაცემები TSV ფაილში /
+    /// extract the data of the publication PDF into a TSV file
+    Extract {
+        /// ჩარიცხვების PDF ფაილი / the publication PDF
+        input_file: String,
+        /// TSV ფაილი, რომელშიც ჩაიწეროს მონაცემები / the TSV file to
+        /// write the data to
+        output_file: Option<String>,
+        /// არ გამოიყენოს ქეში / don't use the cache
+        #[arg(long)]
+        no_cache: bool,
+    },
+    /// დესკალირება და დახარისხება, შედეგი ჩაიწეროს მონაცემთა ფაილში /
+    /// descale and sort, writing the result to a dataset file
+    Descale {
+        #[command(flatten)]
+        inputs: Inputs,
+        /// მონაცემთა (JSON) ფაილი, რომელშიც ჩაიწეროს შედეგი / the dataset
+        /// (JSON) file to write the result to
+        output_file: Option<String>,
+        /// რომელი საკონკურსო ქულით დახარისხდეს: published (გამოქვეყნებული)
+        /// ან equalized (გათანაბრებული ქულებიდან გამოთვლილი) / the
+        /// competitive score to sort by: published or equalized (computed
+        /// from the equalized scores)
+        #[arg(short, long, default_value = "published")]
+        rank_by: Ranking,
+        /// გამოცდების წელი, ნაგულისხმევად ჩარიცხვების ფაილის სახელიდან /
+        /// the exam year, by default from the name of the publication
+        #[arg(short, long)]
+        year: Option<u16>,
+    },
+    /// მონაცემთა ფაილიდან შექმნას PDF, HTML ან CSV/JSON / make a PDF,
+    /// HTML or CSV/JSON from a dataset file
+    Report(ReportArgs),
+    /// შეამოწმოს შემავალი ფაილები და ანკერები (თითოეულის გამოკლებით) /
+    /// check the input files and the anchors (leaving out each in turn)
+    Validate {
+        #[command(flatten)]
+        inputs: Inputs,
+    },
+    /// ხელახლა ჩარიცხოს აბიტურიენტები დესკალირებული ქულებით / admit the
+    /// students again by their descaled scores
+    Simulate {
+        /// მონაცემთა ფაილი (descale-ის შედეგი) / the dataset file (made by
+        /// descale)
+        dataset_file: String,
+        /// ფაკულტეტების ადგილების CSV ფაილი (faculty_id,capacity),
+        /// ნაგულისხმევად ჩარიცხულთა რაოდენობა / a CSV file of the
+        /// faculties' places (faculty_id,capacity), by default as many as
+        /// were admitted
+        #[arg(short, long)]
+        capacities: Option<String>,
+        /// აბიტურიენტების არჩევანის CSV ფაილი
+        /// (student_id,faculty_id,faculty_id,...) / a CSV file of the
+        /// students' choices (student_id,faculty_id,faculty_id,...)
+        #[arg(short, long)]
+        preferences: Option<String>,
+        /// ფაკულტეტების კოეფიციენტების CSV ფაილი (faculty_id,subject,weight),
+        /// ნაგულისხმევად შეფასდეს გამოქვეყნებული ქულებიდან / a CSV file of
+        /// the faculties' coefficients (faculty_id,subject,weight), by
+        /// default fitted to the published scores
+        #[arg(short, long)]
+        weights: Option<String>,
+    },
+    /// ხელახლა გასცეს გრანტები დესკალირებული ქულებით / give out the
+    /// grants again by the descaled scores
+    Grants {
+        /// მონაცემთა ფაილი (descale-ის შედეგი) / the dataset file (made by
+        /// descale)
+        dataset_file: String,
+        /// გრანტების წესების CSV ფაილი (grant,quota|threshold,value),
+        /// ნაგულისხმევად გამოქვეყნებული გრანტების რაოდენობები / a CSV
+        /// file of the grant rules (grant,quota|threshold,value), by default
+        /// as many grants as were published
+        #[arg(short, long)]
+        rules: Option<String>,
+        /// გრანტის ქულაში შემავალი საგნები, მძიმით გამოყოფილი,
+        /// ნაგულისხმევად ფაკულტეტის საგნები / the subjects of the grant
+        /// score, separated by commas, by default the faculty's subjects
+        #[arg(short, long, value_delimiter = ',', value_parser = parse_subject)]
+        subjects: Vec<Subject>,
+        /// CSV ფაილი, რომელშიც ჩაიწეროს თითოეული აბიტურიენტის გრანტი /
+        /// the CSV file to write every student's grant to
+        output_file: Option<String>,
+    },
+    /// სად დგას საგნის ქულა: პროცენტილი და ფაკულტეტები, რომლებზეც ის
+    /// საკმარისი იქნებოდა / where a subject score stands: its percentile
+    /// and the faculties it would have been enough for
+    Query {
+        /// მონაცემთა ფაილი (descale-ის შედეგი) / the dataset file (made by
+        /// descale)
+        dataset_file: String,
+        /// საგანი (სახელი ან გასაღები, მაგ. math) / the subject (its name
+        /// or its key, e.g. math)
+        #[arg(value_parser = parse_subject)]
+        subject: Subject,
+        /// გათანაბრებული ქულა / the equalized score
+        score: f32,
+        /// ქულა სკალირებულია (სერტიფიკატიდან), გათანაბრებული შეფასდეს
+        /// დესკალირების მონაცემებით / the score is scaled (from the
+        /// certificate), the equalized one is estimated with the descaling
+        /// data
+        #[arg(short, long, requires = "descaling_data_file")]
+        scaled: bool,
+        /// დესკალირების მონაცემების CSV ან XLSX/ODS ფაილი / the CSV or
+        /// XLSX/ODS file of the descaling data
+        #[arg(short, long = "descaling-data")]
+        descaling_data_file: Option<String>,
+    },
+    /// შეადაროს სხვადასხვა წლის მონაცემები / compare the data of several
+    /// years
+    Compare(CompareArgs),
+    /// ჩაწეროს წიგნის ნაგულისხმევი შაბლონები საქაღალდეში, report
+    /// --templates-ით შესაცვლელად / write the default templates of the
+    /// book to a folder, to be changed and given to report --templates
+    Templates {
+        /// საქაღალდე, არსებული ფაილები არ გადაიწერება / the folder, files
+        /// already there are kept
+        #[arg(default_value = "templates")]
+        directory: String,
+    },
+}
+
+#[derive(Args, Debug)]
+struct Inputs {
+    /// ჩარიცხვების PDF, TSV ან XLSX/ODS ფაილი / the publication's PDF, TSV
+    /// or XLSX/ODS file
     input_file: String,
-    /// დესკალირების მონაცემების CSV ფაილი
+    /// დესკალირების მონაცემების CSV ან XLSX/ODS ფაილი / the CSV or
+    /// XLSX/ODS file of the descaling data
     descaling_data_file: String,
-    /// დროებითი ფაილების საქაღალდე
+    /// დესკალირების მოდელი: linear ან monotone-cubic / the descaling
+    /// model: linear or monotone-cubic
+    #[arg(short, long, default_value = "linear")]
+    model: DescalingModel,
+    /// ანკერების არმქონე საგნების სკალირება შეაფასოს თავად ჩარიცხვების
+    /// სიიდან / estimate the scaling of subjects without anchors from the
+    /// publication itself
+    #[arg(short, long)]
+    estimate_anchors: bool,
+    /// XLSX/ODS ფაილების სვეტების შესაბამისობის CSV ფაილი / a CSV file
+    /// mapping the columns of the XLSX/ODS files
+    #[arg(short, long)]
+    columns: Option<String>,
+    /// ფაკულტეტების კოეფიციენტების CSV ფაილი (faculty_id,subject,weight),
+    /// ნაგულისხმევად შეფასდეს გამოქვეყნებული ქულებიდან / a CSV file of the
+    /// faculties' coefficients (faculty_id,subject,weight), by default
+    /// fitted to the published scores
+    #[arg(short, long)]
+    weights: Option<String>,
+    /// არ გამოიყენოს ქეში / don't use the cache
+    #[arg(long)]
+    no_cache: bool,
+}
+
+#[derive(Args, Debug)]
+struct CompareArgs {
+    /// თითო წელზე მონაცემთა ფაილი (descale-ის შედეგი) ან ჩარიცხვების ფაილი
+    /// და დესკალირების მონაცემები მძიმით გამოყოფილი, სურვილისამებრ წლით
+    /// წინ / a dataset file (made by descale) or a publication and its
+    /// descaling data separated by a comma per year, optionally after the
+    /// year: [YEAR=]DATASET or [YEAR=]PUBLICATION,DESCALING_DATA
+    #[arg(required = true)]
+    inputs: Vec<DatasetInput>,
+    /// ჩარიცხვების ფაილების დესკალირების მოდელი: linear ან monotone-cubic
+    /// / the model the publications are descaled with: linear or
+    /// monotone-cubic
+    #[arg(short, long, default_value = "linear")]
+    model: DescalingModel,
+    /// CSV ფაილი, რომელშიც ჩაიწეროს შედარება / the CSV file to write the
+    /// comparison to
+    #[arg(short, long, default_value = "comparison.csv")]
+    output_file: String,
+    /// შედარება ჩაიწეროს სიაშიც: latex (PDF), typst (PDF) ან html, CSV
+    /// ფაილის სახელით / write the comparison to a list too: latex (PDF),
+    /// typst (PDF) or html, named after the CSV file
+    #[arg(short, long)]
+    backend: Option<Backend>,
+    /// საქაღალდე შაბლონებით, რომლებიც ნაგულისხმევს ჩაანაცვლებს (იხ.
+    /// templates) / a folder of templates replacing the default ones (see
+    /// templates)
+    #[arg(long)]
+    templates: Option<String>,
+    /// არ გამოიყენოს ქეში / don't use the cache
+    #[arg(long)]
+    no_cache: bool,
+}
+
+#[derive(Args, Debug)]
+struct ReportArgs {
+    /// მონაცემთა ფაილი (descale-ის შედეგი) / the dataset file (made by
+    /// descale)
+    dataset_file: String,
+    /// დროებითი ფაილების საქაღალდე / the folder of the temporary files
     work_path: Option<String>,
-    /// შეიცავდეს გრაფიკებს
+    /// შეიცავდეს გრაფიკებს / include graphs
     #[arg(short, long)]
     graphs: bool,
-    /// შეიცავდეს საკონკურსო ქულის მიხედვით 
-    /// დახარისხებულ სიას
+    /// შეიცავდეს საკონკურსო ქულის მიხედვით
+    /// დახარისხებულ სიას / include the list sorted by competitive score
     #[arg(short, long)]
     top_list: bool,
-    /// შეიცავდეს ფაკულტეტებს
+    /// შეიცავდეს ფაკულტეტებს / include the faculties
     #[arg(short, long)]
     faculties: bool,
-    /// შეამოკლოს უნივერსიტეტების სახელები
+    /// შეიცავდეს ფაკულტეტების შეჯამებას (ზღვრები, მედიანები, გრანტები) და
+    /// ჩაწეროს ის CSV ფაილშიც / include the faculty summary (cutoffs,
+    /// medians, grants) and write it to a CSV file too
+    #[arg(short = 'S', long)]
+    summary: bool,
+    /// შეიცავდეს უნივერსიტეტების სტატისტიკას და რეიტინგს და ჩაწეროს ის CSV
+    /// ფაილშიც / include the universities' statistics and ranking and write
+    /// them to a CSV file too
+    #[arg(long)]
+    schools: bool,
+    /// შეიცავდეს საგნების ქულების განაწილებას (-g-თი ჰისტოგრამებსაც) /
+    /// include the distribution of the subject scores (with -g histograms
+    /// too)
+    #[arg(short, long)]
+    distributions: bool,
+    /// შეიცავდეს საგნებს შორის კორელაციას და საგნების კომბინაციებს (-g-თი
+    /// გაბნევის დიაგრამებსაც) და ჩაწეროს ის JSON ფაილშიც / include the
+    /// correlation between subjects and the subject combinations (with -g
+    /// scatter plots too) and write them to a JSON file too
+    #[arg(short, long)]
+    correlations: bool,
+    /// შეამოკლოს უნივერსიტეტების სახელები / shorten the universities'
+    /// names
     #[arg(short, long)]
     shorten_names: bool,
+    /// აჩვენოს დესკალირებული ქულების ცდომილება (±) / show the uncertainty
+    /// of the descaled scores (±)
+    #[arg(short, long)]
+    uncertainty: bool,
+    /// ჩაწეროს დესკალირებული სია ფაილში: csv ან json / write the descaled
+    /// list to a file: csv or json
+    #[arg(short = 'x', long)]
+    export: Option<ExportFormat>,
+    /// ექსპორტისას თითო ფაკულტეტი ცალკე ფაილში ჩაწეროს / export every
+    /// faculty to a file of its own
+    #[arg(long, requires = "export")]
+    export_faculties: bool,
+    /// რაში ჩაიწეროს სიები: latex (PDF), typst (PDF) ან html / what to
+    /// write the lists with: latex (PDF), typst (PDF) or html
+    #[arg(short, long, default_value = "latex")]
+    backend: Backend,
+    /// საქაღალდე შაბლონებით, რომლებიც ნაგულისხმევს ჩაანაცვლებს (იხ.
+    /// templates) / a folder of templates replacing the default ones (see
+    /// templates)
+    #[arg(long)]
+    templates: Option<String>,
+    /// არ გამოიყენოს ქეში / don't use the cache
+    #[arg(long)]
+    no_cache: bool,
+    /// არ წაშალოს დროებითი ფაილების საქაღალდე / keep the folder of the
+    /// temporary files
+    #[arg(long)]
+    keep_work_dir: bool,
 }
 
-fn main() {
-    let Cli {
-        input_file,
-        descaling_data_file,
-        work_path,
-        graphs,
-        top_list,
-        faculties,
-        shorten_names,
-    } = Cli::parse();
-    let include_faculties = faculties;
-    let work_path = work_path.unwrap_or(input_file.replace(".pdf", "-work-directory"));
+/// The file name without its extension, output files are named after it.
+fn file_stem(file_name: &str) -> String {
+    Path::new(file_name)
+        .with_extension("")
+        .to_string_lossy()
+        .into_owned()
+}
 
-    let publication_tsv_file_name = input_file.replace("pdf", "tsv");
+/// A subject by its name or its key.
+fn parse_subject(s: &str) -> std::result::Result<Subject, String> {
+    Subject::from(s)
+        .or_else(|| Subject::from_key(s))
+        .ok_or_else(|| format!("unknown subject `{s}`"))
+}
 
-    parse_publication_pdf(&input_file, &publication_tsv_file_name);
+/// The cache in its default directory, `None` if it's not to be used.
+fn open_cache(no_cache: bool) -> Result<Option<Cache>> {
+    if no_cache {
+        return Ok(None);
+    }
 
-    let (students, mut schools, faculties) =
-        read_publication_tsv(publication_tsv_file_name.as_str());
+    Cache::new(Cache::default_directory()).map(Some)
+}
 
-    fs::remove_file(publication_tsv_file_name).unwrap();
+/// Reads the publication and the descaling data, estimating the scaling of
+/// subjects without anchors if asked to.
+fn read_inputs(
+    inputs: &Inputs,
+) -> Result<(Publication, [Option<SubjectStats>; ALL_SUBJECTS.len()])> {
+    let column_mapping = match &inputs.columns {
+        Some(columns) => read_column_mapping(columns)?,
+        None => ColumnMapping::default(),
+    };
+
+    let cache = open_cache(inputs.no_cache)?;
+    let publication = read_publication(&inputs.input_file, &column_mapping, cache.as_ref())?;
+
+    let mut independent_data = read_descaling_data(&inputs.descaling_data_file, &column_mapping)?;
+
+    if inputs.estimate_anchors {
+        println!("estimating scaling... ");
+        let fits = estimate_scaling(&publication.0, &independent_data)?;
+        for fit in &fits {
+            println!("\t{fit}");
+        }
+        independent_data = with_estimated_anchors(independent_data, &fits);
+        println!("done.");
+    }
+
+    Ok((publication, independent_data))
+}
+
+/// The configured weights where there are any, fitted to the published
+/// competitive scores elsewhere.
+fn read_faculty_weights(
+    weights_file: Option<&str>,
+    students: &[StudentData],
+    faculties: &HashMap<String, Faculty>,
+) -> Result<HashMap<String, FacultyWeights>> {
+    let configured = match weights_file {
+        Some(weights_file) => read_weights(weights_file)?,
+        None => HashMap::new(),
+    };
+
+    println!("fitting faculty weights... ");
+    let weights = faculty_weights(students, faculties, &configured);
+    println!("done.");
+
+    Ok(weights)
+}
+
+fn extract(input_file: &str, output_file: Option<String>, no_cache: bool) -> Result<()> {
+    let output_file = output_file.unwrap_or(format!("{}.tsv", file_stem(input_file)));
+
+    match open_cache(no_cache)? {
+        Some(cache) => {
+            let publication_tsv = cache.publication_tsv(input_file)?;
+
+            fs::copy(&publication_tsv, &output_file).map_err(io_error(&output_file))?;
+
+            Ok(())
+        }
+        None => parse_publication_pdf(input_file, &output_file),
+    }
+}
+
+fn descale(
+    inputs: &Inputs,
+    output_file: Option<String>,
+    rank_by: Ranking,
+    year: Option<u16>,
+) -> Result<()> {
+    let output_file =
+        output_file.unwrap_or(format!("{}-dataset.json", file_stem(&inputs.input_file)));
+
+    let (publication, independent_data) = read_inputs(inputs)?;
+
+    println!("descaling... ");
+    let mut dataset = Dataset::descale(
+        publication,
+        independent_data,
+        inputs.model,
+        year.or_else(|| year_from_file_name(&inputs.input_file)),
+    )?;
+    println!("done.");
+
+    if rank_by == Ranking::Equalized {
+        let weights = read_faculty_weights(
+            inputs.weights.as_deref(),
+            &dataset.students,
+            &dataset.faculties,
+        )?;
+
+        println!("ranking by equalized competitive scores... ");
+        dataset.students = sort_students_by(dataset.students, |s| {
+            weights.get(&s.faculty_id)?.competitive_score(s, true)
+        });
+        println!("done.");
+    }
+
+    println!("writing {output_file}... ");
+    dataset.write(&output_file)?;
+    println!("done.");
+
+    Ok(())
+}
+
+fn validate(inputs: &Inputs) -> Result<()> {
+    let ((students, schools, faculties), independent_data) = read_inputs(inputs)?;
+
+    println!(
+        "{} students, {} schools, {} faculties",
+        students.len(),
+        schools.len(),
+        faculties.len()
+    );
+
+    // fails on subjects the publication has scores of but the descaling data
+    // can't descale
+    descaling_curves(&students, &independent_data, inputs.model)?;
+
+    println!("validating anchors... ");
+    for validation in validate_anchors(&students, &independent_data, inputs.model)? {
+        println!("{validation}");
+    }
+    println!("done.");
+
+    let weights = read_faculty_weights(inputs.weights.as_deref(), &students, &faculties)?;
+    let sources = weights.values().counts_by(|w| w.source);
+    println!(
+        "weights of {} faculties: {} configured, {} fitted, {} uniform",
+        weights.len(),
+        sources.get(&WeightSource::Configured).unwrap_or(&0),
+        sources.get(&WeightSource::Fitted).unwrap_or(&0),
+        sources.get(&WeightSource::Uniform).unwrap_or(&0),
+    );
+    for faculty_weights in weights
+        .values()
+        .filter(|w| w.residual > CONSISTENCY_TOLERANCE)
+        .sorted_by(|a, b| f32::total_cmp(&b.residual, &a.residual))
+    {
+        println!("\t{faculty_weights}");
+    }
+
+    let inconsistent = inconsistent_scores(&students, &weights);
+    println!(
+        "{} published competitive scores differ from the recomputed ones by more than {CONSISTENCY_TOLERANCE:.1}",
+        inconsistent.len()
+    );
+    for (student, difference) in inconsistent {
+        println!(
+            "\t{} ({}): {} ({difference:+.1})",
+            student.id, student.faculty_id, student.overall_score
+        );
+    }
+
+    Ok(())
+}
 
-    let students = sort_students(descale_with_independent_data(
+fn simulate(
+    dataset_file: &str,
+    capacities: Option<String>,
+    preferences: Option<String>,
+    weights: Option<String>,
+) -> Result<()> {
+    let Dataset {
         students,
-        read_independent_descaling_data(&descaling_data_file),
+        faculties,
+        ..
+    } = Dataset::read(dataset_file)?;
+
+    let capacities = match capacities {
+        Some(capacities) => read_capacities(&capacities)?,
+        None => published_capacities(&students),
+    };
+    // without preferences every student only applies to the faculty they
+    // were admitted to, no one can gain a place elsewhere
+    let has_preferences = preferences.is_some();
+    let preferences = match preferences {
+        Some(preferences) => read_preferences(&preferences)?,
+        None => Preferences::new(),
+    };
+    let weights = read_faculty_weights(weights.as_deref(), &students, &faculties)?;
+
+    println!("simulating admissions... ");
+    let admissions = simulate_admissions(
+        &students,
+        &faculties,
+        &capacities,
+        &preferences,
+        |student, faculty| weights.get(&faculty.id)?.competitive_score(student, true),
+    );
+    println!("done.");
+
+    let changes = admissions.iter().counts_by(Admission::change);
+    let moved = admissions
+        .iter()
+        .filter(|a| a.simulated_faculty.as_ref() == Some(&a.published_faculty))
+        .map(|a| {
+            a.simulated_rank
+                .unwrap_or_default()
+                .abs_diff(a.published_rank)
+        })
+        .collect_vec();
+
+    if has_preferences {
+        println!(
+            "{} students: {} unchanged, {} gained a place, {} lost one",
+            admissions.len(),
+            changes.get(&PlacementChange::Unchanged).unwrap_or(&0),
+            changes.get(&PlacementChange::Gained).unwrap_or(&0),
+            changes.get(&PlacementChange::Lost).unwrap_or(&0),
+        );
+    } else {
+        println!(
+            "{} students, each applying only to the faculty they were admitted to: \
+            no one can gain a place without preferences (-p), {} lost theirs to the capacities",
+            admissions.len(),
+            changes.get(&PlacementChange::Lost).unwrap_or(&0),
+        );
+    }
+    println!(
+        "{} students kept their faculty but moved within it, by {:.1} places on average",
+        moved.iter().filter(|m| **m > 0).count(),
+        moved.iter().sum::<usize>() as f32 / moved.len().max(1) as f32,
+    );
+
+    for admission in admissions
+        .iter()
+        .filter(|a| a.change() != PlacementChange::Unchanged)
+    {
+        println!("\t{admission}");
+    }
+
+    Ok(())
+}
+
+fn grants(
+    dataset_file: &str,
+    rules_file: Option<String>,
+    subjects: &[Subject],
+    output_file: Option<String>,
+) -> Result<()> {
+    let output_file = output_file.unwrap_or(format!(
+        "{}-grants.csv",
+        file_stem(dataset_file)
+            .strip_suffix("-dataset")
+            .unwrap_or(&file_stem(dataset_file))
     ));
 
-    if shorten_names {
-        let school_short_names: HashMap<String, String> = {
-            let mut map = HashMap::new();
+    let Dataset {
+        students,
+        faculties,
+        ..
+    } = Dataset::read(dataset_file)?;
 
-            let mut reader = ReaderBuilder::new().from_reader(SCHOOLS_SHORT_NAMES_CSV.as_bytes());
+    let mut rules = GrantRules::published(&students);
+    if let Some(rules_file) = rules_file {
+        rules.limits = read_grant_limits(&rules_file)?;
+    }
+    if !subjects.is_empty() {
+        let mut counted = [false; ALL_SUBJECTS.len()];
+        for subject in subjects {
+            counted[*subject as usize] = true;
+        }
+        rules.subjects = Some(counted);
+    }
 
-            while !reader.is_done() {
-                let mut csv_line = StringRecord::new();
-                reader
-                    .read_record(&mut csv_line)
-                    .expect("error while reading independent data");
+    println!("simulating grants... ");
+    let allocations = simulate_grants(&students, &faculties, &rules);
+    println!("done.");
 
-                if csv_line.len() != 2 {
-                    continue;
-                }
+    let changed = allocations
+        .iter()
+        .filter(|a| a.published != a.simulated)
+        .collect_vec();
 
-                let csv_line = csv_line.iter().collect_vec();
+    println!(
+        "{} students: {} ({} changed)",
+        allocations.len(),
+        grant_totals(&allocations, |_| "")[""],
+        changed.len(),
+    );
 
-                map.insert(String::from(csv_line[0]), String::from(csv_line[1]));
-            }
+    println!("schools:");
+    for (school_id, totals) in grant_totals(&allocations, |a| school_id(&a.faculty_id))
+        .iter()
+        .filter(|(_, totals)| totals.published != totals.simulated)
+    {
+        println!("\t{school_id}: {totals}");
+    }
 
-            map
-        };
+    println!("faculties:");
+    for (faculty_id, totals) in grant_totals(&allocations, |a| &a.faculty_id)
+        .iter()
+        .filter(|(_, totals)| totals.published != totals.simulated)
+    {
+        println!("\t{faculty_id}: {totals}");
+    }
+
+    println!("writing {output_file}... ");
+    write_grant_allocations(&output_file, &allocations)?;
+    println!("done.");
+
+    Ok(())
+}
 
-        for (id, short_name) in school_short_names {
+fn query(
+    dataset_file: &str,
+    subject: Subject,
+    score: f32,
+    scaled: bool,
+    descaling_data_file: Option<String>,
+) -> Result<()> {
+    let Dataset {
+        model,
+        students,
+        schools,
+        faculties,
+        ..
+    } = Dataset::read(dataset_file)?;
+
+    // --scaled requires the descaling data
+    let score = match descaling_data_file.filter(|_| scaled) {
+        Some(descaling_data_file) => {
+            let independent_data =
+                read_descaling_data(&descaling_data_file, &ColumnMapping::default())?;
+            estimate_equalized(&students, &independent_data, model, subject, score)?
+        }
+        None => Score::Equalized(score),
+    };
+
+    let standing = score_standing(&students, &faculties, subject, score);
+
+    match score {
+        Score::EqualizedAndScaled {
+            scaled,
+            equalized,
+            uncertainty,
+        } => {
+            println!("{subject}: scaled {scaled:.1} -> equalized {equalized:.1} ± {uncertainty:.2}")
+        }
+        _ => println!("{subject}: equalized {:.1}", score.sort_key()),
+    }
+
+    let optional = |value: Option<f32>| {
+        value
+            .map(|v| format!("{v:.1}"))
+            .unwrap_or(String::from("-"))
+    };
+    println!(
+        "percentile: scaled {}, equalized {}",
+        optional(standing.scaled_percentile),
+        optional(standing.equalized_percentile)
+    );
+
+    let describe = |faculty: &FacultyStanding| {
+        let school = schools
+            .get(school_id(&faculty.faculty_id))
+            .map(|s| s.short_name.clone().unwrap_or(s.name.clone()))
+            .unwrap_or_default();
+        let name = faculties
+            .get(&faculty.faculty_id)
+            .map(|f| f.name.as_str())
+            .unwrap_or_default();
+
+        format!(
+            "{} {name} ({school}): cutoff {:.1}, median {:.1}, percentile {:.1} of {}",
+            faculty.faculty_id,
+            faculty.cutoff,
+            faculty.median,
+            faculty.percentile,
+            faculty.admitted
+        )
+    };
+
+    let competitive = standing.competitive().collect_vec();
+    println!(
+        "competitive in {} of {} faculties counting {subject}:",
+        competitive.len(),
+        standing.faculties.len()
+    );
+    for faculty in competitive {
+        println!("\t{}", describe(faculty));
+    }
+
+    let borderline = standing.borderline().collect_vec();
+    if !borderline.is_empty() {
+        println!("within the uncertainty of {} more:", borderline.len());
+        for faculty in borderline {
+            println!("\t{}", describe(faculty));
+        }
+    }
+
+    Ok(())
+}
+
+fn compare(args: CompareArgs, lang: Lang) -> Result<()> {
+    let CompareArgs {
+        inputs,
+        model,
+        output_file,
+        backend,
+        templates,
+        no_cache,
+    } = args;
+
+    let cache = open_cache(no_cache)?;
+    let datasets = read_datasets(&inputs, model, cache.as_ref())?;
+
+    println!("comparing {}... ", datasets.keys().join(", "));
+    let comparisons = compare_years(&datasets);
+    println!("done.");
+
+    let (subjects, faculties): (Vec<_>, Vec<_>) =
+        comparisons.iter().partition(|c| c.faculty_id.is_none());
+
+    for comparison in subjects {
+        println!("{comparison}");
+    }
+    println!(
+        "{} faculties, {} of them in every year",
+        faculties.iter().map(|c| &c.faculty_id).unique().count(),
+        faculties
+            .iter()
+            .filter(|c| c.years.len() == datasets.len())
+            .map(|c| &c.faculty_id)
+            .unique()
+            .count()
+    );
+
+    println!("writing {output_file}... ");
+    write_comparisons(&output_file, &comparisons)?;
+    println!("done.");
+
+    let Some(backend) = backend else {
+        return Ok(());
+    };
+    let output_name = file_stem(&output_file);
+
+    if backend == Backend::Html {
+        HTMLMaker::new(output_name)
+            .lang(lang)
+            .write_comparisons(&comparisons)
+            .save()?;
+
+        return Ok(());
+    }
+
+    PDFMaker::new(format!("{output_name}-work-directory"), output_name)?
+        .backend(backend)
+        .templates(Templates::new(templates.as_deref())?)
+        .lang(lang)
+        .cache(cache)
+        .write_comparisons(&comparisons)?
+        .save()?
+        .compile()?;
+
+    Ok(())
+}
+
+fn report(args: ReportArgs, lang: Lang) -> Result<()> {
+    let ReportArgs {
+        dataset_file,
+        work_path,
+        graphs,
+        top_list,
+        faculties,
+        summary,
+        schools: include_schools,
+        distributions: include_distributions,
+        correlations: include_correlations,
+        shorten_names,
+        uncertainty,
+        export,
+        export_faculties: export_by_faculty,
+        backend,
+        templates,
+        no_cache,
+        keep_work_dir,
+    } = args;
+    let include_faculties = faculties;
+
+    let dataset_name = file_stem(&dataset_file);
+    let input_name = dataset_name
+        .strip_suffix("-dataset")
+        .unwrap_or(&dataset_name)
+        .to_string();
+    let work_path = work_path.unwrap_or(format!("{input_name}-work-directory"));
+
+    let Dataset {
+        model,
+        students,
+        mut schools,
+        faculties,
+        ..
+    } = Dataset::read(&dataset_file)?;
+
+    if shorten_names {
+        for (id, short_name) in read_school_short_names()? {
             if let Some(school) = schools.get_mut(&id.clone()) {
                 school.short_name = Some(short_name);
             }
@@ -492,65 +1785,269 @@ fn main() {
     let faculty_buckets = collect_faculties(students.clone());
     let faculty_buckets = faculty_buckets
         .iter()
-        .sorted_by(|a, b| {
-            f32::partial_cmp(&a.0.parse::<f32>().unwrap(), &b.0.parse::<f32>().unwrap()).unwrap()
-        })
+        // ids have up to eleven digits, too many for an f32 to tell apart
+        .sorted_by_key(|(id, _)| (id.parse::<u64>().unwrap_or(u64::MAX), *id))
         .map(|(id, students)| (id, students.get(0..students.len()).unwrap()))
         .collect_vec();
 
+    let has_sections = top_list
+        || summary
+        || include_schools
+        || include_distributions
+        || include_correlations
+        || include_faculties;
+
     // WRITE OUT
 
-    let work_path = work_path;
+    if let Some(format) = export {
+        let output_name = format!("{input_name}-out-descaled");
+
+        if export_by_faculty {
+            let directory = format!("{output_name}-faculties");
+            println!("exporting faculties to {directory}... ");
+            export_faculties(&directory, format, &faculty_buckets, &schools, &faculties)?;
+        } else {
+            let file_name = format!("{output_name}.{}", format.extension());
+            println!("exporting to {file_name}... ");
+            export_students(&file_name, format, &students, &schools, &faculties)?;
+        }
+        println!("done.");
+
+        if !has_sections {
+            return Ok(());
+        }
+    }
+
+    let summaries = summary.then(|| summarize_faculties(&faculty_buckets, &faculties));
+
+    if let Some(summaries) = &summaries {
+        let file_name = format!("{input_name}-out-faculty-summary.csv");
+        println!("writing {file_name}... ");
+        write_faculty_summaries(&file_name, summaries, &schools)?;
+        println!("done.");
+    }
+
+    let school_summaries = include_schools.then(|| summarize_schools(&students));
+
+    if let Some(school_summaries) = &school_summaries {
+        let file_name = format!("{input_name}-out-school-summary.csv");
+        println!("writing {file_name}... ");
+        write_school_summaries(&file_name, school_summaries, &schools)?;
+        println!("done.");
+    }
+
+    let analysis = include_correlations.then(|| {
+        (
+            subject_correlations(&students),
+            subject_combinations(&students, &faculties),
+        )
+    });
+
+    if let Some((correlations, combinations)) = &analysis {
+        let file_name = format!("{input_name}-out-correlations.json");
+        println!("writing {file_name}... ");
+        write_subject_analysis(&file_name, correlations, combinations)?;
+        println!("done.");
+    }
+
+    let output_file = input_name
+        + [
+            Some("-out"),
+            if has_sections { Some("descaled") } else { None },
+            if has_sections && model == DescalingModel::MonotoneCubic {
+                Some("monotone-cubic")
+            } else {
+                None
+            },
+            if top_list { Some("top-list") } else { None },
+            if top_list && shorten_names {
+                Some("with-shortened-names")
+            } else {
+                None
+            },
+            if summary { Some("summary") } else { None },
+            if include_schools {
+                Some("schools")
+            } else {
+                None
+            },
+            if include_distributions {
+                Some("distributions")
+            } else {
+                None
+            },
+            if include_correlations {
+                Some("correlations")
+            } else {
+                None
+            },
+            if (top_list
+                || summary
+                || include_schools
+                || include_distributions
+                || include_correlations)
+                && include_faculties
+            {
+                Some("and")
+            } else {
+                None
+            },
+            if include_faculties {
+                Some("faculties")
+            } else {
+                None
+            },
+            if (include_faculties || include_distributions || include_correlations) && graphs {
+                Some("with-graphs")
+            } else {
+                None
+            },
+            // the georgian book keeps the names it always had
+            if has_sections && lang != Lang::Ka {
+                Some(lang.catalog().code)
+            } else {
+                None
+            },
+        ]
+        .iter()
+        .filter_map(|&a| a)
+        .join("-")
+        .as_str();
+
+    if backend == Backend::Html {
+        let mut html_out = &mut HTMLMaker::new(output_file);
+
+        html_out = html_out.show_uncertainty(uncertainty).lang(lang);
+
+        if top_list {
+            html_out = html_out.write_top_list(&students[..], &schools, &faculties);
+        }
+
+        if let Some(summaries) = &summaries {
+            html_out = html_out.write_summary(summaries, &schools);
+        }
+
+        if let Some(school_summaries) = &school_summaries {
+            html_out = html_out.write_schools(school_summaries, &schools);
+        }
+
+        if include_distributions {
+            html_out = html_out.write_distributions(&subject_distributions(&students), graphs);
+        }
+
+        if let Some((correlations, combinations)) = &analysis {
+            html_out = html_out.write_correlations(&students, correlations, combinations, graphs);
+        }
+
+        if include_faculties {
+            if graphs {
+                html_out = html_out.write_graphs(&faculty_buckets, &faculties)?;
+            }
+
+            html_out = html_out.write_faculties(&faculty_buckets, &schools, &faculties);
+        }
+
+        html_out.save()?;
+
+        return Ok(());
+    }
 
     // Compile the PDF
 
-    let mut pdf_out = &mut PDFMaker::new(
-        work_path,
-        input_file.replace(
-            ".pdf",
-            [
-                Some("-out"),
-                if top_list || include_faculties {
-                    Some("descaled")
-                } else {
-                    None
-                },
-                if top_list { Some("top-list") } else { None },
-                if top_list && shorten_names { Some("with-shortened-names") } else { None },
-                if top_list && include_faculties {
-                    Some("and")
-                } else {
-                    None
-                },
-                if include_faculties {
-                    Some("faculties")
-                } else {
-                    None
-                },
-                if include_faculties && graphs {
-                    Some("with-graphs")
-                } else {
-                    None
-                },
-            ]
-            .iter()
-            .filter_map(|&a| a)
-            .join("-")
-            .as_str(),
-        ),
-    );
+    let mut pdf_out = &mut PDFMaker::new(work_path, output_file)?;
+
+    pdf_out = pdf_out
+        .show_uncertainty(uncertainty)
+        .backend(backend)
+        .templates(Templates::new(templates.as_deref())?)
+        .lang(lang)
+        .cache(open_cache(no_cache)?)
+        .keep_work_dir(keep_work_dir);
 
     if top_list {
-        pdf_out = pdf_out.write_top_list(&students[..], &schools, &faculties);
+        pdf_out = pdf_out.write_top_list(&students[..], &schools, &faculties)?;
+    }
+
+    if let Some(summaries) = &summaries {
+        pdf_out = pdf_out.write_summary(summaries, &schools)?;
+    }
+
+    if let Some(school_summaries) = &school_summaries {
+        pdf_out = pdf_out.write_schools(school_summaries, &schools)?;
+    }
+
+    if include_distributions {
+        pdf_out = pdf_out.write_distributions(&subject_distributions(&students), graphs)?;
+    }
+
+    if let Some((correlations, combinations)) = &analysis {
+        pdf_out = pdf_out.write_correlations(&students, correlations, combinations, graphs)?;
     }
 
     if include_faculties {
         if graphs {
-            pdf_out = pdf_out.write_graphs(&faculty_buckets, &faculties);
+            pdf_out = pdf_out.write_graphs(&faculty_buckets, &faculties)?;
         }
 
-        pdf_out = pdf_out.write_faculties(&faculty_buckets, &schools, &faculties);
+        pdf_out = pdf_out.write_faculties(&faculty_buckets, &schools, &faculties)?;
+    }
+
+    pdf_out.save()?.compile()?;
+
+    Ok(())
+}
+
+fn run() -> Result<()> {
+    let Cli { command, lang } = Cli::parse();
+    lang.set_console();
+
+    match command {
+        Commands::Extract {
+            input_file,
+            output_file,
+            no_cache,
+        } => extract(&input_file, output_file, no_cache),
+        Commands::Descale {
+            inputs,
+            output_file,
+            rank_by,
+            year,
+        } => descale(&inputs, output_file, rank_by, year),
+        Commands::Report(args) => report(args, lang),
+        Commands::Validate { inputs } => validate(&inputs),
+        Commands::Simulate {
+            dataset_file,
+            capacities,
+            preferences,
+            weights,
+        } => simulate(&dataset_file, capacities, preferences, weights),
+        Commands::Grants {
+            dataset_file,
+            rules,
+            subjects,
+            output_file,
+        } => grants(&dataset_file, rules, &subjects, output_file),
+        Commands::Query {
+            dataset_file,
+            subject,
+            score,
+            scaled,
+            descaling_data_file,
+        } => query(&dataset_file, subject, score, scaled, descaling_data_file),
+        Commands::Compare(args) => compare(args, lang),
+        Commands::Templates { directory } => {
+            println!("writing the templates to {directory}... ");
+            write_default_templates(&directory)?;
+            println!("done.");
+
+            Ok(())
+        }
     }
+}
 
-    pdf_out.save().unwrap().compile();
+fn main() {
+    if let Err(error) = run() {
+        eprintln!("error: {error}");
+        std::process::exit(1);
+    }
 }
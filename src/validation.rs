@@ -0,0 +1,283 @@
+use std::fmt;
+
+use itertools::Itertools;
+
+use crate::descaling::DescalingModel;
+use crate::error::Result;
+use crate::parsing::*;
+use crate::processing::descaling_curves;
+
+/// Errors up to this many raw points are never flagged, however well the
+/// other anchors of the subject fit.
+const OUTLIER_MIN_ERROR: f32 = 1.0;
+/// An anchor is flagged if it's off by more than this many times the mean
+/// absolute error of the other anchors of its subject.
+const OUTLIER_FACTOR: f32 = 3.0;
+
+/// An anchor and the equalized score the curve through the other anchors
+/// predicts for it.
+#[derive(Debug, Clone, Copy)]
+pub struct AnchorPrediction {
+    pub scaled: f32,
+    pub equalized: f32,
+    pub predicted: f32,
+    /// Off by much more than the other anchors of the subject.
+    pub outlier: bool,
+    /// Has a lower equalized score than an anchor with a lower scaled score,
+    /// or the other way around. Usually a typo.
+    pub out_of_order: bool,
+}
+
+impl AnchorPrediction {
+    /// Predicted minus actual equalized score.
+    pub fn error(&self) -> f32 {
+        self.predicted - self.equalized
+    }
+
+    pub fn is_suspicious(&self) -> bool {
+        self.outlier || self.out_of_order
+    }
+}
+
+/// Leave-one-out errors of the anchors of a single subject.
+#[derive(Debug, Clone)]
+pub struct AnchorValidation {
+    pub subject: Subject,
+    pub predictions: Vec<AnchorPrediction>,
+}
+
+impl AnchorValidation {
+    /// Mean absolute error.
+    pub fn mean_absolute_error(&self) -> f32 {
+        mean(self.predictions.iter().map(|p| p.error().abs()))
+    }
+
+    pub fn max_error(&self) -> f32 {
+        self.predictions
+            .iter()
+            .map(|p| p.error().abs())
+            .fold(0.0, f32::max)
+    }
+
+    /// Mean error, positive if the curve tends to overestimate.
+    pub fn bias(&self) -> f32 {
+        mean(self.predictions.iter().map(AnchorPrediction::error))
+    }
+}
+
+impl fmt::Display for AnchorValidation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} anchors, MAE {:.2}, max error {:.2}, bias {:+.2}",
+            self.subject,
+            self.predictions.len(),
+            self.mean_absolute_error(),
+            self.max_error(),
+            self.bias(),
+        )?;
+
+        for prediction in &self.predictions {
+            write!(
+                f,
+                "\n\t{:.1} -> {:.2}, predicted {:.2} ({:+.2})",
+                prediction.scaled,
+                prediction.equalized,
+                prediction.predicted,
+                prediction.error(),
+            )?;
+            if prediction.outlier {
+                write!(f, " <- outlier")?;
+            }
+            if prediction.out_of_order {
+                write!(f, " <- out of order")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn mean(values: impl Iterator<Item = f32>) -> f32 {
+    let (sum, count) = values.fold((0.0, 0), |(sum, count), x| (sum + x, count + 1));
+
+    if count == 0 {
+        0.0
+    } else {
+        sum / count as f32
+    }
+}
+
+fn scaled_and_equalized(score: &Score) -> Option<(f32, f32)> {
+    match *score {
+        Score::EqualizedAndScaled {
+            scaled, equalized, ..
+        } => Some((scaled, equalized)),
+        _ => None,
+    }
+}
+
+/// Holds out every anchor of the descaling data in turn, descales its scaled
+/// score with the rest and compares the result with its equalized score.
+/// Subjects without anchors are left out.
+pub fn validate_anchors(
+    students: &[StudentData],
+    independent_data: &[Option<SubjectStats>; ALL_SUBJECTS.len()],
+    model: DescalingModel,
+) -> Result<Vec<AnchorValidation>> {
+    let mut validations = Vec::new();
+
+    for (subject_index, subject) in ALL_SUBJECTS.iter().enumerate() {
+        let Some(stats) = &independent_data[subject_index] else {
+            continue;
+        };
+
+        let mut predictions = Vec::new();
+
+        for (anchor_index, anchor) in stats.anchors.iter().enumerate() {
+            let Some((scaled, equalized)) = scaled_and_equalized(anchor) else {
+                continue;
+            };
+
+            let mut held_out = independent_data.clone();
+            if let Some(stats) = &mut held_out[subject_index] {
+                stats.anchors.remove(anchor_index);
+            }
+
+            let curves = descaling_curves(students, &held_out, model)?;
+            let Some(curve) = &curves[subject_index] else {
+                continue;
+            };
+
+            let out_of_order = stats
+                .anchors
+                .iter()
+                .filter_map(scaled_and_equalized)
+                .any(|(s, e)| (s < scaled && e > equalized) || (s > scaled && e < equalized));
+
+            predictions.push(AnchorPrediction {
+                scaled,
+                equalized,
+                predicted: curve.equalize(scaled),
+                outlier: false,
+                out_of_order,
+            });
+        }
+
+        if predictions.is_empty() {
+            continue;
+        }
+
+        let errors = predictions.iter().map(|p| p.error().abs()).collect_vec();
+        for (i, prediction) in predictions.iter_mut().enumerate() {
+            let others = mean(
+                errors
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, _)| *j != i)
+                    .map(|(_, e)| *e),
+            );
+
+            prediction.outlier = errors[i] > OUTLIER_MIN_ERROR.max(OUTLIER_FACTOR * others);
+        }
+
+        predictions.sort_by(|a, b| f32::total_cmp(&a.scaled, &b.scaled));
+
+        validations.push(AnchorValidation {
+            subject: *subject,
+            predictions,
+        });
+    }
+
+    Ok(validations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Math scores of 0 to 50 scaled to 100 to 200, with anchors on that line
+    /// every 10 scaled points but 150, which is anchored at `anchor_150`.
+    fn validate(anchor_150: f32) -> AnchorValidation {
+        let students = [100.0, 200.0].map(|scaled| {
+            let mut scores = [None; ALL_SUBJECTS.len()];
+            scores[Subject::Math as usize] = Some(Score::Scaled(scaled));
+
+            StudentData {
+                id: String::new(),
+                scores,
+                overall_score: String::new(),
+                placement: None,
+                faculty_id: String::new(),
+                grant: None,
+            }
+        });
+
+        let anchor = |scaled: f32, equalized: f32| Score::EqualizedAndScaled {
+            scaled,
+            equalized,
+            uncertainty: 0.0,
+        };
+        let mut independent_data: [Option<SubjectStats>; ALL_SUBJECTS.len()] = Default::default();
+        independent_data[Subject::Math as usize] = Some(SubjectStats {
+            min: Some(Score::Equalized(0.0)),
+            max: Some(Score::Equalized(50.0)),
+            anchors: [110.0, 120.0, 130.0, 140.0, 160.0, 170.0, 180.0, 190.0]
+                .map(|scaled| anchor(scaled, (scaled - 100.0) / 2.0))
+                .into_iter()
+                .chain([anchor(150.0, anchor_150)])
+                .collect_vec(),
+            ..SubjectStats::default()
+        });
+
+        validate_anchors(
+            &students,
+            &independent_data,
+            DescalingModel::PiecewiseLinear,
+        )
+        .unwrap()
+        .into_iter()
+        .exactly_one()
+        .unwrap()
+    }
+
+    fn flagged(validation: &AnchorValidation, flag: fn(&AnchorPrediction) -> bool) -> Vec<f32> {
+        validation
+            .predictions
+            .iter()
+            .filter(|p| flag(p))
+            .map(|p| p.scaled)
+            .collect_vec()
+    }
+
+    #[test]
+    fn typos_are_flagged() {
+        // 5 instead of 25
+        let validation = validate(5.0);
+
+        // held out, the typo is predicted at 25, and it pulls the predictions
+        // of 140 and 160 off by 10: 20 > 3 * 20 / 8 but 10 < 3 * 30 / 8
+        let typo = validation.predictions[4];
+        assert_eq!(typo.scaled, 150.0);
+        assert_eq!(typo.predicted, 25.0);
+        assert_eq!(flagged(&validation, |p| p.outlier), [150.0]);
+
+        // it's below the anchors of 110 (5, a tie) to 140
+        assert_eq!(
+            flagged(&validation, |p| p.out_of_order),
+            [120.0, 130.0, 140.0, 150.0]
+        );
+        assert_eq!(validation.max_error(), 20.0);
+    }
+
+    #[test]
+    fn small_errors_are_not_flagged() {
+        // off by half a point, 3 times the others' mean error but under 1
+        let validation = validate(24.5);
+        assert!(validation.predictions.iter().all(|p| !p.is_suspicious()));
+
+        let validation = validate(25.0);
+        assert_eq!(validation.mean_absolute_error(), 0.0);
+        assert!(validation.predictions.iter().all(|p| !p.is_suspicious()));
+    }
+}